@@ -0,0 +1,433 @@
+use std::cmp::Ordering;
+
+use super::{
+    super::{
+        error::{evaluation_error, indicator, instantiation_error, type_error},
+        flags::Flags,
+        rational::{Exact, Rational},
+    },
+    Builtin, Runner, Term,
+};
+
+// The numeric tower: integers widen to rationals, and anything mixed with a
+// float becomes a float.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Number {
+    Int(i64),
+    Rational(Rational),
+    Float(f64),
+}
+
+use Number::{Float, Int};
+
+impl Number {
+    pub fn to_term(self) -> Term {
+        match self {
+            Int(n) => Term::Int(n),
+            Number::Rational(r) => Term::Rational(r),
+            Float(n) => Term::Float(n),
+        }
+    }
+
+    fn to_f64(self) -> f64 {
+        match self {
+            Int(n) => n as f64,
+            Number::Rational(r) => r.to_f64(),
+            Float(n) => n,
+        }
+    }
+
+    fn int(self) -> Result<i64, Term> {
+        match self {
+            Int(n) => Ok(n),
+            _ => Err(type_error("integer", self.to_term())),
+        }
+    }
+
+    fn exact(self) -> Option<Rational> {
+        match self {
+            Int(n) => Some(Rational::from_int(n)),
+            Number::Rational(r) => Some(r),
+            Float(_) => None,
+        }
+    }
+
+    fn rational(self) -> Result<Rational, Term> {
+        self.exact().ok_or_else(|| type_error("rational", self.to_term()))
+    }
+}
+
+fn exact(result: Option<Exact>) -> Result<Number, Term> {
+    match result {
+        Some(Exact::Int(n)) => Ok(Int(n)),
+        Some(Exact::Rational(r)) => Ok(Number::Rational(r)),
+        None => Err(overflow()),
+    }
+}
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    let builtin: Builtin = match (name, arity) {
+        ("is", 2) => is,
+        ("=:=", 2) => |runner, args| compare(runner, args, |o| o == Ordering::Equal),
+        ("=\\=", 2) => |runner, args| compare(runner, args, |o| o != Ordering::Equal),
+        ("<", 2) => |runner, args| compare(runner, args, |o| o == Ordering::Less),
+        (">", 2) => |runner, args| compare(runner, args, |o| o == Ordering::Greater),
+        ("=<", 2) => |runner, args| compare(runner, args, |o| o != Ordering::Greater),
+        (">=", 2) => |runner, args| compare(runner, args, |o| o != Ordering::Less),
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+fn is(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let value = eval(&args[1], &runner.flags)?.to_term();
+    Ok(runner.unify(&args[0], &value))
+}
+
+fn compare(runner: &mut Runner, args: &[Term], test: fn(Ordering) -> bool) -> Result<bool, Term> {
+    let lhs = eval(&args[0], &runner.flags)?;
+    let rhs = eval(&args[1], &runner.flags)?;
+    Ok(num_cmp(lhs, rhs).is_some_and(test))
+}
+
+pub fn num_cmp(lhs: Number, rhs: Number) -> Option<Ordering> {
+    match (lhs, rhs) {
+        (Int(l), Int(r)) => Some(l.cmp(&r)),
+        (Float(_), _) | (_, Float(_)) => lhs.to_f64().partial_cmp(&rhs.to_f64()),
+        (l, r) => Some(l.exact()?.cmp(r.exact()?)),
+    }
+}
+
+pub fn eval(term: &Term, flags: &Flags) -> Result<Number, Term> {
+    match term.deref() {
+        Term::Var(_) => Err(instantiation_error()),
+        Term::Int(n) => Ok(Int(n)),
+        Term::Rational(r) => Ok(Number::Rational(r)),
+        Term::Float(n) => Ok(Float(n)),
+        Term::Atom(name) => eval_constant(&name),
+        // a one character string evaluates to its code
+        Term::Str(text) => {
+            let mut chars = text.chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch), None) => Ok(Int(ch as i64)),
+                _ => Err(type_error("evaluable", Term::Str(text))),
+            }
+        }
+        Term::Compound(c) => match c.args.len() {
+            1 => eval_unary(&c.name, eval(&c.args[0], flags)?),
+            2 => eval_binary(&c.name, eval(&c.args[0], flags)?, eval(&c.args[1], flags)?, flags),
+            n => Err(type_error("evaluable", indicator(&c.name, n))),
+        },
+        dict @ Term::Dict(_) => Err(type_error("evaluable", dict)),
+    }
+}
+
+fn eval_constant(name: &str) -> Result<Number, Term> {
+    Ok(match name {
+        "pi" => Float(std::f64::consts::PI),
+        "e" => Float(std::f64::consts::E),
+        "inf" | "infinite" => Float(f64::INFINITY),
+        "nan" => Float(f64::NAN),
+        "epsilon" => Float(f64::EPSILON),
+        "max_tagged_integer" => Int(i64::MAX),
+        "min_tagged_integer" => Int(i64::MIN),
+        _ => return Err(type_error("evaluable", indicator(name, 0))),
+    })
+}
+
+fn overflow() -> Term {
+    evaluation_error("int_overflow")
+}
+
+// rejects results that left the finite floats
+fn float(n: f64) -> Result<Number, Term> {
+    if n.is_nan() {
+        Err(evaluation_error("undefined"))
+    } else if n.is_infinite() {
+        Err(evaluation_error("float_overflow"))
+    } else {
+        Ok(Float(n))
+    }
+}
+
+// a float function that is only defined where `domain` holds
+fn partial(x: Number, domain: fn(f64) -> bool, f: fn(f64) -> f64) -> Result<Number, Term> {
+    let x = x.to_f64();
+    if !domain(x) {
+        return Err(evaluation_error("undefined"));
+    }
+    float(f(x))
+}
+
+fn to_int(n: f64) -> Result<Number, Term> {
+    if n.is_nan() {
+        return Err(evaluation_error("undefined"));
+    }
+    if n < i64::MIN as f64 || n >= i64::MAX as f64 {
+        return Err(overflow());
+    }
+    Ok(Int(n as i64))
+}
+
+fn eval_unary(name: &str, x: Number) -> Result<Number, Term> {
+    match name {
+        "-" => match x {
+            Int(n) => n.checked_neg().map(Int).ok_or_else(overflow),
+            Number::Rational(r) => exact(Rational::from_int(0).sub(r)),
+            Float(n) => Ok(Float(-n)),
+        },
+        "+" => Ok(x),
+        "abs" => match x {
+            Int(n) => n.checked_abs().map(Int).ok_or_else(overflow),
+            Number::Rational(r) if r.numerator() < 0 => eval_unary("-", x),
+            Number::Rational(_) => Ok(x),
+            Float(n) => Ok(Float(n.abs())),
+        },
+        "sign" => Ok(match x {
+            Int(n) => Int(n.signum()),
+            Number::Rational(r) => Int(r.numerator().signum()),
+            Float(n) => Float(if n == 0.0 { 0.0 } else { n.signum() }),
+        }),
+        "numerator" => Ok(Int(x.rational()?.numerator())),
+        "denominator" => Ok(Int(x.rational()?.denominator())),
+        "rational" | "rationalize" => match x {
+            Float(n) if !n.is_finite() => Err(evaluation_error("undefined")),
+            Float(n) if name == "rational" => exact(Rational::from_f64(n)),
+            Float(n) => exact(Rational::rationalize(n)),
+            _ => Ok(x),
+        },
+        "\\" => Ok(Int(!x.int()?)),
+        "msb" => {
+            let n = x.int()?;
+            if n <= 0 {
+                return Err(type_error("not_less_than_one", Term::Int(n)));
+            }
+            Ok(Int(63 - n.leading_zeros() as i64))
+        }
+        "sqrt" => partial(x, |x| x >= 0.0, f64::sqrt),
+        "sin" => float(x.to_f64().sin()),
+        "cos" => float(x.to_f64().cos()),
+        "tan" => float(x.to_f64().tan()),
+        "asin" => partial(x, |x| (-1.0..=1.0).contains(&x), f64::asin),
+        "acos" => partial(x, |x| (-1.0..=1.0).contains(&x), f64::acos),
+        "atan" => float(x.to_f64().atan()),
+        "sinh" => float(x.to_f64().sinh()),
+        "cosh" => float(x.to_f64().cosh()),
+        "tanh" => float(x.to_f64().tanh()),
+        "asinh" => float(x.to_f64().asinh()),
+        "acosh" => partial(x, |x| x >= 1.0, f64::acosh),
+        "atanh" => partial(x, |x| x > -1.0 && x < 1.0, f64::atanh),
+        "exp" => float(x.to_f64().exp()),
+        "log" => partial(x, |x| x > 0.0, f64::ln),
+        "log2" => partial(x, |x| x > 0.0, f64::log2),
+        "float" => Ok(Float(x.to_f64())),
+        "integer" => eval_unary("round", x),
+        "float_integer_part" => Ok(Float(x.to_f64().trunc())),
+        "float_fractional_part" => Ok(Float(x.to_f64().fract())),
+        "truncate" | "round" | "ceiling" | "floor" => match x {
+            Int(_) => Ok(x),
+            Number::Rational(r) => {
+                // the denominator is above one, so `r` lies strictly between
+                // `floor` and `floor + 1`
+                let (n, d) = (r.numerator() as i128, r.denominator() as i128);
+                let floor = n.div_euclid(d) as i64;
+                let twice_remainder = 2 * n.rem_euclid(d);
+                Ok(Int(match name {
+                    "floor" => floor,
+                    "truncate" if n > 0 => floor,
+                    "round" if twice_remainder < d => floor,
+                    "round" if twice_remainder == d && n < 0 => floor,
+                    _ => floor + 1,
+                }))
+            }
+            Float(n) => to_int(match name {
+                "truncate" => n.trunc(),
+                "round" => n.round(),
+                "ceiling" => n.ceil(),
+                _ => n.floor(),
+            }),
+        },
+        _ => Err(type_error("evaluable", indicator(name, 1))),
+    }
+}
+
+pub fn eval_binary(name: &str, x: Number, y: Number, flags: &Flags) -> Result<Number, Term> {
+    match name {
+        "+" | "-" | "*" => {
+            if let (Int(a), Int(b)) = (x, y) {
+                let result = match name {
+                    "+" => a.checked_add(b),
+                    "-" => a.checked_sub(b),
+                    _ => a.checked_mul(b),
+                };
+                return result.map(Int).ok_or_else(overflow);
+            }
+            if let (Some(a), Some(b)) = (x.exact(), y.exact()) {
+                return exact(match name {
+                    "+" => a.add(b),
+                    "-" => a.sub(b),
+                    _ => a.mul(b),
+                });
+            }
+            let (a, b) = (x.to_f64(), y.to_f64());
+            float(match name {
+                "+" => a + b,
+                "-" => a - b,
+                _ => a * b,
+            })
+        }
+        "/" => match (x, y) {
+            (_, Int(0)) => Err(evaluation_error("zero_divisor")),
+            (Int(a), Int(b)) if a.checked_rem(b) == Some(0) => {
+                a.checked_div(b).map(Int).ok_or_else(overflow)
+            }
+            (Int(_), Int(_)) if !flags.prefer_rationals => float(x.to_f64() / y.to_f64()),
+            (Float(_), _) | (_, Float(_)) => {
+                if y.to_f64() == 0.0 {
+                    return Err(evaluation_error("zero_divisor"));
+                }
+                float(x.to_f64() / y.to_f64())
+            }
+            _ => eval_binary("rdiv", x, y, flags),
+        },
+        "rdiv" => {
+            let (a, b) = (x.rational()?, y.rational()?);
+            if b.numerator() == 0 {
+                return Err(evaluation_error("zero_divisor"));
+            }
+            exact(a.div(b))
+        }
+        "//" | "mod" | "rem" | "div" => {
+            let (a, b) = (x.int()?, y.int()?);
+            if b == 0 {
+                return Err(evaluation_error("zero_divisor"));
+            }
+            let result = match name {
+                "//" => a.checked_div(b),
+                // only the quotient of the smallest integer by -1 overflows;
+                // the remainder is 0
+                "rem" => Some(a.wrapping_rem(b)),
+                "mod" => {
+                    let m = a.wrapping_rem(b);
+                    Some(if m != 0 && (m < 0) != (b < 0) { m + b } else { m })
+                }
+                _ => floor_div(a, b),
+            };
+            result.map(Int).ok_or_else(overflow)
+        }
+        "min" | "max" => {
+            let ordering = num_cmp(x, y).unwrap_or(Ordering::Equal);
+            let pick_x = match name {
+                "min" => ordering != Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            };
+            Ok(if pick_x { x } else { y })
+        }
+        "gcd" => {
+            let (mut a, mut b) = (x.int()?.unsigned_abs(), y.int()?.unsigned_abs());
+            while b != 0 {
+                (a, b) = (b, a % b);
+            }
+            i64::try_from(a).map(Int).map_err(|_| overflow())
+        }
+        "**" => match (x, y) {
+            (Int(_), Int(b)) if b >= 0 || flags.prefer_rationals => exact_pow(x, y),
+            (Number::Rational(_), Int(_)) => exact_pow(x, y),
+            _ => power(x, y),
+        },
+        "^" => match (x, y) {
+            (Int(a), Int(b)) if b < 0 && !flags.prefer_rationals && a.abs() != 1 => {
+                if a == 0 {
+                    return Err(evaluation_error("zero_divisor"));
+                }
+                Err(type_error("float", Term::Int(a)))
+            }
+            (Int(_), Int(_)) | (Number::Rational(_), Int(_)) => exact_pow(x, y),
+            _ => power(x, y),
+        },
+        ">>" => {
+            let (a, b) = (x.int()?, y.int()?);
+            Ok(Int(if b >= 0 { shr(a, b.unsigned_abs()) } else { shl(a, b.unsigned_abs())? }))
+        }
+        "<<" => {
+            let (a, b) = (x.int()?, y.int()?);
+            Ok(Int(if b >= 0 { shl(a, b.unsigned_abs())? } else { shr(a, b.unsigned_abs()) }))
+        }
+        "/\\" => Ok(Int(x.int()? & y.int()?)),
+        "\\/" => Ok(Int(x.int()? | y.int()?)),
+        "xor" => Ok(Int(x.int()? ^ y.int()?)),
+        "atan2" | "atan" => {
+            let (a, b) = (x.to_f64(), y.to_f64());
+            if a == 0.0 && b == 0.0 {
+                return Err(evaluation_error("undefined"));
+            }
+            float(a.atan2(b))
+        }
+        "copysign" => float(x.to_f64().copysign(y.to_f64())),
+        "log" => {
+            let (base, n) = (x.to_f64(), y.to_f64());
+            if base <= 0.0 || n <= 0.0 || base == 1.0 {
+                return Err(evaluation_error("undefined"));
+            }
+            float(n.ln() / base.ln())
+        }
+        _ => Err(type_error("evaluable", indicator(name, 2))),
+    }
+}
+
+fn floor_div(a: i64, b: i64) -> Option<i64> {
+    let q = a.checked_div(b)?;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        q.checked_sub(1)
+    } else {
+        Some(q)
+    }
+}
+
+fn shl(a: i64, b: u64) -> Result<i64, Term> {
+    if a == 0 {
+        return Ok(0);
+    }
+    if b >= 63 || (a << b) >> b != a {
+        return Err(overflow());
+    }
+    Ok(a << b)
+}
+
+// shifting by 63 or more leaves only the sign
+fn shr(a: i64, b: u64) -> i64 {
+    a >> b.min(63)
+}
+
+// integer or rational base raised to an integer power
+fn exact_pow(x: Number, y: Number) -> Result<Number, Term> {
+    let (base, exp) = (x.rational()?, y.int()?);
+    if exp < 0 && base.numerator() == 0 {
+        return Err(evaluation_error("zero_divisor"));
+    }
+    // powers of 0, 1 and -1 only depend on the parity of the exponent, so
+    // huge exponents must not overflow for them
+    let power = if base.denominator() == 1 && base.numerator().abs() <= 1 {
+        match exp {
+            0 => 0,
+            e if e % 2 == 0 => 2,
+            _ => 1,
+        }
+    } else {
+        u32::try_from(exp.unsigned_abs()).map_err(|_| overflow())?
+    };
+    let result = exact(base.pow(power))?;
+    if exp >= 0 {
+        return Ok(result);
+    }
+    let result = result.rational()?;
+    exact(Rational::from_int(1).div(result))
+}
+
+fn power(x: Number, y: Number) -> Result<Number, Term> {
+    let (base, exp) = (x.to_f64(), y.to_f64());
+    if base == 0.0 && exp < 0.0 {
+        return Err(evaluation_error("zero_divisor"));
+    }
+    float(base.powf(exp))
+}
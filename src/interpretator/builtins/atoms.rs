@@ -0,0 +1,345 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    parser::{self, Parser, ParserError},
+    tokenizer::{Token, Tokenizer, TokenizerError},
+};
+
+use super::{
+    super::{
+        error::{domain_error, instantiation_error, representation_error, syntax_error, type_error},
+        write::format_term,
+        Clause,
+    },
+    list_to_vec, Builtin, Runner, Term,
+};
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    let builtin: Builtin = match (name, arity) {
+        ("atom_codes", 2) => |runner, args| relate(runner, args, Term::atom, codes, list_text),
+        ("atom_chars", 2) => |runner, args| relate(runner, args, Term::atom, chars, list_text),
+        ("char_code", 2) => char_code,
+        ("atom_length", 2) => length,
+        ("atom_concat", 3) => |runner, args| concat(runner, args, Term::atom),
+        ("sub_atom", 5) => |runner, args| sub_text(runner, args, "atom"),
+        ("$sub_text", 10) => |runner, args| next_sub_text(runner, &args[..6], &args[6..]),
+        ("upcase_atom", 2) => |runner, args| {
+            let upper = Term::atom(&required_text(&args[0])?.to_uppercase());
+            Ok(runner.unify(&args[1], &upper))
+        },
+        ("downcase_atom", 2) => |runner, args| {
+            let lower = Term::atom(&required_text(&args[0])?.to_lowercase());
+            Ok(runner.unify(&args[1], &lower))
+        },
+        ("number_codes", 2) => number_codes,
+        ("atom_number", 2) => atom_number,
+        ("term_to_atom", 2) => term_to_atom,
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+// the text of an atomic term, or `None` while it is unbound
+pub fn text(term: &Term) -> Result<Option<String>, Term> {
+    match term.deref() {
+        Term::Var(_) => Ok(None),
+        Term::Atom(name) => Ok(Some(name.to_string())),
+        Term::Str(text) => Ok(Some(text.to_string())),
+        culprit @ (Term::Compound(_) | Term::Dict(_)) => Err(type_error("atomic", culprit)),
+        number => Ok(Some(number.to_string())),
+    }
+}
+
+pub fn required_text(term: &Term) -> Result<String, Term> {
+    text(term)?.ok_or_else(instantiation_error)
+}
+
+// the text spelled by a list of characters or character codes, or by a
+// string
+pub fn list_text(list: &Term) -> Result<String, Term> {
+    if let Term::Str(text) = list.deref() {
+        return Ok(text.to_string());
+    }
+    let mut text = String::new();
+    for item in list_to_vec(list)? {
+        match item.deref() {
+            Term::Var(_) => return Err(instantiation_error()),
+            Term::Int(code) => match u32::try_from(code).ok().and_then(char::from_u32) {
+                Some(ch) => text.push(ch),
+                None => return Err(representation_error("character_code")),
+            },
+            Term::Atom(name) if name.chars().count() == 1 => text.push_str(&name),
+            culprit => return Err(type_error("character", culprit)),
+        }
+    }
+    Ok(text)
+}
+
+pub fn chars(text: &str) -> Term {
+    Term::from_list(text.chars().map(|ch| Term::atom(&ch.to_string())).collect())
+}
+
+pub fn codes(text: &str) -> Term {
+    Term::from_list(text.chars().map(|ch| Term::Int(ch as i64)).collect())
+}
+
+// Relates the text in the first argument to another form of it in the
+// second: `to` builds that form from the text, `make` builds the first
+// argument when only `read` can get the text out of the second.
+pub fn relate(
+    runner: &mut Runner,
+    args: &[Term],
+    make: fn(&str) -> Term,
+    to: fn(&str) -> Term,
+    read: fn(&Term) -> Result<String, Term>,
+) -> Result<bool, Term> {
+    let built = match text(&args[0])? {
+        Some(text) => return Ok(runner.unify(&args[1], &to(&text))),
+        None => make(&read(&args[1])?),
+    };
+    Ok(runner.unify(&args[0], &built))
+}
+
+pub fn length(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let text = required_text(&args[0])?;
+    match args[1].deref() {
+        Term::Var(_) => {}
+        Term::Int(n) if n < 0 => return Err(domain_error("not_less_than_zero", Term::Int(n))),
+        Term::Int(_) => {}
+        culprit => return Err(type_error("integer", culprit)),
+    }
+    Ok(runner.unify(&args[1], &Term::Int(text.chars().count() as i64)))
+}
+
+// joins the first two arguments, or enumerates every way of splitting the
+// third one when they are not both known
+pub fn concat(runner: &mut Runner, args: &[Term], make: fn(&str) -> Term) -> Result<bool, Term> {
+    if let (Some(left), Some(right)) = (text(&args[0])?, text(&args[1])?) {
+        return Ok(runner.unify(&args[2], &make(&(left + &right))));
+    }
+    let whole: Vec<char> = required_text(&args[2])?.chars().collect();
+    let pair = Term::compound("-", vec![args[0].clone(), args[1].clone()]);
+    let candidates = (0..=whole.len())
+        .map(|i| {
+            let left: String = whole[..i].iter().collect();
+            let right: String = whole[i..].iter().collect();
+            Term::compound("-", vec![make(&left), make(&right)])
+        })
+        .collect();
+    Ok(runner.unify_any(&pair, candidates))
+}
+
+// sub_atom/5 and sub_string/5: the `Before`, `Length`, `After` and `Sub`
+// combinations that agree with whatever is already bound, by position then
+// length. Each solution looks ahead for the next one and leaves a choice
+// point that resumes from there, so a call that fixes the position or the
+// sub-text only goes through the text it needs.
+pub fn sub_text(runner: &mut Runner, args: &[Term], kind: &str) -> Result<bool, Term> {
+    let whole: Rc<str> = Rc::from(required_text(&args[0])?);
+    let n = whole.chars().count();
+    let state = [Term::atom(kind), Term::Str(whole), Term::Int(n as i64), Term::Int(0), Term::Int(0), Term::Int(0)];
+    next_sub_text(runner, &state, &args[1..5])
+}
+
+// goes on with a sub_atom/5 or sub_string/5 call from the state a solution
+// left: the kind of text, the text and its length, then the position, its
+// byte offset and the length to look from
+fn next_sub_text(runner: &mut Runner, state: &[Term], args: &[Term]) -> Result<bool, Term> {
+    let whole = match state[1].deref() {
+        Term::Str(whole) => whole,
+        _ => return Ok(false),
+    };
+    let [n, start, byte, len] = [&state[2], &state[3], &state[4], &state[5]].map(|t| match t.deref() {
+        Term::Int(i) => i as usize,
+        _ => 0,
+    });
+    let (before, length, after) = (bound_length(&args[0])?, bound_length(&args[1])?, bound_length(&args[2])?);
+    let sub = text(&args[3])?;
+    let bounds = SubBounds { before, length, after, sub: sub.as_deref() };
+    let (start, byte, len) = match bounds.next(&whole, n, start, byte, len) {
+        Some(found) => found,
+        None => return Ok(false),
+    };
+    if let Some((next, next_byte, next_len)) = bounds.next(&whole, n, start, byte, len + 1) {
+        let mut rest = state.to_vec();
+        rest[3..].clone_from_slice(&[Term::Int(next as i64), Term::Int(next_byte as i64), Term::Int(next_len as i64)]);
+        rest.extend_from_slice(args);
+        runner.push_alternative(Term::compound("$sub_text", rest), runner.choicepoints.len());
+    }
+    let end = char_offset(&whole, byte, len).unwrap_or(whole.len());
+    let text = &whole[byte..end];
+    let make = match state[0].deref() {
+        Term::Atom(kind) if &*kind == "string" => Term::string,
+        _ => Term::atom,
+    };
+    let values = vec![Term::Int(start as i64), Term::Int(len as i64), Term::Int((n - start - len) as i64), make(text)];
+    Ok(runner.unify(&Term::compound("sub", args.to_vec()), &Term::compound("sub", values)))
+}
+
+// the byte offset `chars` characters after `byte`, which may be the end
+fn char_offset(whole: &str, byte: usize, chars: usize) -> Option<usize> {
+    whole[byte..].char_indices().map(|(i, _)| byte + i).chain([whole.len()]).nth(chars)
+}
+
+struct SubBounds<'a> {
+    before: Option<usize>,
+    length: Option<usize>,
+    after: Option<usize>,
+    sub: Option<&'a str>,
+}
+
+impl SubBounds<'_> {
+    // the first solution at `start`, `byte` with a length of at least `len`,
+    // or else at a later position
+    fn next(&self, whole: &str, n: usize, mut start: usize, mut byte: usize, mut len: usize) -> Option<(usize, usize, usize)> {
+        let sub_len = self.sub.map(|sub| sub.chars().count());
+        // a known length and `After` leave one position
+        let before = match (self.before, self.length.or(sub_len), self.after) {
+            (None, Some(length), Some(after)) => Some(n.checked_sub(length + after)?),
+            (before, _, _) => before,
+        };
+        loop {
+            if start > n {
+                return None;
+            }
+            match (before, self.sub) {
+                (Some(before), _) if start > before => return None,
+                (Some(before), _) if start < before => {
+                    byte = char_offset(whole, byte, before - start)?;
+                    start = before;
+                    len = 0;
+                }
+                (None, Some(sub)) => {
+                    let offset = whole[byte..].find(sub)?;
+                    if offset > 0 {
+                        start += whole[byte..byte + offset].chars().count();
+                        byte += offset;
+                        len = 0;
+                    }
+                }
+                _ => {}
+            }
+            let rest = n - start;
+            let (mut low, mut high) = (len, rest);
+            if let Some(after) = self.after {
+                high = high.min(rest.checked_sub(after)?);
+                low = low.max(rest - after);
+            }
+            for fixed in [self.length, sub_len].into_iter().flatten() {
+                (low, high) = (low.max(fixed), high.min(fixed));
+            }
+            if low <= high && self.sub.is_none_or(|sub| whole[byte..].starts_with(sub)) {
+                return Some((start, byte, low));
+            }
+            byte = char_offset(whole, byte, 1).unwrap_or(whole.len());
+            start += 1;
+            len = 0;
+        }
+    }
+}
+
+fn bound_length(term: &Term) -> Result<Option<usize>, Term> {
+    match term.deref() {
+        Term::Var(_) => Ok(None),
+        Term::Int(n) if n < 0 => Err(domain_error("not_less_than_zero", Term::Int(n))),
+        Term::Int(n) => Ok(Some(n as usize)),
+        culprit => Err(type_error("integer", culprit)),
+    }
+}
+
+fn char_code(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    match args[0].deref() {
+        Term::Atom(name) if name.chars().count() == 1 => {
+            let code = Term::Int(name.chars().next().unwrap() as i64);
+            return Ok(runner.unify(&args[1], &code));
+        }
+        Term::Var(_) => {}
+        culprit => return Err(type_error("character", culprit)),
+    }
+    match args[1].deref() {
+        Term::Var(_) => Err(instantiation_error()),
+        Term::Int(code) => match u32::try_from(code).ok().and_then(char::from_u32) {
+            Some(ch) => Ok(runner.unify(&args[0], &Term::atom(&ch.to_string()))),
+            None => Err(representation_error("character_code")),
+        },
+        culprit => Err(type_error("integer", culprit)),
+    }
+}
+
+// the number written in `text`, which may carry a sign but nothing else
+// around it
+pub fn parse_number(runner: &mut Runner, text: &str) -> Option<Term> {
+    let text = text.trim_start();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let tokens = Tokenizer::from_str(digits).parse().ok()?;
+    let number = match tokens[..] {
+        [Token::Number(n)] => parser::Number::Integer(if negative { n.checked_neg()? } else { n }),
+        [Token::Rational(n, d)] => parser::Number::Rational(if negative { -n } else { n }, d),
+        [Token::Float(n)] => parser::Number::Float(if negative { -n } else { n }),
+        _ => return None,
+    };
+    Some(number.to_term(&mut HashMap::new(), &mut runner.id_factory))
+}
+
+fn number_codes(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let number = args[0].deref();
+    let ground_list = matches!(args[1].deref(), Term::Str(_)) || args[1].is_list() && args[1].variables().is_empty();
+    if !ground_list {
+        match number {
+            Term::Var(_) => return Err(instantiation_error()),
+            Term::Int(_) | Term::Rational(_) | Term::Float(_) => {
+                return Ok(runner.unify(&args[1], &codes(&number.to_string())));
+            }
+            culprit => return Err(type_error("number", culprit)),
+        }
+    }
+    let text = list_text(&args[1])?;
+    match parse_number(runner, &text) {
+        Some(parsed) => Ok(runner.unify(&number, &parsed)),
+        None => Err(syntax_error("illegal_number")),
+    }
+}
+
+fn atom_number(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    match args[0].deref() {
+        Term::Var(_) => match args[1].deref() {
+            Term::Var(_) => Err(instantiation_error()),
+            number @ (Term::Int(_) | Term::Rational(_) | Term::Float(_)) => {
+                Ok(runner.unify(&args[0], &Term::atom(&number.to_string())))
+            }
+            culprit => Err(type_error("number", culprit)),
+        },
+        Term::Atom(name) => match parse_number(runner, &name) {
+            Some(number) => Ok(runner.unify(&args[1], &number)),
+            None => Ok(false),
+        },
+        culprit => Err(type_error("atom", culprit)),
+    }
+}
+
+fn term_to_atom(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    match text(&args[1])? {
+        Some(text) => {
+            let term = read_term(runner, &text)?;
+            Ok(runner.unify(&args[0], &term))
+        }
+        None => {
+            let text = format_term(&args[0], &runner.ops, true);
+            Ok(runner.unify(&args[1], &Term::atom(&text)))
+        }
+    }
+}
+
+// parses `text` as a single term with fresh variables
+pub fn read_term(runner: &mut Runner, text: &str) -> Result<Term, Term> {
+    let tokens = Tokenizer::from_str(text).parse().map_err(|error| match error {
+        TokenizerError::BaseError { msg, .. } => syntax_error(&msg),
+    })?;
+    let clause = Parser::from_tokens(tokens).parse_read_term().map_err(|error| match error {
+        ParserError::BaseError { msg } => syntax_error(&msg),
+    })?;
+    Ok(clause.to_term(&mut HashMap::new(), &mut runner.id_factory))
+}
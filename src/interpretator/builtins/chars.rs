@@ -0,0 +1,85 @@
+use super::{
+    super::error::{representation_error, type_error},
+    Builtin, Runner, Term,
+};
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    let builtin: Builtin = match (name, arity) {
+        ("char_type", 2) => |runner, args| char_type(runner, args, |ch| Term::atom(&ch.to_string())),
+        ("code_type", 2) => |runner, args| char_type(runner, args, |ch| Term::Int(ch as i64)),
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+// the character a char_type/2 or code_type/2 argument stands for, or `None`
+// while it is unbound
+fn character(term: &Term) -> Result<Option<char>, Term> {
+    match term.deref() {
+        Term::Var(_) => Ok(None),
+        Term::Atom(name) if name.chars().count() == 1 => Ok(name.chars().next()),
+        Term::Int(code) => match u32::try_from(code).ok().and_then(char::from_u32) {
+            Some(ch) => Ok(Some(ch)),
+            None => Err(representation_error("character_code")),
+        },
+        culprit => Err(type_error("character", culprit)),
+    }
+}
+
+// every type `ch` has, with the characters in them built by `make`
+fn types(ch: char, make: fn(char) -> Term) -> Vec<Term> {
+    let mut types = Vec::new();
+    let mut has = |name: &str, holds: bool| {
+        if holds {
+            types.push(Term::atom(name));
+        }
+    };
+    let graph = !ch.is_whitespace() && !ch.is_control();
+    has("alnum", ch.is_alphanumeric());
+    has("alpha", ch.is_alphanumeric() || ch == '_');
+    has("csym", ch.is_alphanumeric() || ch == '_');
+    has("csymf", ch.is_alphabetic() || ch == '_');
+    has("ascii", ch.is_ascii());
+    has("white", ch == ' ' || ch == '\t');
+    has("cntrl", ch.is_control());
+    has("space", ch.is_whitespace());
+    has("end_of_line", ch == '\n' || ch == '\r');
+    has("graph", graph);
+    has("print", graph || ch == ' ');
+    has("punct", graph && !ch.is_alphanumeric());
+    has("digit", ch.is_ascii_digit());
+    has("upper", ch.is_uppercase());
+    has("lower", ch.is_lowercase());
+    if let Some(weight) = ch.to_digit(10) {
+        types.push(Term::compound("digit", vec![Term::Int(weight as i64)]));
+    }
+    let lower = ch.to_lowercase().next().unwrap_or(ch);
+    let upper = ch.to_uppercase().next().unwrap_or(ch);
+    if ch.is_uppercase() {
+        types.push(Term::compound("upper", vec![make(lower)]));
+    }
+    if ch.is_lowercase() {
+        types.push(Term::compound("lower", vec![make(upper)]));
+    }
+    types.push(Term::compound("to_lower", vec![make(lower)]));
+    types.push(Term::compound("to_upper", vec![make(upper)]));
+    types
+}
+
+// Enumerates the types of a known character, or with an unbound one every
+// character in Latin-1 that has the type.
+fn char_type(runner: &mut Runner, args: &[Term], make: fn(char) -> Term) -> Result<bool, Term> {
+    if let Some(ch) = character(&args[0])? {
+        return Ok(runner.unify_any(&args[1], types(ch, make)));
+    }
+    let candidates = (0..=255u8)
+        .map(char::from)
+        .flat_map(|ch| {
+            types(ch, make)
+                .into_iter()
+                .map(move |kind| Term::compound("-", vec![make(ch), kind]))
+        })
+        .collect();
+    let pair = Term::compound("-", vec![args[0].clone(), args[1].clone()]);
+    Ok(runner.unify_any(&pair, candidates))
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use super::{
+    super::error::{instantiation_error, type_error},
+    list_to_vec, Builtin, Runner, Term,
+};
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    let builtin: Builtin = match (name, arity) {
+        ("call", 2..=8) => |runner, args| {
+            let goal = add_args(&args[0], &args[1..])?;
+            meta_call(runner, goal)
+        },
+        ("apply", 2) => |runner, args| {
+            let goal = add_args(&args[0], &list_to_vec(&args[1])?)?;
+            meta_call(runner, goal)
+        },
+        ("not", 1) => |runner, args| meta_call(runner, Term::compound("\\+", vec![args[0].clone()])),
+        ("once", 1) => |runner, args| {
+            let goal = Term::compound("->", vec![callable(&args[0])?, Term::atom("true")]);
+            meta_call(runner, goal)
+        },
+        ("ignore", 1) => |runner, args| {
+            let once = Term::compound("->", vec![callable(&args[0])?, Term::atom("true")]);
+            meta_call(runner, Term::compound(";", vec![once, Term::atom("true")]))
+        },
+        // library(yall): `Params>>Body`, `\X^Body` and `Free/Lambda` called
+        // with extra arguments
+        (">>", 2..=9) => |runner, args| {
+            let lambda = Term::compound(">>", args[..2].to_vec());
+            let copy = match args[0].deref() {
+                Term::Compound(c) if &*c.name == "/" && c.args.len() == 2 => {
+                    let lambda = Term::compound(">>", vec![c.args[1].clone(), args[1].clone()]);
+                    copy_lambda(runner, &lambda, &c.args[0])
+                }
+                _ => copy_lambda(runner, &lambda, &Term::atom("[]")),
+            };
+            apply_lambda(runner, copy, &args[2..])
+        },
+        ("\\", 2..=8) => |runner, args| {
+            let copy = copy_lambda(runner, &Term::compound("\\", vec![args[0].clone()]), &Term::atom("[]"));
+            apply_lambda(runner, copy, &args[1..])
+        },
+        ("/", 2..=9) => |runner, args| {
+            let copy = copy_lambda(runner, &args[1], &args[0]);
+            apply_lambda(runner, copy, &args[2..])
+        },
+        // `X^Body` inside a lambda: binds its parameter and goes on with the body
+        ("^", 3..=9) => |runner, args| {
+            if !runner.unify(&args[0], &args[2]) {
+                return Ok(false);
+            }
+            let goal = add_args(&args[1], &args[3..])?;
+            meta_call(runner, goal)
+        },
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+// renames the variables of a lambda apart, except the ones in `free`, so one
+// call cannot bind them for the next
+fn copy_lambda(runner: &mut Runner, lambda: &Term, free: &Term) -> Term {
+    let mut mapping = HashMap::new();
+    for variable in free.variables() {
+        if let Term::Var(v) = &variable {
+            mapping.insert(v.id, variable.clone());
+        }
+    }
+    lambda.rename(&mut mapping, &mut runner.id_factory)
+}
+
+// binds the parameters of a copied lambda to the arguments, passing the ones
+// left over on to its body
+fn apply_lambda(runner: &mut Runner, lambda: Term, extra: &[Term]) -> Result<bool, Term> {
+    let (params, body) = match lambda.deref() {
+        Term::Compound(c) if &*c.name == ">>" && c.args.len() == 2 => (c.args[0].list_items().0, c.args[1].clone()),
+        Term::Compound(c) if &*c.name == "\\" && c.args.len() == 1 => {
+            return meta_call(runner, add_args(&c.args[0], extra)?);
+        }
+        _ => return meta_call(runner, add_args(&lambda, extra)?),
+    };
+    let bound = params.len().min(extra.len());
+    for (param, arg) in params.iter().zip(extra) {
+        if !runner.unify(param, arg) {
+            return Ok(false);
+        }
+    }
+    meta_call(runner, add_args(&body, &extra[bound..])?)
+}
+
+fn callable(goal: &Term) -> Result<Term, Term> {
+    match goal.deref() {
+        Term::Var(_) => Err(instantiation_error()),
+        goal if goal.is_callable() => Ok(goal),
+        culprit => Err(type_error("callable", culprit)),
+    }
+}
+
+// the goal a closure stands for once `extra` is appended to its arguments;
+// a module qualification stays on the outside
+fn add_args(closure: &Term, extra: &[Term]) -> Result<Term, Term> {
+    match callable(closure)? {
+        Term::Compound(c) if &*c.name == ":" && c.args.len() == 2 => {
+            Ok(Term::compound(":", vec![c.args[0].clone(), add_args(&c.args[1], extra)?]))
+        }
+        Term::Atom(name) if extra.is_empty() => Ok(Term::Atom(name)),
+        Term::Atom(name) => Ok(Term::compound(&name, extra.to_vec())),
+        Term::Compound(c) => {
+            let mut args = c.args.to_vec();
+            args.extend_from_slice(extra);
+            Ok(Term::compound(&c.name, args))
+        }
+        culprit => Err(type_error("callable", culprit)),
+    }
+}
+
+// runs `goal` next, with any cut in it local to the call
+fn meta_call(runner: &mut Runner, goal: Term) -> Result<bool, Term> {
+    let cut_barrier = runner.choicepoints.len();
+    runner.push_goal(goal, cut_barrier);
+    Ok(true)
+}
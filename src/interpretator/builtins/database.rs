@@ -0,0 +1,275 @@
+use std::{collections::HashMap, rc::Rc};
+
+use super::{
+    super::{
+        database::{ClauseRecord, Key},
+        error::{domain_error, indicator, instantiation_error, permission_error, type_error},
+        module::{qualify, strip},
+    },
+    is_builtin, list_to_vec, Builtin, Runner, Term,
+};
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    let builtin: Builtin = match (name, arity) {
+        ("assert", 1) | ("assertz", 1) => |runner, args| assert(runner, &args[0], false),
+        ("asserta", 1) => |runner, args| assert(runner, &args[0], true),
+        ("retract", 1) => retract,
+        ("retractall", 1) => retractall,
+        ("abolish", 1) => abolish,
+        ("clause", 2) => |runner, args| clause(runner, &args[0], &args[1], None),
+        ("clause", 3) => |runner, args| clause(runner, &args[0], &args[1], Some(&args[2])),
+        ("clause_property", 2) => clause_property,
+        ("dynamic", 1) => |runner, args| declare(runner, &args[0], true),
+        ("discontiguous", 1) => |runner, args| declare(runner, &args[0], false),
+        // removes the clause retract/1 unified with, failing if it is gone
+        ("$erase", 2) => |runner, args| {
+            let (module, key) = qualified_indicator(runner, &args[0])?;
+            match args[1].deref() {
+                Term::Int(id) => Ok(runner.database.remove(&module, &key, id as usize)),
+                _ => Ok(false),
+            }
+        },
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+fn head_key(head: &Term) -> Result<Key, Term> {
+    match head.deref() {
+        Term::Var(_) => Err(instantiation_error()),
+        head => head.name_arity().ok_or_else(|| type_error("callable", head)),
+    }
+}
+
+// The module whose predicate the database builtins work on: the one a call
+// from `module` would reach, or `module` itself for a new predicate.
+fn defining(runner: &Runner, module: Rc<str>, key: &Key) -> Rc<str> {
+    runner.visible(&module, key).unwrap_or(module)
+}
+
+// builtins and predicates loaded without a dynamic declaration are static,
+// programs cannot change them
+fn check_modifiable(runner: &Runner, module: &str, key: &Key) -> Result<(), Term> {
+    let defined_static = runner.database.contains(module, key) && !runner.database.is_dynamic(module, key);
+    if defined_static || is_builtin(&key.0, key.1) {
+        return Err(permission_error("modify", "static_procedure", indicator(&key.0, key.1)));
+    }
+    Ok(())
+}
+
+// the module, key and unqualified head of a predicate a program changes
+fn modifiable(runner: &Runner, module: Rc<str>, head: &Term) -> Result<(Rc<str>, Key, Term), Term> {
+    let (module, head) = strip(head, module)?;
+    let key = head_key(&head)?;
+    let module = defining(runner, module, &key);
+    check_modifiable(runner, &module, &key)?;
+    Ok((module, key, head))
+}
+
+fn callable_body(body: &Term) -> bool {
+    match body.deref() {
+        Term::Var(_) => true,
+        Term::Compound(c) if matches!(&*c.name, "," | ";" | "->" | "*->") && c.args.len() == 2 => {
+            callable_body(&c.args[0]) && callable_body(&c.args[1])
+        }
+        body => body.is_callable(),
+    }
+}
+
+fn assert(runner: &mut Runner, clause: &Term, front: bool) -> Result<bool, Term> {
+    let clause = clause.rename(&mut HashMap::new(), &mut runner.id_factory);
+    let (module, clause) = strip(&clause, runner.module.clone())?;
+    let (head, body) = ClauseRecord::parts(&clause);
+    let (module, key, head) = modifiable(runner, module, &head)?;
+    if !callable_body(&body) {
+        return Err(type_error("callable", clause));
+    }
+    runner.database.declare_dynamic(&module, key.clone());
+    runner.database.add(&module, key, head, body, front, None);
+    Ok(true)
+}
+
+// the clauses of the predicate, renamed apart, as `clause(Head, Body, Id)`
+fn clause_copies(runner: &mut Runner, module: &str, key: &Key) -> Vec<Term> {
+    let clauses = match runner.database.clauses(module, key) {
+        Some(clauses) => clauses,
+        None => return Vec::new(),
+    };
+    clauses
+        .iter()
+        .map(|c| {
+            let mut mapping = HashMap::new();
+            let head = c.head.rename(&mut mapping, &mut runner.id_factory);
+            let body = c.body.rename(&mut mapping, &mut runner.id_factory);
+            Term::compound("clause", vec![head, body, Term::Int(c.id as i64)])
+        })
+        .collect()
+}
+
+// Unifies with the clauses on backtracking and erases each one it settles
+// on. The candidates are taken when the call starts, so clauses added later
+// are not seen, while `$erase/2` skips those removed in the meantime.
+fn retract(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let (module, clause) = strip(&args[0], runner.module.clone())?;
+    let (head, body) = ClauseRecord::parts(&clause);
+    let (module, key, head) = modifiable(runner, module, &head)?;
+    let candidates = clause_copies(runner, &module, &key);
+    let id = Term::var(&mut runner.id_factory);
+    let erase = Term::compound("$erase", vec![qualify(&indicator(&key.0, key.1), &module), id.clone()]);
+    let cut_barrier = runner.choicepoints.len();
+    runner.push_goal(erase, cut_barrier);
+    let pattern = Term::compound("clause", vec![head, body, id]);
+    Ok(runner.unify_any(&pattern, candidates))
+}
+
+fn retractall(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let (module, key, head) = modifiable(runner, runner.module.clone(), &args[0])?;
+    runner.database.declare_dynamic(&module, key.clone());
+    for copy in clause_copies(runner, &module, &key) {
+        if let Term::Compound(c) = &copy {
+            let trail_len = runner.trail.mark(&runner.id_factory);
+            if runner.unify(&head, &c.args[0]) {
+                if let Term::Int(id) = c.args[2] {
+                    runner.database.remove(&module, &key, id as usize);
+                }
+            }
+            runner.undo_trail(trail_len);
+        }
+    }
+    Ok(true)
+}
+
+// clause(?Head, ?Body, ?Ref) also relates every clause to a reference
+// `'$clause'(Module:Name/Arity, Id)`, for clause_property/2
+fn clause(runner: &mut Runner, head: &Term, body: &Term, reference: Option<&Term>) -> Result<bool, Term> {
+    let referenced = reference.and_then(|reference| clause_reference(runner, reference));
+    let (module, head) = strip(head, runner.module.clone())?;
+    let (module, key) = match &referenced {
+        Some((module, key, _)) => (module.clone(), key.clone()),
+        None => {
+            let key = head_key(&head)?;
+            (defining(runner, module, &key), key)
+        }
+    };
+    match body.deref() {
+        Term::Var(_) => {}
+        body if body.is_callable() => {}
+        culprit => return Err(type_error("callable", culprit)),
+    }
+    if is_builtin(&key.0, key.1) {
+        return Err(permission_error("access", "private_procedure", indicator(&key.0, key.1)));
+    }
+    let candidates = clause_copies(runner, &module, &key)
+        .into_iter()
+        .filter_map(|copy| {
+            let c = match &copy {
+                Term::Compound(c) => c,
+                _ => return None,
+            };
+            let id = match c.args[2] {
+                Term::Int(id) => id as usize,
+                _ => return None,
+            };
+            if referenced.as_ref().is_some_and(|(_, _, wanted)| *wanted != id) {
+                return None;
+            }
+            let predicate = qualify(&indicator(&key.0, key.1), &module);
+            let reference = Term::compound("$clause", vec![predicate, c.args[2].clone()]);
+            Some(Term::compound("clause", vec![c.args[0].clone(), c.args[1].clone(), reference]))
+        })
+        .collect();
+    let reference = reference.cloned().unwrap_or_else(|| Term::var(&mut runner.id_factory));
+    let pattern = Term::compound("clause", vec![head, body.clone(), reference]);
+    Ok(runner.unify_any(&pattern, candidates))
+}
+
+// the predicate and clause id of a `'$clause'(Module:Name/Arity, Id)`
+// reference
+fn clause_reference(runner: &Runner, reference: &Term) -> Option<(Rc<str>, Key, usize)> {
+    match reference.deref() {
+        Term::Compound(c) if &*c.name == "$clause" && c.args.len() == 2 => {
+            let (module, key) = qualified_indicator(runner, &c.args[0]).ok()?;
+            match c.args[1].deref() {
+                Term::Int(id) => Some((module, key, id as usize)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// clause_property(+Ref, ?Property): `file(File)` and `line_count(Line)` for
+// the text of a loaded clause, `source(File)` for the file consulted to load
+// it, and `fact` for clauses without a body
+fn clause_property(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let referenced = clause_reference(runner, &args[0]);
+    let record = match referenced.and_then(|(module, key, id)| runner.database.clause(&module, &key, id)) {
+        Some(record) => record,
+        None => return Ok(false),
+    };
+    let mut properties = Vec::new();
+    if let Some(source) = &record.source {
+        properties.push(Term::compound("file", vec![Term::Atom(source.file.clone())]));
+        properties.push(Term::compound("line_count", vec![Term::Int(source.line as i64)]));
+        properties.push(Term::compound("source", vec![Term::Atom(source.owner.clone())]));
+    }
+    if matches!(record.body.deref(), Term::Atom(name) if &*name == "true") {
+        properties.push(Term::atom("fact"));
+    }
+    Ok(runner.unify_any(&args[1], properties))
+}
+
+pub fn predicate_indicator(term: &Term) -> Result<Key, Term> {
+    let (name, arity) = match term.deref() {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Compound(c) if &*c.name == "/" && c.args.len() == 2 => (c.args[0].deref(), c.args[1].deref()),
+        culprit => return Err(type_error("predicate_indicator", culprit)),
+    };
+    let name = match name {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Atom(name) => name,
+        culprit => return Err(type_error("atom", culprit)),
+    };
+    match arity {
+        Term::Var(_) => Err(instantiation_error()),
+        Term::Int(n) if n < 0 => Err(domain_error("not_less_than_zero", Term::Int(n))),
+        Term::Int(n) => Ok((name, n as usize)),
+        culprit => Err(type_error("integer", culprit)),
+    }
+}
+
+// `Name/Arity`, maybe qualified, with the module it names
+fn qualified_indicator(runner: &Runner, term: &Term) -> Result<(Rc<str>, Key), Term> {
+    let (module, term) = strip(term, runner.module.clone())?;
+    Ok((module, predicate_indicator(&term)?))
+}
+
+fn abolish(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let (module, key) = qualified_indicator(runner, &args[0])?;
+    let module = defining(runner, module, &key);
+    check_modifiable(runner, &module, &key)?;
+    runner.database.abolish(&module, &key);
+    Ok(true)
+}
+
+// dynamic/1 and discontiguous/1 take a predicate indicator, a conjunction or
+// a list of them; they declare predicates of the module they run in
+fn declare(runner: &mut Runner, spec: &Term, dynamic: bool) -> Result<bool, Term> {
+    let (module, spec) = strip(spec, runner.module.clone())?;
+    let specs = match &spec {
+        Term::Compound(c) if &*c.name == "," && c.args.len() == 2 => c.args.to_vec(),
+        Term::Compound(c) if &*c.name == "." && c.args.len() == 2 => list_to_vec(&spec)?,
+        _ => {
+            let key = predicate_indicator(&spec)?;
+            if dynamic {
+                check_modifiable(runner, &module, &key)?;
+                runner.database.declare_dynamic(&module, key);
+            }
+            return Ok(true);
+        }
+    };
+    for spec in specs {
+        declare(runner, &qualify(&spec, &module), dynamic)?;
+    }
+    Ok(true)
+}
@@ -0,0 +1,128 @@
+use std::rc::Rc;
+
+use super::{
+    super::{
+        dict::{Dict, Key},
+        error::{domain_error, instantiation_error, type_error},
+        json,
+    },
+    atoms::text,
+    list_to_vec, Builtin, Runner, Term,
+};
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    let builtin: Builtin = match (name, arity) {
+        ("is_dict", 1) => |_, args| Ok(matches!(args[0].deref(), Term::Dict(_))),
+        ("is_dict", 2) => |runner, args| match args[0].deref() {
+            Term::Dict(d) => Ok(runner.unify(&args[1], &d.tag)),
+            _ => Ok(false),
+        },
+        ("get_dict", 3) => get_dict,
+        ("put_dict", 4) => |runner, args| {
+            let (key, dict) = (dict_key(&args[0])?, dict(&args[1])?);
+            let updated = dict.put(key, args[2].clone());
+            Ok(runner.unify(&args[3], &Term::Dict(Rc::new(updated))))
+        },
+        ("put_dict", 3) => |runner, args| {
+            let (new, mut updated) = (dict(&args[0])?, (*dict(&args[1])?).clone());
+            for (key, value) in new.pairs() {
+                updated = updated.put(key, value);
+            }
+            Ok(runner.unify(&args[2], &Term::Dict(Rc::new(updated))))
+        },
+        ("del_dict", 4) => |runner, args| {
+            let (key, dict) = (dict_key(&args[0])?, dict(&args[1])?);
+            let value = match dict.get(&key) {
+                Some(value) => value.clone(),
+                None => return Ok(false),
+            };
+            let rest = match dict.remove(&key) {
+                Some(rest) => Term::Dict(Rc::new(rest)),
+                None => return Ok(false),
+            };
+            Ok(runner.unify(&args[2], &value) && runner.unify(&args[3], &rest))
+        },
+        ("dict_pairs", 3) => dict_pairs,
+        ("atom_json_dict", 3) => atom_json_dict,
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+fn dict(term: &Term) -> Result<Rc<Dict>, Term> {
+    match term.deref() {
+        Term::Var(_) => Err(instantiation_error()),
+        Term::Dict(d) => Ok(d),
+        culprit => Err(type_error("dict", culprit)),
+    }
+}
+
+fn dict_key(term: &Term) -> Result<Key, Term> {
+    match term.deref() {
+        Term::Var(_) => Err(instantiation_error()),
+        key => Key::from_term(&key).ok_or_else(|| type_error("dict_key", key)),
+    }
+}
+
+// looks the key up when it is bound and enumerates the pairs otherwise
+fn get_dict(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let dict = dict(&args[1])?;
+    if let Term::Var(_) = args[0].deref() {
+        let pair = Term::compound("-", vec![args[0].clone(), args[2].clone()]);
+        let candidates = dict
+            .pairs()
+            .into_iter()
+            .map(|(key, value)| Term::compound("-", vec![key.to_term(), value]))
+            .collect();
+        return Ok(runner.unify_any(&pair, candidates));
+    }
+    match dict.get(&dict_key(&args[0])?) {
+        Some(value) => {
+            let value = value.clone();
+            Ok(runner.unify(&args[2], &value))
+        }
+        None => Ok(false),
+    }
+}
+
+// relates a dict to its tag and its `Key-Value` pairs in key order
+fn dict_pairs(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    if let Term::Dict(d) = args[0].deref() {
+        let pairs = d
+            .pairs()
+            .into_iter()
+            .map(|(key, value)| Term::compound("-", vec![key.to_term(), value]))
+            .collect();
+        return Ok(runner.unify(&args[1], &d.tag) && runner.unify(&args[2], &Term::from_list(pairs)));
+    }
+    let mut pairs = Vec::new();
+    for pair in list_to_vec(&args[2])? {
+        match pair.deref() {
+            Term::Var(_) => return Err(instantiation_error()),
+            Term::Compound(c) if matches!(&*c.name, "-" | "=" | ":") && c.args.len() == 2 => {
+                pairs.push((dict_key(&c.args[0])?, c.args[1].clone()));
+            }
+            culprit => return Err(type_error("pair", culprit)),
+        }
+    }
+    match Dict::from_pairs(args[1].clone(), pairs) {
+        Some(built) => Ok(runner.unify(&args[0], &Term::Dict(Rc::new(built)))),
+        None => Err(domain_error("unique_key_pairs", args[2].deref())),
+    }
+}
+
+// atom_json_dict(?Text, ?Dict, +Options): reads JSON text into a dict, or
+// writes one as compact JSON when the text is unbound
+fn atom_json_dict(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    list_to_vec(&args[2])?;
+    match text(&args[0])? {
+        Some(source) => {
+            let value = json::parse(&source, &mut runner.id_factory)?;
+            Ok(runner.unify(&args[1], &value))
+        }
+        None => {
+            let written = json::write(&args[1])?;
+            Ok(runner.unify(&args[0], &Term::atom(&written)))
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use crate::parser::Operators;
+
+use super::{
+    super::{
+        error::{instantiation_error, representation_error, type_error},
+        stream::Streams,
+        write::format_term,
+    },
+    atoms::{list_text, required_text, text},
+    io::{is_capture_sink, output_stream, unify_sink, write_text},
+    list_to_vec, Builtin, Runner, Term,
+};
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    let builtin: Builtin = match (name, arity) {
+        ("format", 1) => |runner, args| format(runner, None, &args[0], &Term::atom("[]")),
+        ("format", 2) => |runner, args| format(runner, None, &args[0], &args[1]),
+        ("format", 3) => |runner, args| format(runner, Some(&args[0]), &args[1], &args[2]),
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+// format(+Sink, +Format, :Args) writes to a stream, or to text when the sink
+// is one of with_output_to/2's. Arguments that are not a list are a single
+// argument.
+fn format(runner: &mut Runner, sink: Option<&Term>, format: &Term, args: &Term) -> Result<bool, Term> {
+    let format = match format.deref() {
+        list @ Term::Compound(_) => list_text(&list)?,
+        text => required_text(&text)?,
+    };
+    let args = match args.deref() {
+        list if list.is_list() => list_to_vec(&list)?,
+        single => vec![single],
+    };
+    let stream = match sink {
+        Some(sink) if is_capture_sink(sink) => None,
+        Some(stream) => Some(stream.clone()),
+        None => Some(Streams::term(runner.streams.output)),
+    };
+    let column = match &stream {
+        Some(stream) => {
+            let id = output_stream(runner, stream, None)?;
+            runner.streams.get(id).map_or(0, |stream| stream.column)
+        }
+        None => 0,
+    };
+    let text = Formatter::new(&runner.ops, column).run(&format, args)?;
+    match (stream, sink) {
+        (Some(stream), _) => write_text(runner, &stream, &text),
+        (None, Some(sink)) => unify_sink(runner, sink, &text),
+        (None, None) => Ok(true),
+    }
+}
+
+// the formal term of a malformed format or argument list
+fn format_error(message: &str) -> Term {
+    Term::compound("format", vec![Term::string(message)])
+}
+
+// Builds the output of one format/2 call. Text since the last column stop
+// forms a segment; `~t` marks fill points in it, and the next column stop
+// pads the segment at those points, or at its end when there are none.
+struct Formatter<'a> {
+    ops: &'a Operators,
+    out: String,
+    start_column: usize,
+    // the column of the last column stop
+    stop: usize,
+    // byte offsets of the fill points in `out`, with their fill characters
+    fills: Vec<(usize, char)>,
+}
+
+impl Formatter<'_> {
+    fn new(ops: &Operators, start_column: usize) -> Formatter<'_> {
+        Formatter { ops, out: String::new(), start_column, stop: start_column, fills: Vec::new() }
+    }
+
+    fn run(mut self, format: &str, args: Vec<Term>) -> Result<String, Term> {
+        let mut args = args.into_iter();
+        let mut chars = format.chars().peekable();
+        while let Some(ch) = chars.next() {
+            if ch != '~' {
+                self.emit(&ch.to_string());
+                continue;
+            }
+            // the numeric argument: digits, `*` for the next argument, or
+            // a backquote and the character whose code it is
+            let numeric = match chars.peek() {
+                Some('*') => {
+                    chars.next();
+                    match next_arg(&mut args)?.deref() {
+                        Term::Var(_) => return Err(instantiation_error()),
+                        Term::Int(n) if n >= 0 => Some(n as usize),
+                        Term::Int(n) => return Err(type_error("not_less_than_zero", Term::Int(n))),
+                        culprit => return Err(type_error("integer", culprit)),
+                    }
+                }
+                Some('`') => {
+                    chars.next();
+                    Some(chars.next().ok_or_else(|| format_error("truncated format"))? as usize)
+                }
+                Some(ch) if ch.is_ascii_digit() => {
+                    let mut n = 0usize;
+                    while let Some(digit) = chars.peek().and_then(|ch| ch.to_digit(10)) {
+                        n = n.saturating_mul(10).saturating_add(digit as usize);
+                        chars.next();
+                    }
+                    Some(n)
+                }
+                _ => None,
+            };
+            let directive = chars.next().ok_or_else(|| format_error("truncated format"))?;
+            match directive {
+                '~' => self.emit("~"),
+                'w' => {
+                    let text = format_term(&next_arg(&mut args)?, self.ops, false);
+                    self.emit(&text);
+                }
+                'q' | 'p' => {
+                    let text = format_term(&next_arg(&mut args)?, self.ops, true);
+                    self.emit(&text);
+                }
+                'a' => match next_arg(&mut args)?.deref() {
+                    Term::Var(_) => return Err(instantiation_error()),
+                    culprit @ (Term::Compound(_) | Term::Dict(_)) => return Err(type_error("atomic", culprit)),
+                    atomic => self.emit(&text(&atomic)?.unwrap_or_default()),
+                },
+                'd' | 'D' => match next_arg(&mut args)?.deref() {
+                    Term::Var(_) => return Err(instantiation_error()),
+                    Term::Int(n) => self.emit(&integer_text(n, numeric.unwrap_or(0), directive == 'D')),
+                    culprit => return Err(type_error("integer", culprit)),
+                },
+                'f' | 'e' | 'g' => {
+                    let x = match next_arg(&mut args)?.deref() {
+                        Term::Var(_) => return Err(instantiation_error()),
+                        Term::Int(n) => n as f64,
+                        Term::Rational(r) => r.to_f64(),
+                        Term::Float(x) => x,
+                        culprit => return Err(type_error("evaluable", culprit)),
+                    };
+                    let digits = numeric.unwrap_or(6);
+                    let text = match directive {
+                        'f' => format!("{:.*}", digits, x),
+                        'e' => exponent_text(x, digits),
+                        _ => general_text(x, digits),
+                    };
+                    self.emit(&text);
+                }
+                's' => match next_arg(&mut args)?.deref() {
+                    Term::Var(_) => return Err(instantiation_error()),
+                    Term::Str(text) => self.emit(&text),
+                    list if list.is_list() => self.emit(&list_text(&list)?),
+                    culprit => return Err(type_error("text", culprit)),
+                },
+                'n' => self.emit(&"\n".repeat(numeric.unwrap_or(1))),
+                'c' => match next_arg(&mut args)?.deref() {
+                    Term::Var(_) => return Err(instantiation_error()),
+                    Term::Int(code) => match u32::try_from(code).ok().and_then(char::from_u32) {
+                        Some(ch) => self.emit(&ch.to_string().repeat(numeric.unwrap_or(1))),
+                        None => return Err(representation_error("character_code")),
+                    },
+                    culprit => return Err(type_error("integer", culprit)),
+                },
+                'r' | 'R' => {
+                    let radix = match numeric {
+                        Some(radix @ 2..=36) => radix as u32,
+                        _ => return Err(format_error("~r needs a radix from 2 to 36")),
+                    };
+                    match next_arg(&mut args)?.deref() {
+                        Term::Var(_) => return Err(instantiation_error()),
+                        Term::Int(n) => self.emit(&radix_text(n, radix, directive == 'R')),
+                        culprit => return Err(type_error("integer", culprit)),
+                    }
+                }
+                'i' => {
+                    next_arg(&mut args)?;
+                }
+                't' => {
+                    let fill = numeric.and_then(|code| char::from_u32(code as u32)).unwrap_or(' ');
+                    self.fills.push((self.out.len(), fill));
+                }
+                '|' => {
+                    let column = numeric.unwrap_or_else(|| self.column());
+                    self.column_stop(column);
+                }
+                '+' => self.column_stop(self.stop + numeric.unwrap_or(8)),
+                other => return Err(format_error(&format!("unknown directive ~{}", other))),
+            }
+        }
+        if args.next().is_some() {
+            return Err(format_error("too many arguments"));
+        }
+        Ok(self.out)
+    }
+
+    fn emit(&mut self, text: &str) {
+        self.out.push_str(text);
+        if text.contains('\n') {
+            self.stop = 0;
+            self.fills.clear();
+        }
+    }
+
+    fn column(&self) -> usize {
+        match self.out.rfind('\n') {
+            Some(newline) => self.out[newline + 1..].chars().count(),
+            None => self.start_column + self.out.chars().count(),
+        }
+    }
+
+    // pads the segment out to `target`, sharing the padding between its
+    // fill points with the first ones taking any remainder
+    fn column_stop(&mut self, target: usize) {
+        let column = self.column();
+        if column < target {
+            if self.fills.is_empty() {
+                self.fills.push((self.out.len(), ' '));
+            }
+            let padding = target - column;
+            let (share, extra) = (padding / self.fills.len(), padding % self.fills.len());
+            for (i, (at, fill)) in self.fills.iter().enumerate().rev() {
+                let width = share + usize::from(i < extra);
+                self.out.insert_str(*at, &fill.to_string().repeat(width));
+            }
+        }
+        self.stop = target.max(column);
+        self.fills.clear();
+    }
+}
+
+fn next_arg(args: &mut impl Iterator<Item = Term>) -> Result<Term, Term> {
+    args.next().ok_or_else(|| format_error("not enough arguments"))
+}
+
+// `~Nd` puts a decimal point N digits from the right; `~D` also groups the
+// integer part in threes
+fn integer_text(n: i64, decimals: usize, group: bool) -> String {
+    let mut digits = n.unsigned_abs().to_string();
+    if digits.len() <= decimals {
+        digits = "0".repeat(decimals + 1 - digits.len()) + &digits;
+    }
+    let (integer, fraction) = digits.split_at(digits.len() - decimals);
+    let mut text = String::from(if n < 0 { "-" } else { "" });
+    for (i, digit) in integer.chars().enumerate() {
+        if group && i > 0 && (integer.len() - i) % 3 == 0 {
+            text.push(',');
+        }
+        text.push(digit);
+    }
+    if decimals > 0 {
+        text.push('.');
+        text.push_str(fraction);
+    }
+    text
+}
+
+// C's `%e`: a signed exponent of at least two digits
+fn exponent_text(x: f64, digits: usize) -> String {
+    let text = format!("{:.*e}", digits, x);
+    match text.split_once('e') {
+        Some((mantissa, exponent)) => {
+            let exponent: i32 = exponent.parse().unwrap_or(0);
+            let sign = if exponent < 0 { '-' } else { '+' };
+            format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+        }
+        None => text,
+    }
+}
+
+// C's `%g`: `%e` for very small or large numbers and `%f` otherwise, with
+// `digits` significant digits and no trailing zeros
+fn general_text(x: f64, digits: usize) -> String {
+    let precision = digits.max(1);
+    if !x.is_finite() {
+        return format!("{}", x);
+    }
+    let exponent: i32 = format!("{:.*e}", precision - 1, x)
+        .split_once('e')
+        .and_then(|(_, exponent)| exponent.parse().ok())
+        .unwrap_or(0);
+    if exponent < -4 || exponent >= precision as i32 {
+        let text = exponent_text(x, precision - 1);
+        match text.split_once('e') {
+            Some((mantissa, exponent)) => format!("{}e{}", trim_zeros(mantissa), exponent),
+            None => text,
+        }
+    } else {
+        let decimals = (precision as i32 - 1 - exponent).max(0) as usize;
+        trim_zeros(&format!("{:.*}", decimals, x)).to_string()
+    }
+}
+
+fn trim_zeros(text: &str) -> &str {
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        text
+    }
+}
+
+fn radix_text(n: i64, radix: u32, upper: bool) -> String {
+    let mut value = n.unsigned_abs();
+    let mut digits = Vec::new();
+    loop {
+        let digit = char::from_digit((value % radix as u64) as u32, radix).expect("a digit below the radix");
+        digits.push(if upper { digit.to_ascii_uppercase() } else { digit });
+        value /= radix as u64;
+        if value == 0 {
+            break;
+        }
+    }
+    if n < 0 {
+        digits.push('-');
+    }
+    digits.iter().rev().collect()
+}
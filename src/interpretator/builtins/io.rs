@@ -0,0 +1,511 @@
+use std::io::{self, ErrorKind};
+
+use crate::tokenizer::{StartsWith, Symbol};
+
+use super::{
+    super::{
+        error::{
+            domain_error, existence_error, instantiation_error, io_error, permission_error, syntax_error, type_error,
+            uninstantiation_error,
+        },
+        stream::{EndOfStream, EofAction, Input, Mode, Stream, StreamType, Streams, USER_OUTPUT},
+        write::format_term,
+    },
+    atoms::{chars, codes, read_term},
+    list_to_vec, Builtin, Runner, Term,
+};
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    let builtin: Builtin = match (name, arity) {
+        ("open", 3) => |runner, args| open(runner, &args[0], &args[1], &args[2], &Term::atom("[]")),
+        ("open", 4) => |runner, args| open(runner, &args[0], &args[1], &args[2], &args[3]),
+        ("close", 1) => |runner, args| close(runner, &args[0], &Term::atom("[]")),
+        ("close", 2) => |runner, args| close(runner, &args[0], &args[1]),
+        ("current_input", 1) => |runner, args| current(runner, &args[0], runner.streams.input),
+        ("current_output", 1) => |runner, args| current(runner, &args[0], runner.streams.output),
+        ("set_input", 1) => |runner, args| {
+            runner.streams.input = input_stream(runner, &args[0], None)?;
+            Ok(true)
+        },
+        ("set_output", 1) => |runner, args| {
+            runner.streams.output = output_stream(runner, &args[0], None)?;
+            Ok(true)
+        },
+        ("read", 1) => |runner, args| read(runner, &current_input(runner), &args[0]),
+        ("read", 2) => |runner, args| read(runner, &args[0], &args[1]),
+        ("get_char", 1) => |runner, args| get_char(runner, &current_input(runner), &args[0], Input::read_char),
+        ("get_char", 2) => |runner, args| get_char(runner, &args[0], &args[1], Input::read_char),
+        ("peek_char", 1) => |runner, args| get_char(runner, &current_input(runner), &args[0], Input::peek_char),
+        ("peek_char", 2) => |runner, args| get_char(runner, &args[0], &args[1], Input::peek_char),
+        ("get_byte", 1) => |runner, args| get_byte(runner, &current_input(runner), &args[0], Input::read_byte),
+        ("get_byte", 2) => |runner, args| get_byte(runner, &args[0], &args[1], Input::read_byte),
+        ("peek_byte", 1) => |runner, args| get_byte(runner, &current_input(runner), &args[0], Input::peek_byte),
+        ("peek_byte", 2) => |runner, args| get_byte(runner, &args[0], &args[1], Input::peek_byte),
+        ("at_end_of_stream", 0) => |runner, _| at_end_of_stream(runner, &current_input(runner)),
+        ("at_end_of_stream", 1) => |runner, args| at_end_of_stream(runner, &args[0]),
+        ("put_char", 1) => |runner, args| put_char(runner, &current_output(runner), &args[0]),
+        ("put_char", 2) => |runner, args| put_char(runner, &args[0], &args[1]),
+        ("put_byte", 1) => |runner, args| put_byte(runner, &current_output(runner), &args[0]),
+        ("put_byte", 2) => |runner, args| put_byte(runner, &args[0], &args[1]),
+        ("nl", 0) => |runner, _| write_text(runner, &current_output(runner), "\n"),
+        ("nl", 1) => |runner, args| write_text(runner, &args[0], "\n"),
+        ("write", 1) => |runner, args| write(runner, &current_output(runner), &args[0], false),
+        ("write", 2) => |runner, args| write(runner, &args[0], &args[1], false),
+        ("writeq", 1) | ("print", 1) => |runner, args| write(runner, &current_output(runner), &args[0], true),
+        ("writeq", 2) | ("print", 2) => |runner, args| write(runner, &args[0], &args[1], true),
+        ("flush_output", 0) => |runner, _| flush_output(runner, &current_output(runner)),
+        ("flush_output", 1) => |runner, args| flush_output(runner, &args[0]),
+        ("stream_property", 2) => stream_property,
+        ("with_output_to", 2) => with_output_to,
+        ("$begin_capture", 2) => |runner, args| {
+            let old = runner.streams.output;
+            runner.streams.output = runner.streams.add_buffer();
+            let id = Term::Int(runner.streams.output as i64);
+            Ok(runner.unify(&args[0], &id) && runner.unify(&args[1], &Term::Int(old as i64)))
+        },
+        ("$end_capture", 2) => |runner, args| {
+            end_capture(runner, &args[0], &args[1]);
+            Ok(true)
+        },
+        ("$end_capture", 3) => |runner, args| {
+            let text = end_capture(runner, &args[0], &args[1]);
+            unify_sink(runner, &args[2], &text)
+        },
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+fn current_input(runner: &Runner) -> Term {
+    Streams::term(runner.streams.input)
+}
+
+fn current_output(runner: &Runner) -> Term {
+    Streams::term(runner.streams.output)
+}
+
+// the open stream that a stream term or an alias names
+fn stream_id(runner: &Runner, term: &Term) -> Result<usize, Term> {
+    let term = term.deref();
+    let id = match &term {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Atom(alias) => runner.streams.by_alias(alias),
+        _ => match Streams::id(&term) {
+            Some(id) => Some(id),
+            None => return Err(domain_error("stream_or_alias", term)),
+        },
+    };
+    id.filter(|id| runner.streams.get(*id).is_some())
+        .ok_or_else(|| existence_error("stream", term))
+}
+
+// an input stream, of the given type when there is one
+fn input_stream(runner: &Runner, term: &Term, stream_type: Option<StreamType>) -> Result<usize, Term> {
+    let id = stream_id(runner, term)?;
+    let stream = runner.streams.get(id).expect("stream_id finds open streams");
+    if !stream.is_input() {
+        return Err(permission_error("input", "stream", term.deref()));
+    }
+    match stream_type {
+        Some(expected) if expected != stream.stream_type => {
+            Err(permission_error("input", &format!("{}_stream", stream.stream_type.name()), term.deref()))
+        }
+        _ => Ok(id),
+    }
+}
+
+pub fn output_stream(runner: &Runner, term: &Term, stream_type: Option<StreamType>) -> Result<usize, Term> {
+    let id = stream_id(runner, term)?;
+    let stream = runner.streams.get(id).expect("stream_id finds open streams");
+    if stream.is_input() {
+        return Err(permission_error("output", "stream", term.deref()));
+    }
+    match stream_type {
+        Some(expected) if expected != stream.stream_type => {
+            Err(permission_error("output", &format!("{}_stream", stream.stream_type.name()), term.deref()))
+        }
+        _ => Ok(id),
+    }
+}
+
+// Reads from an input stream of the given type. Once the stream is past its
+// end, its eof_action decides between an error, another end of file, or
+// trying the reader again.
+fn read_from<T>(
+    runner: &mut Runner,
+    term: &Term,
+    stream_type: StreamType,
+    read: impl FnOnce(&mut Input) -> io::Result<Option<T>>,
+) -> Result<Option<T>, Term> {
+    let id = input_stream(runner, term, Some(stream_type))?;
+    let stream = runner.streams.get_mut(id).expect("stream_id finds open streams");
+    let eof_action = stream.eof_action;
+    let input = stream.reader().expect("input_stream finds input streams");
+    if input.end == EndOfStream::Past {
+        match eof_action {
+            EofAction::Error => return Err(permission_error("input", "past_end_of_stream", term.deref())),
+            EofAction::EofCode => return Ok(None),
+            EofAction::Reset => input.end = EndOfStream::Not,
+        }
+    }
+    read(input).map_err(|_| io_error("read", term.deref()))
+}
+
+fn write_bytes(runner: &mut Runner, term: &Term, bytes: &[u8]) -> Result<bool, Term> {
+    let id = output_stream(runner, term, Some(StreamType::Binary))?;
+    let stream = runner.streams.get_mut(id).expect("stream_id finds open streams");
+    let writer = stream.writer().expect("output_stream finds output streams");
+    writer.write_all(bytes).map_err(|_| io_error("write", term.deref()))?;
+    Ok(true)
+}
+
+pub fn write_text(runner: &mut Runner, term: &Term, text: &str) -> Result<bool, Term> {
+    let id = output_stream(runner, term, Some(StreamType::Text))?;
+    let stream = runner.streams.get_mut(id).expect("stream_id finds open streams");
+    stream.write_text(text).map_err(|_| io_error("write", term.deref()))?;
+    Ok(true)
+}
+
+// open(+Source, +Mode, -Stream, +Options) for files, with the `type`,
+// `alias`, `eof_action` and `reposition` options
+fn open(runner: &mut Runner, source: &Term, mode: &Term, stream: &Term, options: &Term) -> Result<bool, Term> {
+    let file_name = match source.deref() {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Atom(name) => name,
+        culprit => return Err(domain_error("source_sink", culprit)),
+    };
+    let mode = match mode.deref() {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Atom(name) => Mode::from_name(&name).ok_or_else(|| domain_error("io_mode", Term::Atom(name)))?,
+        culprit => return Err(type_error("atom", culprit)),
+    };
+    if !matches!(stream.deref(), Term::Var(_)) {
+        return Err(uninstantiation_error(stream.deref()));
+    }
+    let (mut stream_type, mut eof_action, mut alias) = (StreamType::Text, EofAction::EofCode, None);
+    for option in list_to_vec(options)? {
+        let (name, value) = match option.deref() {
+            Term::Var(_) => return Err(instantiation_error()),
+            Term::Compound(c) if c.args.len() == 1 => match c.args[0].deref() {
+                Term::Var(_) => return Err(instantiation_error()),
+                Term::Atom(value) => (c.name.clone(), value),
+                _ => return Err(domain_error("stream_option", option.deref())),
+            },
+            culprit => return Err(domain_error("stream_option", culprit)),
+        };
+        match (&*name, &*value) {
+            ("type", "text") => stream_type = StreamType::Text,
+            ("type", "binary") => stream_type = StreamType::Binary,
+            ("eof_action", action) if EofAction::from_name(action).is_some() => {
+                eof_action = EofAction::from_name(action).expect("checked by the guard");
+            }
+            ("alias", _) => alias = Some(value),
+            ("reposition", "false") => {}
+            ("reposition", "true") => return Err(permission_error("open", "source_sink", option.deref())),
+            _ => return Err(domain_error("stream_option", option.deref())),
+        }
+    }
+    if let Some(alias) = &alias {
+        if runner.streams.by_alias(alias).is_some() {
+            let culprit = Term::compound("alias", vec![Term::Atom(alias.clone())]);
+            return Err(permission_error("open", "source_sink", culprit));
+        }
+    }
+    let mut opened = Stream::open(&file_name, mode, stream_type, eof_action).map_err(|error| match error.kind() {
+        ErrorKind::NotFound => existence_error("source_sink", Term::Atom(file_name.clone())),
+        _ => permission_error("open", "source_sink", Term::Atom(file_name.clone())),
+    })?;
+    opened.alias = alias;
+    let id = runner.streams.add(opened);
+    Ok(runner.unify(stream, &Streams::term(id)))
+}
+
+// close(+Stream, +Options); `force(true)` drops the stream even when
+// flushing it fails
+fn close(runner: &mut Runner, stream: &Term, options: &Term) -> Result<bool, Term> {
+    let mut force = false;
+    for option in list_to_vec(options)? {
+        match option.deref() {
+            Term::Var(_) => return Err(instantiation_error()),
+            Term::Compound(c) if &*c.name == "force" && c.args.len() == 1 => match c.args[0].deref() {
+                Term::Atom(value) if matches!(&*value, "true" | "false") => force = &*value == "true",
+                Term::Var(_) => return Err(instantiation_error()),
+                _ => return Err(domain_error("close_option", option.deref())),
+            },
+            culprit => return Err(domain_error("close_option", culprit)),
+        }
+    }
+    let id = stream_id(runner, stream)?;
+    match runner.streams.close(id) {
+        Err(_) if !force => Err(io_error("close", stream.deref())),
+        _ => Ok(true),
+    }
+}
+
+fn current(runner: &mut Runner, stream: &Term, id: usize) -> Result<bool, Term> {
+    match stream.deref() {
+        Term::Var(_) => {}
+        culprit if Streams::id(&culprit).is_none() => return Err(domain_error("stream", culprit)),
+        _ => {}
+    }
+    Ok(runner.unify(stream, &Streams::term(id)))
+}
+
+fn read(runner: &mut Runner, stream: &Term, term: &Term) -> Result<bool, Term> {
+    let read = match read_from(runner, stream, StreamType::Text, read_clause)? {
+        None => Term::atom("end_of_file"),
+        Some((text, true)) => read_term(runner, &text)?,
+        Some((_, false)) => return Err(syntax_error("end_of_file")),
+    };
+    Ok(runner.unify(term, &read))
+}
+
+// Reads the text of one clause up to its end `.`, leaving comments out.
+// `None` when only layout is left before the end of the stream; the flag is
+// false when the stream ended in the middle of the clause.
+fn read_clause(input: &mut Input) -> io::Result<Option<(String, bool)>> {
+    let (mut text, mut blank, mut quote) = (String::new(), true, None);
+    while let Some(ch) = input.read_char()? {
+        if let Some(open) = quote {
+            text.push(ch);
+            if ch == '\\' {
+                text.extend(input.read_char()?);
+            } else if ch == open {
+                if input.peek_char()? == Some(open) {
+                    text.extend(input.read_char()?);
+                } else {
+                    quote = None;
+                }
+            }
+            continue;
+        }
+        match ch {
+            '%' => {
+                while input.read_char()?.is_some_and(|ch| ch != '\n') {}
+                text.push('\n');
+                continue;
+            }
+            '/' if input.peek_char()? == Some('*') => {
+                input.read_char()?;
+                while let Some(ch) = input.read_char()? {
+                    if ch == '*' && input.peek_char()? == Some('/') {
+                        input.read_char()?;
+                        break;
+                    }
+                }
+                text.push(' ');
+                continue;
+            }
+            // `0'c`, the code of a character, which may be a quote
+            '\'' if is_char_code_prefix(&text) => {
+                text.push(ch);
+                if let Some(quoted) = input.read_char()? {
+                    text.push(quoted);
+                    if quoted == '\\' || (quoted == '\'' && input.peek_char()? == Some('\'')) {
+                        text.extend(input.read_char()?);
+                    }
+                }
+                continue;
+            }
+            '\'' | '"' | '`' => quote = Some(ch),
+            '.' if !text.ends_with(Symbol::starts_with) => {
+                let next = input.peek_char()?;
+                if next.is_none_or(|ch| ch.is_whitespace() || ch == '%') {
+                    if next.is_some_and(char::is_whitespace) {
+                        input.read_char()?;
+                    }
+                    text.push('.');
+                    return Ok(Some((text, true)));
+                }
+            }
+            _ => {}
+        }
+        text.push(ch);
+        blank &= ch.is_whitespace();
+    }
+    Ok(if blank { None } else { Some((text, false)) })
+}
+
+fn is_char_code_prefix(text: &str) -> bool {
+    let mut chars = text.chars().rev();
+    chars.next() == Some('0') && !chars.next().is_some_and(|ch| ch.is_alphanumeric() || ch == '_')
+}
+
+fn get_char(
+    runner: &mut Runner,
+    stream: &Term,
+    ch: &Term,
+    read: fn(&mut Input) -> io::Result<Option<char>>,
+) -> Result<bool, Term> {
+    match ch.deref() {
+        Term::Var(_) => {}
+        Term::Atom(name) if &*name == "end_of_file" || name.chars().count() == 1 => {}
+        culprit => return Err(type_error("in_character", culprit)),
+    }
+    let got = match read_from(runner, stream, StreamType::Text, read)? {
+        Some(got) => Term::atom(&got.to_string()),
+        None => Term::atom("end_of_file"),
+    };
+    Ok(runner.unify(ch, &got))
+}
+
+// bytes are integers from 0 to 255, with -1 at the end of the stream
+fn get_byte(
+    runner: &mut Runner,
+    stream: &Term,
+    byte: &Term,
+    read: fn(&mut Input) -> io::Result<Option<u8>>,
+) -> Result<bool, Term> {
+    match byte.deref() {
+        Term::Var(_) => {}
+        Term::Int(n) if (-1..=255).contains(&n) => {}
+        culprit => return Err(type_error("in_byte", culprit)),
+    }
+    let got = read_from(runner, stream, StreamType::Binary, read)?;
+    Ok(runner.unify(byte, &Term::Int(got.map_or(-1, i64::from))))
+}
+
+fn at_end_of_stream(runner: &mut Runner, stream: &Term) -> Result<bool, Term> {
+    let id = input_stream(runner, stream, None)?;
+    let input = runner.streams.get_mut(id).and_then(Stream::reader).expect("input_stream finds input streams");
+    if input.end == EndOfStream::Past {
+        return Ok(true);
+    }
+    let next = input.peek_byte().map_err(|_| io_error("read", stream.deref()))?;
+    Ok(next.is_none())
+}
+
+fn put_char(runner: &mut Runner, stream: &Term, ch: &Term) -> Result<bool, Term> {
+    match ch.deref() {
+        Term::Var(_) => Err(instantiation_error()),
+        Term::Atom(name) if name.chars().count() == 1 => write_text(runner, stream, &name),
+        culprit => Err(type_error("character", culprit)),
+    }
+}
+
+fn put_byte(runner: &mut Runner, stream: &Term, byte: &Term) -> Result<bool, Term> {
+    match byte.deref() {
+        Term::Var(_) => Err(instantiation_error()),
+        Term::Int(n) if (0..=255).contains(&n) => write_bytes(runner, stream, &[n as u8]),
+        culprit => Err(type_error("byte", culprit)),
+    }
+}
+
+fn write(runner: &mut Runner, stream: &Term, term: &Term, quoted: bool) -> Result<bool, Term> {
+    let text = format_term(term, &runner.ops, quoted);
+    write_text(runner, stream, &text)
+}
+
+fn flush_output(runner: &mut Runner, stream: &Term) -> Result<bool, Term> {
+    let id = output_stream(runner, stream, None)?;
+    runner.streams.flush(id).map_err(|_| io_error("flush", stream.deref()))?;
+    Ok(true)
+}
+
+// stream_property(?Stream, ?Property) over the open streams
+fn stream_property(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let ids: Vec<usize> = match args[0].deref() {
+        Term::Var(_) => runner.streams.ids().collect(),
+        stream => match Streams::id(&stream) {
+            Some(id) if runner.streams.get(id).is_some() => vec![id],
+            Some(_) => return Err(existence_error("stream", stream)),
+            None => return Err(domain_error("stream", stream)),
+        },
+    };
+    match args[1].deref() {
+        Term::Var(_) | Term::Atom(_) | Term::Compound(_) => {}
+        culprit => return Err(domain_error("stream_property", culprit)),
+    }
+    let property = |name: &str, value: &str| Term::compound(name, vec![Term::atom(value)]);
+    let mut candidates = Vec::new();
+    for id in ids {
+        let stream = runner.streams.get(id).expect("listed streams are open");
+        let mut properties = vec![
+            Term::atom(if stream.is_input() { "input" } else { "output" }),
+            property("mode", stream.mode.name()),
+            property("type", stream.stream_type.name()),
+        ];
+        if let Some(alias) = &stream.alias {
+            properties.push(property("alias", alias));
+        }
+        if let Some(file_name) = &stream.file_name {
+            properties.push(property("file_name", file_name));
+        }
+        if let Some(end) = stream.end_of_stream() {
+            properties.push(property("eof_action", stream.eof_action.name()));
+            properties.push(property("end_of_stream", end.name()));
+        }
+        candidates.extend(properties.into_iter().map(|p| Term::compound("-", vec![Streams::term(id), p])));
+    }
+    let pattern = Term::compound("-", vec![args[0].clone(), args[1].clone()]);
+    Ok(runner.unify_any(&pattern, candidates))
+}
+
+// Runs `Goal` once with the current output going to a buffer, then unifies
+// the sink with what it wrote. The output is switched back however the goal
+// ends.
+fn with_output_to(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    match args[0].deref() {
+        Term::Var(_) => return Err(instantiation_error()),
+        sink if !is_capture_sink(&sink) => return Err(domain_error("output_sink", sink)),
+        _ => {}
+    }
+    let (id, old, ball) = (
+        Term::var(&mut runner.id_factory),
+        Term::var(&mut runner.id_factory),
+        Term::var(&mut runner.id_factory),
+    );
+    let and = |lhs, rhs| Term::compound(",", vec![lhs, rhs]);
+    let end = Term::compound("$end_capture", vec![id.clone(), old.clone()]);
+    let rethrow = and(end.clone(), Term::compound("throw", vec![ball.clone()]));
+    let guarded = Term::compound("catch", vec![args[1].clone(), ball, rethrow]);
+    let captured = Term::compound("$end_capture", vec![id.clone(), old.clone(), args[0].clone()]);
+    let run = Term::compound(
+        ";",
+        vec![Term::compound("->", vec![guarded, captured]), and(end, Term::atom("fail"))],
+    );
+    let begin = Term::compound("$begin_capture", vec![id, old]);
+    runner.push_goal(and(begin, run), runner.choicepoints.len());
+    Ok(true)
+}
+
+// closes a capture buffer and goes back to the output that was current
+// before it
+fn end_capture(runner: &mut Runner, id: &Term, old: &Term) -> String {
+    let (id, old) = match (id.deref(), old.deref()) {
+        (Term::Int(id), Term::Int(old)) => (id as usize, old as usize),
+        _ => return String::new(),
+    };
+    let bytes = runner.streams.take_buffer(id).unwrap_or_default();
+    runner.streams.output = if runner.streams.get(old).is_some() { old } else { USER_OUTPUT };
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+pub fn is_capture_sink(sink: &Term) -> bool {
+    match sink.deref() {
+        Term::Compound(c) => matches!(
+            (&*c.name, c.args.len()),
+            ("atom" | "string" | "codes" | "chars", 1) | ("codes" | "chars", 2)
+        ),
+        _ => false,
+    }
+}
+
+// unifies `atom(A)`, `string(S)`, `codes(Cs)`, `codes(Cs, Tail)`,
+// `chars(Cs)` or `chars(Cs, Tail)` with captured text
+pub fn unify_sink(runner: &mut Runner, sink: &Term, text: &str) -> Result<bool, Term> {
+    let c = match sink.deref() {
+        Term::Compound(c) if is_capture_sink(sink) => c,
+        Term::Var(_) => return Err(instantiation_error()),
+        culprit => return Err(domain_error("output_sink", culprit)),
+    };
+    let captured = match (&*c.name, c.args.len()) {
+        ("atom", _) => Term::atom(text),
+        ("string", _) => Term::string(text),
+        ("codes", 1) => codes(text),
+        ("chars", 1) => chars(text),
+        ("codes", _) => Term::list(text.chars().map(|ch| Term::Int(ch as i64)).collect(), c.args[1].clone()),
+        (_, _) => Term::list(text.chars().map(|ch| Term::atom(&ch.to_string())).collect(), c.args[1].clone()),
+    };
+    Ok(runner.unify(&c.args[0], &captured))
+}
@@ -0,0 +1,84 @@
+use std::cmp::Ordering;
+
+use super::{
+    super::{
+        error::{domain_error, instantiation_error, type_error},
+        term::compare,
+    },
+    list_to_vec,
+    solutions::sort_unique,
+    Builtin, Runner, Term,
+};
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    let builtin: Builtin = match (name, arity) {
+        ("msort", 2) => |runner, args| {
+            let mut items = list_to_vec(&args[0])?;
+            items.sort_by(compare);
+            Ok(runner.unify(&args[1], &Term::from_list(items)))
+        },
+        ("sort", 2) => |runner, args| {
+            let mut items = list_to_vec(&args[0])?;
+            sort_unique(&mut items);
+            Ok(runner.unify(&args[1], &Term::from_list(items)))
+        },
+        ("sort", 4) => sort_4,
+        ("keysort", 2) => keysort,
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+// a stable sort of `Key-Value` pairs on their keys
+fn keysort(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let mut pairs = Vec::new();
+    for pair in list_to_vec(&args[0])? {
+        match pair.deref() {
+            Term::Var(_) => return Err(instantiation_error()),
+            Term::Compound(c) if &*c.name == "-" && c.args.len() == 2 => pairs.push((c.args[0].clone(), pair.clone())),
+            culprit => return Err(type_error("pair", culprit)),
+        }
+    }
+    pairs.sort_by(|a, b| compare(&a.0, &b.0));
+    let sorted = pairs.into_iter().map(|(_, pair)| pair).collect();
+    Ok(runner.unify(&args[1], &Term::from_list(sorted)))
+}
+
+// sort(Key, Order, List, Sorted): a stable sort on the `Key`th argument of
+// each element, or on the whole element when `Key` is 0. `@<` and `@>` drop
+// elements with equal keys, `@=<` and `@>=` keep them.
+fn sort_4(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let key = match args[0].deref() {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Int(n) if n < 0 => return Err(domain_error("not_less_than_zero", Term::Int(n))),
+        Term::Int(n) => n as usize,
+        culprit => return Err(type_error("integer", culprit)),
+    };
+    let (descending, unique) = match args[1].deref() {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Atom(order) => match &*order {
+            "@<" => (false, true),
+            "@=<" => (false, false),
+            "@>" => (true, true),
+            "@>=" => (true, false),
+            _ => return Err(domain_error("order", Term::Atom(order))),
+        },
+        culprit => return Err(type_error("atom", culprit)),
+    };
+    let mut keyed = Vec::new();
+    for item in list_to_vec(&args[2])? {
+        let sort_key = match (key, item.deref()) {
+            (0, _) => item.clone(),
+            (n, Term::Compound(c)) if n <= c.args.len() => c.args[n - 1].clone(),
+            (_, culprit) => return Err(type_error("compound", culprit)),
+        };
+        keyed.push((sort_key, item));
+    }
+    let order = |a: &Term, b: &Term| if descending { compare(b, a) } else { compare(a, b) };
+    keyed.sort_by(|a, b| order(&a.0, &b.0));
+    if unique {
+        keyed.dedup_by(|a, b| order(&a.0, &b.0) == Ordering::Equal);
+    }
+    let sorted = keyed.into_iter().map(|(_, item)| item).collect();
+    Ok(runner.unify(&args[3], &Term::from_list(sorted)))
+}
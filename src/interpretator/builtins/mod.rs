@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod tests;
+mod arithmetic;
+mod atoms;
+mod chars;
+mod control;
+mod database;
+mod dicts;
+mod format;
+mod io;
+mod lists;
+mod numbers;
+mod solutions;
+mod strings;
+mod system;
+mod terms;
+mod types;
+
+use super::{
+    error::{instantiation_error, type_error},
+    term::Term,
+    Runner,
+};
+
+// A builtin gets the dereferenced call arguments and reports success,
+// failure, or the formal part of an ISO error term.
+pub type Builtin = fn(&mut Runner, &[Term]) -> Result<bool, Term>;
+
+// the control constructs the engine runs itself
+const CONTROL: [(&str, usize); 13] = [
+    ("true", 0),
+    ("fail", 0),
+    ("false", 0),
+    ("!", 0),
+    (",", 2),
+    (";", 2),
+    ("->", 2),
+    ("*->", 2),
+    ("\\+", 1),
+    ("call", 1),
+    ("catch", 3),
+    ("throw", 1),
+    (":", 2),
+];
+
+pub fn is_builtin(name: &str, arity: usize) -> bool {
+    CONTROL.contains(&(name, arity)) || lookup(name, arity).is_some()
+}
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    control::lookup(name, arity)
+        .or_else(|| arithmetic::lookup(name, arity))
+        .or_else(|| numbers::lookup(name, arity))
+        .or_else(|| terms::lookup(name, arity))
+        .or_else(|| atoms::lookup(name, arity))
+        .or_else(|| chars::lookup(name, arity))
+        .or_else(|| strings::lookup(name, arity))
+        .or_else(|| solutions::lookup(name, arity))
+        .or_else(|| lists::lookup(name, arity))
+        .or_else(|| database::lookup(name, arity))
+        .or_else(|| dicts::lookup(name, arity))
+        .or_else(|| io::lookup(name, arity))
+        .or_else(|| format::lookup(name, arity))
+        .or_else(|| types::lookup(name, arity))
+        .or_else(|| system::lookup(name, arity))
+}
+
+// the builtin whose errors an internal helper raises, as the helper
+// finishes its work
+pub fn reported_as(name: &str, arity: usize) -> (&str, usize) {
+    match (name, arity) {
+        ("$aggregate_all", 3) => ("aggregate_all", 3),
+        ("$end_capture", 3) => ("with_output_to", 2),
+        _ => (name, arity),
+    }
+}
+
+// elements of a proper list, raising the ISO errors for partial lists and
+// non-lists
+pub fn list_to_vec(list: &Term) -> Result<Vec<Term>, Term> {
+    let (items, tail) = list.list_items();
+    match tail {
+        Term::Atom(name) if &*name == "[]" => Ok(items),
+        Term::Var(_) => Err(instantiation_error()),
+        _ => Err(type_error("list", list.deref())),
+    }
+}
@@ -0,0 +1,122 @@
+use super::{
+    super::{
+        error::{domain_error, evaluation_error, instantiation_error, type_error},
+        random::Rng,
+    },
+    Builtin, Runner, Term,
+};
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    let builtin: Builtin = match (name, arity) {
+        ("between", 3) => between,
+        ("succ", 2) => succ,
+        ("plus", 3) => plus,
+        ("numlist", 3) => |runner, args| {
+            let (low, high) = (integer(&args[0])?, integer(&args[1])?);
+            if high < low {
+                return Ok(false);
+            }
+            let list = (low..=high).map(Term::Int).collect();
+            Ok(runner.unify(&args[2], &Term::from_list(list)))
+        },
+        ("random", 1) => |runner, args| {
+            let value = Term::Float(runner.rng.next_float());
+            Ok(runner.unify(&args[0], &value))
+        },
+        ("random_between", 3) => |runner, args| {
+            let (low, high) = (integer(&args[0])?, integer(&args[1])?);
+            if high < low {
+                return Ok(false);
+            }
+            let value = Term::Int(runner.rng.between(low, high));
+            Ok(runner.unify(&args[2], &value))
+        },
+        ("set_random", 1) => set_random,
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+fn integer(term: &Term) -> Result<i64, Term> {
+    match term.deref() {
+        Term::Var(_) => Err(instantiation_error()),
+        Term::Int(n) => Ok(n),
+        culprit => Err(type_error("integer", culprit)),
+    }
+}
+
+// an integer or an unbound variable
+fn optional_integer(term: &Term) -> Result<Option<i64>, Term> {
+    match term.deref() {
+        Term::Var(_) => Ok(None),
+        Term::Int(n) => Ok(Some(n)),
+        culprit => Err(type_error("integer", culprit)),
+    }
+}
+
+fn natural(term: &Term) -> Result<Option<i64>, Term> {
+    match optional_integer(term)? {
+        Some(n) if n < 0 => Err(type_error("not_less_than_zero", Term::Int(n))),
+        n => Ok(n),
+    }
+}
+
+// Counts up from `Low` one solution at a time, each leaving a choice point
+// for the next, so an `inf` upper bound never runs out.
+fn between(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let low = integer(&args[0])?;
+    let high = match args[1].deref() {
+        Term::Atom(name) if matches!(&*name, "inf" | "infinite") => None,
+        high => Some(integer(&high)?),
+    };
+    if let Some(value) = optional_integer(&args[2])? {
+        return Ok(low <= value && high.is_none_or(|high| value <= high));
+    }
+    if high.is_some_and(|high| low > high) {
+        return Ok(false);
+    }
+    if high != Some(low) {
+        if let Some(next) = low.checked_add(1) {
+            let rest = Term::compound("between", vec![Term::Int(next), args[1].clone(), args[2].clone()]);
+            runner.push_alternative(rest, runner.choicepoints.len());
+        }
+    }
+    Ok(runner.unify(&args[2], &Term::Int(low)))
+}
+
+fn succ(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    match (natural(&args[0])?, natural(&args[1])?) {
+        (Some(n), _) => match n.checked_add(1) {
+            Some(next) => Ok(runner.unify(&args[1], &Term::Int(next))),
+            None => Err(evaluation_error("int_overflow")),
+        },
+        (None, Some(0)) => Ok(false),
+        (None, Some(n)) => Ok(runner.unify(&args[0], &Term::Int(n - 1))),
+        (None, None) => Err(instantiation_error()),
+    }
+}
+
+// `X + Y = Z` with any two of them known
+fn plus(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let overflow = || evaluation_error("int_overflow");
+    let values = [optional_integer(&args[0])?, optional_integer(&args[1])?, optional_integer(&args[2])?];
+    let (target, value) = match values {
+        [Some(x), Some(y), _] => (2, x.checked_add(y).ok_or_else(overflow)?),
+        [Some(x), None, Some(z)] => (1, z.checked_sub(x).ok_or_else(overflow)?),
+        [None, Some(y), Some(z)] => (0, z.checked_sub(y).ok_or_else(overflow)?),
+        _ => return Err(instantiation_error()),
+    };
+    Ok(runner.unify(&args[target], &Term::Int(value)))
+}
+
+fn set_random(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let option = args[0].deref();
+    match &option {
+        Term::Var(_) => Err(instantiation_error()),
+        Term::Compound(c) if &*c.name == "seed" && c.args.len() == 1 => {
+            runner.rng = Rng::seeded(integer(&c.args[0])? as u64);
+            Ok(true)
+        }
+        _ => Err(domain_error("set_random", option.clone())),
+    }
+}
@@ -0,0 +1,203 @@
+use std::cmp::Ordering;
+
+use super::{
+    super::{
+        error::{domain_error, instantiation_error, type_error},
+        term::{compare, variant},
+    },
+    arithmetic::{eval, eval_binary, num_cmp, Number},
+    list_to_vec, Builtin, Runner, Term,
+};
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    let builtin: Builtin = match (name, arity) {
+        ("findall", 3) => |runner, args| findall(runner, args, Term::atom("[]")),
+        ("findall", 4) => |runner, args| findall(runner, args, args[3].clone()),
+        ("bagof", 3) => |runner, args| bagof(runner, args, "bagof"),
+        ("setof", 3) => |runner, args| bagof(runner, args, "setof"),
+        ("forall", 2) => forall,
+        ("aggregate_all", 3) => aggregate_all,
+        // the second halves of bagof/3, setof/3 and aggregate_all/3, run once
+        // the solutions are in
+        ("$bagof", 4) => bag_groups,
+        ("$aggregate_all", 3) => aggregate,
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+fn callable_goal(goal: &Term) -> Result<Term, Term> {
+    match goal.deref() {
+        Term::Var(_) => Err(instantiation_error()),
+        goal if goal.is_callable() => Ok(goal),
+        culprit => Err(type_error("callable", culprit)),
+    }
+}
+
+// the result argument may be unbound or a partial list, anything else could
+// never unify with the solutions
+fn result_list(list: &Term) -> Result<Term, Term> {
+    match list.list_items().1 {
+        Term::Var(_) => Ok(list.clone()),
+        Term::Atom(name) if &*name == "[]" => Ok(list.clone()),
+        _ => Err(type_error("list", list.deref())),
+    }
+}
+
+fn findall(runner: &mut Runner, args: &[Term], tail: Term) -> Result<bool, Term> {
+    let goal = callable_goal(&args[1])?;
+    let list = result_list(&args[2])?;
+    runner.find_all(args[0].clone(), goal, list, tail);
+    Ok(true)
+}
+
+// Collects `Witness-Template` pairs, where the witness holds the variables of
+// the goal that are neither in the template nor quantified with `^`, and
+// leaves it to `$bagof/4` to group them.
+fn bagof(runner: &mut Runner, args: &[Term], kind: &str) -> Result<bool, Term> {
+    let mut goal = args[1].deref();
+    let mut quantified = args[0].variables();
+    loop {
+        let inner = match &goal {
+            Term::Compound(c) if &*c.name == "^" && c.args.len() == 2 => {
+                quantified.extend(c.args[0].variables());
+                c.args[1].deref()
+            }
+            _ => break,
+        };
+        goal = inner;
+    }
+    let goal = callable_goal(&goal)?;
+    result_list(&args[2])?;
+    let witness: Vec<Term> = goal
+        .variables()
+        .into_iter()
+        .filter(|v| !quantified.iter().any(|q| compare(q, v) == Ordering::Equal))
+        .collect();
+    let witness = Term::from_list(witness);
+    let pairs = Term::var(&mut runner.id_factory);
+    let groups = Term::compound("$bagof", vec![pairs.clone(), witness.clone(), args[2].clone(), Term::atom(kind)]);
+    let cut_barrier = runner.choicepoints.len();
+    runner.push_goal(groups, cut_barrier);
+    let template = Term::compound("-", vec![witness, args[0].clone()]);
+    runner.find_all(template, goal, pairs, Term::atom("[]"));
+    Ok(true)
+}
+
+// Groups the solutions by variant witnesses, ordered by witness, and yields
+// one bag per group on backtracking. There is no bag without solutions.
+fn bag_groups(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let mut groups: Vec<(Term, Vec<Term>)> = Vec::new();
+    for pair in list_to_vec(&args[0])? {
+        let (witness, item) = match pair.deref() {
+            Term::Compound(c) => (c.args[0].clone(), c.args[1].clone()),
+            _ => continue,
+        };
+        match groups.iter_mut().find(|(key, _)| variant(key, &witness)) {
+            Some((_, items)) => items.push(item),
+            None => groups.push((witness, vec![item])),
+        }
+    }
+    groups.sort_by(|a, b| compare(&a.0, &b.0));
+    let sorted = matches!(args[3].deref(), Term::Atom(kind) if &*kind == "setof");
+    let candidates = groups
+        .into_iter()
+        .map(|(witness, mut items)| {
+            if sorted {
+                sort_unique(&mut items);
+            }
+            Term::compound("-", vec![witness, Term::from_list(items)])
+        })
+        .collect();
+    let target = Term::compound("-", vec![args[1].clone(), args[2].clone()]);
+    Ok(runner.unify_any(&target, candidates))
+}
+
+// sorts in the standard order of terms and drops duplicates
+pub fn sort_unique(items: &mut Vec<Term>) {
+    items.sort_by(compare);
+    items.dedup_by(|a, b| compare(a, b) == Ordering::Equal);
+}
+
+fn forall(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    callable_goal(&args[0])?;
+    callable_goal(&args[1])?;
+    let counterexample = Term::compound(",", vec![args[0].clone(), Term::compound("\\+", vec![args[1].clone()])]);
+    let cut_barrier = runner.choicepoints.len();
+    runner.push_goal(Term::compound("\\+", vec![counterexample]), cut_barrier);
+    Ok(true)
+}
+
+fn aggregate_all(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let spec = args[0].deref();
+    let template = match &spec {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Atom(name) if &**name == "count" => spec.clone(),
+        Term::Compound(c) => match (&*c.name, c.args.len()) {
+            ("count" | "sum" | "max" | "min" | "bag" | "set", 1) => c.args[0].clone(),
+            ("max" | "min", 2) => Term::compound("-", c.args.to_vec()),
+            _ => return Err(domain_error("aggregate_spec", spec.clone())),
+        },
+        _ => return Err(domain_error("aggregate_spec", spec.clone())),
+    };
+    let goal = callable_goal(&args[1])?;
+    let found = Term::var(&mut runner.id_factory);
+    let aggregate = Term::compound("$aggregate_all", vec![spec, found.clone(), args[2].clone()]);
+    let cut_barrier = runner.choicepoints.len();
+    runner.push_goal(aggregate, cut_barrier);
+    runner.find_all(template, goal, found, Term::atom("[]"));
+    Ok(true)
+}
+
+fn aggregate(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let mut found = list_to_vec(&args[1])?;
+    let (name, arity) = match args[0].name_arity() {
+        Some(key) => key,
+        None => return Err(instantiation_error()),
+    };
+    let result = match (&*name, arity) {
+        ("count", _) => Term::Int(found.len() as i64),
+        ("sum", 1) => {
+            let mut sum = Number::Int(0);
+            for item in &found {
+                sum = eval_binary("+", sum, eval(item, &runner.flags)?, &runner.flags)?;
+            }
+            sum.to_term()
+        }
+        ("max", _) | ("min", _) => {
+            let keep = if &*name == "max" { Ordering::Greater } else { Ordering::Less };
+            let mut best: Option<(Option<Number>, Term, Term)> = None;
+            for item in &found {
+                let (expr, witness) = match item.deref() {
+                    Term::Compound(c) if arity == 2 => (c.args[0].clone(), c.args[1].clone()),
+                    _ => (item.clone(), item.clone()),
+                };
+                // values that are not arithmetic go by the standard order
+                let (number, value) = match eval(&expr, &runner.flags) {
+                    Ok(number) => (Some(number), number.to_term()),
+                    Err(_) if expr.variables().is_empty() => (None, expr.deref()),
+                    Err(error) => return Err(error),
+                };
+                let order = match (&best, number) {
+                    (None, _) => Some(keep),
+                    (Some((Some(current), _, _)), Some(number)) => num_cmp(number, *current),
+                    (Some((_, current, _)), _) => Some(compare(&value, current)),
+                };
+                if order == Some(keep) {
+                    best = Some((number, value, witness));
+                }
+            }
+            match best {
+                None => return Ok(false),
+                Some((_, value, _)) if arity == 1 => value,
+                Some((_, value, witness)) => Term::compound(&name, vec![value, witness]),
+            }
+        }
+        ("bag", _) => Term::from_list(found),
+        _ => {
+            sort_unique(&mut found);
+            Term::from_list(found)
+        }
+    };
+    Ok(runner.unify(&args[2], &result))
+}
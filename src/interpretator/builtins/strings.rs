@@ -0,0 +1,62 @@
+use super::{
+    super::error::{instantiation_error, type_error},
+    atoms::{chars, codes, concat, length, list_text, relate, required_text, sub_text},
+    Builtin, Runner, Term,
+};
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    let builtin: Builtin = match (name, arity) {
+        ("string_concat", 3) => |runner, args| concat(runner, args, Term::string),
+        ("split_string", 4) => split_string,
+        ("sub_string", 5) => |runner, args| sub_text(runner, args, "string"),
+        ("string_code", 3) => string_code,
+        ("string_chars", 2) => |runner, args| relate(runner, args, Term::string, chars, list_text),
+        ("string_codes", 2) => |runner, args| relate(runner, args, Term::string, codes, list_text),
+        ("string_to_atom", 2) => |runner, args| relate(runner, args, Term::string, Term::atom, required_text),
+        ("atom_string", 2) => |runner, args| relate(runner, args, Term::atom, Term::string, required_text),
+        ("string_length", 2) => length,
+        ("string_upper", 2) => |runner, args| {
+            let upper = Term::string(&required_text(&args[0])?.to_uppercase());
+            Ok(runner.unify(&args[1], &upper))
+        },
+        ("string_lower", 2) => |runner, args| {
+            let lower = Term::string(&required_text(&args[0])?.to_lowercase());
+            Ok(runner.unify(&args[1], &lower))
+        },
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+// Splits the text at every character of `SepChars` and strips the characters
+// of `Pad` from both ends of each field. With no separators the whole text
+// is one field, so the call only strips it.
+fn split_string(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let whole = required_text(&args[0])?;
+    let separators: Vec<char> = required_text(&args[1])?.chars().collect();
+    let pad: Vec<char> = required_text(&args[2])?.chars().collect();
+    let fields = whole
+        .split(|ch| separators.contains(&ch))
+        .map(|field| Term::string(field.trim_matches(|ch| pad.contains(&ch))))
+        .collect();
+    Ok(runner.unify(&args[3], &Term::from_list(fields)))
+}
+
+// the code of the character at a one-based index
+fn string_code(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let index = match args[0].deref() {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Int(n) => n,
+        culprit => return Err(type_error("integer", culprit)),
+    };
+    let whole = required_text(&args[1])?;
+    let code = index
+        .checked_sub(1)
+        .and_then(|i| usize::try_from(i).ok())
+        .and_then(|i| whole.chars().nth(i))
+        .map(|ch| Term::Int(ch as i64));
+    match code {
+        Some(code) => Ok(runner.unify(&args[2], &code)),
+        None => Ok(false),
+    }
+}
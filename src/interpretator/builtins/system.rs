@@ -0,0 +1,208 @@
+use std::rc::Rc;
+
+use super::{
+    super::{
+        error::{domain_error, existence_error, instantiation_error, type_error},
+        flags::Flags,
+        library,
+        module::{meta_argument, qualify, strip, Module, USER},
+    },
+    database::predicate_indicator,
+    list_to_vec, Builtin, Runner, Term,
+};
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    let builtin: Builtin = match (name, arity) {
+        ("set_prolog_flag", 2) => set_prolog_flag,
+        ("current_prolog_flag", 2) => current_prolog_flag,
+        ("module", 2) => module,
+        ("use_module", 1) => |runner, args| use_module(runner, &args[0], None),
+        ("use_module", 2) => |runner, args| use_module(runner, &args[0], Some(&args[1])),
+        ("meta_predicate", 1) => |runner, args| meta_predicate(runner, &args[0]),
+        ("current_module", 1) => |runner, args| {
+            let mut modules: Vec<Rc<str>> = runner.modules.keys().cloned().collect();
+            modules.sort();
+            Ok(runner.unify_any(&args[0], modules.into_iter().map(Term::Atom).collect()))
+        },
+        ("consult", 1) => |runner, args| consult(runner, &args[0], false),
+        ("ensure_loaded", 1) => |runner, args| consult(runner, &args[0], true),
+        (".", 2) => |runner, args| consult(runner, &Term::compound(".", args.to_vec()), false),
+        ("include", 1) => |runner, args| {
+            let path = runner.resolve_file(&args[0])?;
+            runner.include(&path)?;
+            Ok(true)
+        },
+        ("source_file", 1) => |runner, args| {
+            let files = runner.files.iter().map(|file| Term::Atom(file.clone())).collect();
+            Ok(runner.unify_any(&args[0], files))
+        },
+        ("source_file", 2) => source_file,
+        ("statistics", 2) => statistics,
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+fn set_prolog_flag(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    runner.flags.set(&args[0], &args[1])?;
+    Ok(true)
+}
+
+fn current_prolog_flag(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let names: Vec<&str> = match args[0].deref() {
+        Term::Var(_) => Flags::NAMES.to_vec(),
+        Term::Atom(name) => match Flags::NAMES.iter().find(|flag| **flag == &*name) {
+            Some(flag) => vec![*flag],
+            None => return Err(domain_error("prolog_flag", Term::Atom(name))),
+        },
+        culprit => return Err(type_error("atom", culprit)),
+    };
+    let candidates = names
+        .into_iter()
+        .filter_map(|name| {
+            let value = runner.flags.get(name)?;
+            Some(Term::compound("flag", vec![Term::atom(name), value]))
+        })
+        .collect();
+    let flag = Term::compound("flag", args.to_vec());
+    Ok(runner.unify_any(&flag, candidates))
+}
+
+// module(+Name, +Exports) starts a module file: the clauses after it belong
+// to module Name, which exports the predicates listed
+fn module(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let name = match args[0].deref() {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Atom(name) => name,
+        culprit => return Err(type_error("atom", culprit)),
+    };
+    let exports = list_to_vec(&args[1])?
+        .iter()
+        .map(predicate_indicator)
+        .collect::<Result<Vec<_>, Term>>()?;
+    let file = runner.loading.last().map(|(owner, _)| owner.clone());
+    runner.modules.insert(name.clone(), Module { file, exports, ..Module::default() });
+    runner.database.touch();
+    runner.source_module = name;
+    Ok(true)
+}
+
+// use_module(+Spec) and use_module(+Spec, +Imports) load a bundled
+// `library(Name)` or a module file, unless it is loaded already, and import
+// all of its exports or the ones listed into the current module
+fn use_module(runner: &mut Runner, spec: &Term, imports: Option<&Term>) -> Result<bool, Term> {
+    let imports = match imports {
+        Some(list) => Some(list_to_vec(list)?.iter().map(predicate_indicator).collect::<Result<Vec<_>, Term>>()?),
+        None => None,
+    };
+    let spec = spec.deref();
+    let module = match &spec {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Compound(c) if &*c.name == "library" && c.args.len() == 1 => {
+            let found = match c.args[0].deref() {
+                Term::Var(_) => return Err(instantiation_error()),
+                Term::Atom(name) => library::find(&name),
+                _ => None,
+            };
+            match found {
+                Some(library) => Some(runner.load_library(library)),
+                None => return Err(existence_error("source_sink", spec)),
+            }
+        }
+        file => {
+            let path = runner.resolve_file(file)?;
+            if !runner.files.iter().any(|loaded| **loaded == *path) {
+                runner.consult(&path)?;
+            }
+            runner.file_module(&path)
+        }
+    };
+    if let Some(module) = module {
+        runner.import(&module, imports);
+    }
+    Ok(true)
+}
+
+// meta_predicate(+Heads) declares which arguments of predicates of the
+// current module are goals or closures, as in `maplist(1, ?)`
+fn meta_predicate(runner: &mut Runner, spec: &Term) -> Result<bool, Term> {
+    let (module, spec) = strip(spec, runner.module.clone())?;
+    let (name, specs) = match &spec {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Compound(c) if &*c.name == "," && c.args.len() == 2 => {
+            meta_predicate(runner, &qualify(&c.args[0], &module))?;
+            return meta_predicate(runner, &qualify(&c.args[1], &module));
+        }
+        Term::Compound(c) => (c.name.clone(), c.args.to_vec()),
+        culprit => return Err(type_error("compound", culprit.clone())),
+    };
+    if let Some(culprit) = specs.iter().find(|spec| meta_argument(spec).is_none()) {
+        return Err(domain_error("meta_argument_specifier", culprit.deref()));
+    }
+    let key = (name, specs.len());
+    runner.modules.entry(module).or_default().meta.insert(key, specs);
+    runner.database.touch();
+    Ok(true)
+}
+
+// consult/1 and `[File, ...]` load a file, a bundled `library(Name)` or a
+// list of them, importing what a module file exports; ensure_loaded/1 skips
+// files that were loaded already
+fn consult(runner: &mut Runner, spec: &Term, once: bool) -> Result<bool, Term> {
+    match spec.deref() {
+        Term::Var(_) => Err(instantiation_error()),
+        Term::Atom(name) if &*name == "[]" => Ok(true),
+        list @ Term::Compound(_) if list.name_arity().is_some_and(|(name, arity)| &*name == "." && arity == 2) => {
+            for item in list_to_vec(&list)? {
+                consult(runner, &item, once)?;
+            }
+            Ok(true)
+        }
+        library @ Term::Compound(_) => use_module(runner, &library, None),
+        file => {
+            let path = runner.resolve_file(&file)?;
+            if !(once && runner.files.iter().any(|loaded| **loaded == *path)) {
+                runner.consult(&path)?;
+            }
+            if let Some(module) = runner.file_module(&path) {
+                runner.import(&module, None);
+            }
+            Ok(true)
+        }
+    }
+}
+
+// source_file(?Head, ?File): the consulted file that defines a predicate;
+// heads of predicates outside `user` are qualified with their module
+fn source_file(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let mut candidates = Vec::new();
+    for file in runner.files.clone() {
+        for (module, (name, arity)) in runner.database.defined_in(&file) {
+            let head = match arity {
+                0 => Term::Atom(name),
+                _ => Term::compound(&name, (0..arity).map(|_| Term::var(&mut runner.id_factory)).collect()),
+            };
+            let head = match &*module {
+                USER => head,
+                _ => qualify(&head, &module),
+            };
+            candidates.push(Term::compound("-", vec![head, Term::Atom(file.clone())]));
+        }
+    }
+    let pattern = Term::compound("-", vec![args[0].clone(), args[1].clone()]);
+    Ok(runner.unify_any(&pattern, candidates))
+}
+
+// statistics(+Key, -Value) reports on the engine: `trailused` is the number
+// of bindings on the trail and `localused` the number of frames in the
+// continuation of the call
+fn statistics(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let value = match args[0].deref() {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Atom(key) if &*key == "trailused" => runner.trail.len(),
+        Term::Atom(key) if &*key == "localused" => runner.continuation_depth(),
+        culprit @ Term::Atom(_) => return Err(domain_error("statistics_key", culprit)),
+        culprit => return Err(type_error("atom", culprit)),
+    };
+    Ok(runner.unify(&args[1], &Term::Int(value as i64)))
+}
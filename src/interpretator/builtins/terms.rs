@@ -0,0 +1,157 @@
+use std::cmp::Ordering;
+
+use std::collections::HashMap;
+
+use super::{
+    super::{
+        error::{domain_error, instantiation_error, type_error},
+        term::{compare, unify_with_occurs_check},
+    },
+    list_to_vec, Builtin, Runner, Term,
+};
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    let builtin: Builtin = match (name, arity) {
+        ("=", 2) => |runner, args| Ok(runner.unify(&args[0], &args[1])),
+        ("\\=", 2) => not_unifiable,
+        ("unify_with_occurs_check", 2) => |runner, args| {
+            Ok(unify_with_occurs_check(&args[0], &args[1], &mut runner.trail))
+        },
+        ("==", 2) => |_, args| Ok(compare(&args[0], &args[1]) == Ordering::Equal),
+        ("\\==", 2) => |_, args| Ok(compare(&args[0], &args[1]) != Ordering::Equal),
+        ("@<", 2) => |_, args| Ok(compare(&args[0], &args[1]) == Ordering::Less),
+        ("@>", 2) => |_, args| Ok(compare(&args[0], &args[1]) == Ordering::Greater),
+        ("@=<", 2) => |_, args| Ok(compare(&args[0], &args[1]) != Ordering::Greater),
+        ("@>=", 2) => |_, args| Ok(compare(&args[0], &args[1]) != Ordering::Less),
+        ("compare", 3) => compare_3,
+        ("functor", 3) => functor,
+        ("arg", 3) => arg,
+        ("=..", 2) => univ,
+        ("copy_term", 2) => copy_term,
+        ("term_variables", 2) => |runner, args| {
+            let variables = Term::from_list(args[0].variables());
+            Ok(runner.unify(&args[1], &variables))
+        },
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+fn not_unifiable(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let trail_len = runner.trail.mark(&runner.id_factory);
+    let unifiable = runner.unify(&args[0], &args[1]);
+    runner.undo_trail(trail_len);
+    Ok(!unifiable)
+}
+
+fn compare_3(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    match args[0].deref() {
+        Term::Var(_) => {}
+        Term::Atom(order) if matches!(&*order, "<" | "=" | ">") => {}
+        Term::Atom(order) => return Err(domain_error("order", Term::Atom(order))),
+        culprit => return Err(type_error("atom", culprit)),
+    }
+    let order = match compare(&args[1], &args[2]) {
+        Ordering::Less => "<",
+        Ordering::Equal => "=",
+        Ordering::Greater => ">",
+    };
+    Ok(runner.unify(&args[0], &Term::atom(order)))
+}
+
+fn functor(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let term = args[0].deref();
+    match &term {
+        Term::Var(_) => {}
+        Term::Compound(c) => {
+            let name = Term::Atom(c.name.clone());
+            let arity = Term::Int(c.args.len() as i64);
+            return Ok(runner.unify(&args[1], &name) && runner.unify(&args[2], &arity));
+        }
+        _ => return Ok(runner.unify(&args[1], &term) && runner.unify(&args[2], &Term::Int(0))),
+    }
+    let (name, arity) = (args[1].deref(), args[2].deref());
+    let arity = match arity {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Int(n) if n < 0 => return Err(domain_error("not_less_than_zero", Term::Int(n))),
+        Term::Int(n) => n as usize,
+        culprit => return Err(type_error("integer", culprit)),
+    };
+    let built = match name {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Compound(_) => return Err(type_error("atomic", name)),
+        _ if arity == 0 => name,
+        Term::Atom(name) => {
+            let args: Vec<Term> = (0..arity).map(|_| Term::var(&mut runner.id_factory)).collect();
+            Term::new_compound(name, args)
+        }
+        culprit => return Err(type_error("atom", culprit)),
+    };
+    Ok(runner.unify(&term, &built))
+}
+
+fn arg(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let compound = match args[1].deref() {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Compound(c) => c,
+        culprit => return Err(type_error("compound", culprit)),
+    };
+    match args[0].deref() {
+        Term::Var(_) => {
+            // enumerate the positions on backtracking
+            let pair = Term::compound("-", vec![args[0].clone(), args[2].clone()]);
+            let candidates = compound
+                .args
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| Term::compound("-", vec![Term::Int(i as i64 + 1), arg.clone()]))
+                .collect();
+            Ok(runner.unify_any(&pair, candidates))
+        }
+        Term::Int(n) if n < 0 => Err(domain_error("not_less_than_zero", Term::Int(n))),
+        Term::Int(n) => match compound.args.get((n as usize).wrapping_sub(1)) {
+            Some(arg) => {
+                let arg = arg.clone();
+                Ok(runner.unify(&args[2], &arg))
+            }
+            None => Ok(false),
+        },
+        culprit => Err(type_error("integer", culprit)),
+    }
+}
+
+fn univ(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let term = args[0].deref();
+    let list = match &term {
+        Term::Var(_) => {
+            let items = list_to_vec(&args[1])?;
+            let built = match items.split_first() {
+                None => return Err(domain_error("non_empty_list", Term::atom("[]"))),
+                Some((head, [])) => match head.deref() {
+                    Term::Var(_) => return Err(instantiation_error()),
+                    culprit @ Term::Compound(_) => return Err(type_error("atomic", culprit)),
+                    atomic => atomic,
+                },
+                Some((head, rest)) => match head.deref() {
+                    Term::Var(_) => return Err(instantiation_error()),
+                    Term::Atom(name) => Term::new_compound(name, rest.to_vec()),
+                    culprit @ Term::Compound(_) => return Err(type_error("atomic", culprit)),
+                    culprit => return Err(type_error("atom", culprit)),
+                },
+            };
+            return Ok(runner.unify(&term, &built));
+        }
+        Term::Compound(c) => {
+            let mut items = vec![Term::Atom(c.name.clone())];
+            items.extend(c.args.iter().cloned());
+            Term::from_list(items)
+        }
+        atomic => Term::from_list(vec![atomic.clone()]),
+    };
+    Ok(runner.unify(&args[1], &list))
+}
+
+fn copy_term(runner: &mut Runner, args: &[Term]) -> Result<bool, Term> {
+    let copy = args[0].rename(&mut HashMap::new(), &mut runner.id_factory);
+    Ok(runner.unify(&args[1], &copy))
+}
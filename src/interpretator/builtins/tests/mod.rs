@@ -0,0 +1,816 @@
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+use super::super::tests::{runner, solve, solve_error};
+
+// a writer whose bytes stay readable after the runner takes it
+#[derive(Clone, Default)]
+struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Buffer {
+  fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+    self.0.borrow_mut().extend_from_slice(bytes);
+    Ok(bytes.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+// what `query` writes to user_output over all its answers
+fn output(program: &str, query: &str) -> String {
+  let mut runner = runner(program, query);
+  let buffer = Buffer::default();
+  runner.set_user_output(Box::new(buffer.clone()));
+  while runner.next_solution().unwrap().is_some() {}
+  let text = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+  text
+}
+
+#[test]
+fn it_evaluates_arithmetic() {
+  assert_eq!(solve("", "X is 1 + 2 * 3 - 4.", "X"), vec!["3"]);
+  assert_eq!(solve("", "X is 7 / 2.", "X"), vec!["3.5"]);
+  assert_eq!(solve("", "X is 8 / 2.", "X"), vec!["4"]);
+  assert_eq!(solve("", "X is -7 // 2.", "X"), vec!["-3"]);
+  assert_eq!(solve("", "X is -7 mod 2.", "X"), vec!["1"]);
+  assert_eq!(solve("", "X is -7 rem 2.", "X"), vec!["-1"]);
+  assert_eq!(solve("", "X is -7 div 2.", "X"), vec!["-4"]);
+  assert_eq!(solve("", "X is 2 ** 10.", "X"), vec!["1024"]);
+  assert_eq!(solve("", "X is 2 ^ 0.5.", "X"), vec!["1.4142135623730951"]);
+  assert_eq!(solve("", "X is max(3, 4.0) + abs(-2).", "X"), vec!["6.0"]);
+  assert_eq!(solve("", "X is gcd(12, 18) << 2.", "X"), vec!["24"]);
+  assert_eq!(solve("", "X is 1 << (-9223372036854775807 - 1).", "X"), vec!["0"]);
+  assert_eq!(solve("", "X is -5 >> 9223372036854775807.", "X"), vec!["-1"]);
+  assert_eq!(solve("", "X is 0 >> (-9223372036854775807 - 1).", "X"), vec!["0"]);
+  assert_eq!(solve("", "X is (-9223372036854775807 - 1) mod -1.", "X"), vec!["0"]);
+  assert_eq!(solve("", "X is (-9223372036854775807 - 1) rem -1.", "X"), vec!["0"]);
+  assert_eq!(solve("", "X is 5 /\\ 3 \\/ 8 xor 1.", "X"), vec!["8"]);
+  assert_eq!(solve("", "X is msb(1000).", "X"), vec!["9"]);
+  assert_eq!(solve("", "X is truncate(3.7) + round(2.5) + ceiling(0.1).", "X"), vec!["7"]);
+  assert_eq!(solve("", "X is float(1) + 0'a.", "X"), vec!["98.0"]);
+  assert_eq!(solve("", "X is cos(pi).", "X"), vec!["-1.0"]);
+}
+
+#[test]
+fn it_compares_numbers() {
+  assert_eq!(solve("", "1 + 1 =:= 2.0.", "X"), vec![""]);
+  assert_eq!(solve("", "1 =\\= 2, 1 < 2, 2 > 1, 2 =< 2, 3 >= 2.5.", "X"), vec![""]);
+  assert!(solve("", "2 < 1.", "X").is_empty());
+}
+
+#[test]
+fn it_raises_arithmetic_errors() {
+  assert!(solve_error("", "X is Y + 1.").starts_with("error(instantiation_error,context(/(is,2),_G"));
+  assert!(solve_error("", "X is foo + 1.").starts_with("error(type_error(evaluable,/(foo,0))"));
+  assert!(solve_error("", "X is bar(1, 2, 3).").starts_with("error(type_error(evaluable,/(bar,3))"));
+  assert!(solve_error("", "X is 1 // 0.").starts_with("error(evaluation_error(zero_divisor)"));
+  assert!(solve_error("", "X is 1.5 mod 2.").starts_with("error(type_error(integer,1.5)"));
+  assert!(solve_error("", "X is 1 >> (-9223372036854775807 - 1).").starts_with("error(evaluation_error(int_overflow)"));
+  assert!(solve_error("", "1 < a.").starts_with("error(type_error(evaluable,/(a,0))"));
+}
+
+#[test]
+fn it_computes_with_rationals() {
+  assert_eq!(solve("", "X is 1r3 + 1r6.", "X"), vec!["1r2"]);
+  assert_eq!(solve("", "X is 1r3 * 3.", "X"), vec!["1"]);
+  assert_eq!(solve("", "X is 1 rdiv 3 - 1.", "X"), vec!["-2r3"]);
+  assert_eq!(solve("", "X is (2r3) ^ 2.", "X"), vec!["4r9"]);
+  assert_eq!(solve("", "X is 2r3 ** -1.", "X"), vec!["3r2"]);
+  assert_eq!(solve("", "X is 1r4 + 0.5.", "X"), vec!["0.75"]);
+  assert_eq!(solve("", "X is numerator(-6r4) + denominator(-6r4).", "X"), vec!["-1"]);
+  assert_eq!(solve("", "X is rationalize(0.1).", "X"), vec!["1r10"]);
+  assert_eq!(solve("", "X is rational(0.25).", "X"), vec!["1r4"]);
+  assert_eq!(solve("", "X is floor(-7r2) + ceiling(7r2) + round(5r2) + truncate(-7r2).", "X"), vec!["0"]);
+  assert_eq!(solve("", "1r3 < 0.34, 1r3 =:= 2r6, 1r2 > 1r3.", "X"), vec![""]);
+  assert!(solve_error("", "X is 1.5 rdiv 2.").starts_with("error(type_error(rational,1.5)"));
+  assert!(solve_error("", "X is rationalize(nan).").starts_with("error(evaluation_error(undefined)"));
+  assert!(solve_error("", "X is rational(inf).").starts_with("error(evaluation_error(undefined)"));
+  assert_eq!(solve("", "X is 1r3 ^ 39.", "X"), vec!["1r4052555153018976267"]);
+  assert!(solve_error("", "X is 1r3 ^ 100.").starts_with("error(evaluation_error(int_overflow)"));
+  assert!(solve_error("", "X is 1r4052555153018976267 * 1r3.").starts_with("error(evaluation_error(int_overflow)"));
+}
+
+#[test]
+fn it_prefers_rationals_when_flag_is_set() {
+  assert_eq!(solve("", "X is 1 / 3.", "X"), vec!["0.3333333333333333"]);
+  assert_eq!(solve("", "set_prolog_flag(prefer_rationals, true), X is 1 / 3.", "X"), vec!["1r3"]);
+  assert_eq!(solve("", "set_prolog_flag(prefer_rationals, true), X is 2 ** -2.", "X"), vec!["1r4"]);
+  assert_eq!(solve("", "current_prolog_flag(prefer_rationals, X).", "X"), vec!["false"]);
+  assert!(solve_error("", "set_prolog_flag(prefer_rationals, maybe).").starts_with("error(domain_error(flag_value,+(prefer_rationals,maybe))"));
+}
+
+#[test]
+fn it_reports_statistics() {
+  assert_eq!(solve("", "statistics(trailused, T).", "T"), vec!["0"]);
+  assert_eq!(solve("", "p(X) = p(_), statistics(localused, L), integer(L).", "X").len(), 1);
+  assert!(solve_error("", "statistics(heap, X).").starts_with("error(domain_error(statistics_key,heap)"));
+  assert!(solve_error("", "statistics(K, X).").starts_with("error(instantiation_error"));
+}
+
+#[test]
+fn it_unifies_terms() {
+  assert_eq!(solve("", "f(X, b) = f(a, Y), Z = X - Y.", "Z"), vec!["-(a,b)"]);
+  assert!(solve("", "f(X, b) = f(a, X).", "X").is_empty());
+  assert_eq!(solve("", "a \\= b.", "X"), vec![""]);
+  assert_eq!(solve("", "f(X) \\= g(X), var(X) = var(X).", "X"), vec!["_G1"]);
+  assert!(solve("", "X \\= a.", "X").is_empty());
+  assert!(solve("", "unify_with_occurs_check(X, f(X)).", "X").is_empty());
+  assert_eq!(solve("", "unify_with_occurs_check(f(X, Y), f(Y, g(Z))).", "X"), vec!["g(_G3)"]);
+}
+
+#[test]
+fn it_compares_terms_in_standard_order() {
+  assert_eq!(solve("", "X == X, f(a) == f(a), X \\== Y, 1 \\== 1.0.", "X"), vec!["_G1"]);
+  assert_eq!(solve("", "_ @< 1, 1.0 @< 1, 1 @< 1r2 + 0, 2 @> 1r2, 1r2 @< 1.", "X"), vec![""]);
+  assert_eq!(solve("", "100 @< a, a @< b, b @< f(a), g(b) @< f(a, a), f(a, b) @< g(a, a).", "X"), vec![""]);
+  assert_eq!(solve("", "f(a, b) @=< f(a, b), f(b) @>= f(a).", "X"), vec![""]);
+  assert_eq!(solve("", "compare(O, 1, 1.0).", "O"), vec![">"]);
+  assert_eq!(solve("", "compare(O, f(X), f(X)).", "O"), vec!["="]);
+  assert!(solve("", "compare(<, b, a).", "O").is_empty());
+  assert!(solve_error("", "compare(less, a, b).").starts_with("error(domain_error(order,less)"));
+}
+
+#[test]
+fn it_checks_types() {
+  assert_eq!(solve("", "var(X), nonvar(a), atom(a), atom([]), number(1r2), integer(3).", "X"), vec!["_G1"]);
+  assert_eq!(solve("", "float(1.0), atomic(1), compound(f(x)), callable(a), callable(f(x)).", "X"), vec![""]);
+  assert_eq!(solve("", "is_list([a, b]), ground(f(a)), rational(1), rational(1r3).", "X"), vec![""]);
+  assert!(solve("", "X = Y, nonvar(X).", "X").is_empty());
+  assert!(solve("", "is_list([a|_]).", "X").is_empty());
+  assert!(solve("", "ground(f(_)).", "X").is_empty());
+  assert!(solve("", "callable(3).", "X").is_empty());
+  assert!(solve("", "atom(a + 1).", "X").is_empty());
+}
+
+#[test]
+fn it_inspects_and_builds_terms() {
+  assert_eq!(solve("", "functor(foo(a, b), N, A), X = N/A.", "X"), vec!["/(foo,2)"]);
+  assert_eq!(solve("", "functor(X, foo, 3), X = foo(A, B, C), var(A), A \\== B, Y = ok.", "Y"), vec!["ok"]);
+  assert_eq!(solve("", "functor(X, 7, 0).", "X"), vec!["7"]);
+  assert_eq!(solve("", "arg(2, f(a, b, c), X).", "X"), vec!["b"]);
+  assert_eq!(solve("", "arg(N, f(a, b), X), Y = N-X.", "Y"), vec!["-(1,a)", "-(2,b)"]);
+  assert!(solve("", "arg(4, f(a), X).", "X").is_empty());
+  assert_eq!(solve("", "f(a, [b]) =.. X.", "X"), vec!["[f,a,[b]]"]);
+  assert_eq!(solve("", "X =.. [g, 1, Y], Y = a.", "X"), vec!["g(1,a)"]);
+  assert_eq!(solve("", "X =.. [1.5].", "X"), vec!["1.5"]);
+  assert_eq!(solve("", "copy_term(f(X, Y, X), C), C = f(A, B, A1), A == A1, A \\== X, B \\== Y, Z = ok.", "Z"), vec!["ok"]);
+  assert_eq!(solve("", "term_variables(f(X, g(Y, X), Z), L), L == [X, Y, Z], R = ok.", "R"), vec!["ok"]);
+  assert!(solve_error("", "functor(X, foo, N).").starts_with("error(instantiation_error"));
+  assert!(solve_error("", "functor(X, foo(a), 1).").starts_with("error(type_error(atomic,foo(a))"));
+  assert!(solve_error("", "arg(x, f(a), X).").starts_with("error(type_error(integer,x)"));
+  assert!(solve_error("", "X =.. [foo|T].").starts_with("error(instantiation_error"));
+  assert!(solve_error("", "X =.. [f(a), b].").starts_with("error(type_error(atomic,f(a))"));
+}
+
+#[test]
+fn it_converts_atoms() {
+  assert_eq!(solve("", "atom_codes(abc, L).", "L"), vec!["[97,98,99]"]);
+  assert_eq!(solve("", "atom_codes(A, [0'h, 0'i]).", "A"), vec!["hi"]);
+  assert_eq!(solve("", "atom_chars(A, [h, i]).", "A"), vec!["hi"]);
+  assert_eq!(solve("", "atom_chars(12, L).", "L"), vec!["[1,2]"]);
+  assert_eq!(solve("", "char_code(C, 0'z).", "C"), vec!["z"]);
+  assert_eq!(solve("", "atom_length('hello world', N).", "N"), vec!["11"]);
+  assert_eq!(solve("", "upcase_atom('mixed Case', U).", "U"), vec!["MIXED CASE"]);
+  assert_eq!(solve("", "atom_codes(' 42', L), number_codes(N, L).", "N"), vec!["42"]);
+  assert_eq!(solve("", "number_codes(N, \"42\").", "N"), vec!["42"]);
+  assert_eq!(solve("", "atom_codes(A, \"abc\"), atom_chars(B, \"de\").", "A"), vec!["abc"]);
+  assert_eq!(solve("", "string_chars(S, \"xy\"), string(S).", "S"), vec!["xy"]);
+  assert_eq!(solve("", "atom_number('-2.5', N).", "N"), vec!["-2.5"]);
+  assert_eq!(solve("", "atom_number(A, 7).", "A"), vec!["7"]);
+  assert!(solve("", "atom_number(abc, N).", "N").is_empty());
+  assert_eq!(
+    solve_error("", "atom_length(X, N)."),
+    "error(instantiation_error,context(/(atom_length,2),_G3))"
+  );
+  assert_eq!(
+    solve_error("", "number_codes(N, [0'4, 0'x])."),
+    "error(syntax_error(illegal_number),context(/(number_codes,2),_G2))"
+  );
+}
+
+#[test]
+fn it_splits_and_joins_atoms() {
+  assert_eq!(solve("", "atom_concat(get_, name, A).", "A"), vec!["get_name"]);
+  assert_eq!(solve("", "atom_concat(X, Y, ab), atom_concat(X, '+', P), atom_concat(P, Y, R).", "R"), vec!["+ab", "a+b", "ab+"]);
+  assert_eq!(solve("", "atom_concat(get_, X, get_name).", "X"), vec!["name"]);
+  assert_eq!(solve("", "sub_atom(hello, 1, 3, A, S).", "S"), vec!["ell"]);
+  assert_eq!(solve("", "sub_atom(abcab, B, _, _, ab).", "B"), vec!["0", "3"]);
+  assert_eq!(solve("", "sub_atom(abc, B, 2, A, S).", "S"), vec!["ab", "bc"]);
+  assert_eq!(solve("", "sub_atom(abc, _, _, 0, S).", "S"), vec!["abc", "bc", "c", ""]);
+  assert_eq!(solve("", "findall(B-L, sub_atom(ab, B, L, _, _), X).", "X"), vec!["[-(0,0),-(0,1),-(0,2),-(1,0),-(1,1),-(2,0)]"]);
+  assert_eq!(solve("", "sub_atom(abc, 3, L, A, S).", "S"), vec![""]);
+  assert_eq!(solve("", "sub_atom(abcab, B, L, 0, ab).", "B"), vec!["3"]);
+  assert_eq!(solve("", "sub_atom(aaa, B, _, _, aa).", "B"), vec!["0", "1"]);
+  assert_eq!(solve("", "sub_atom('héllo', B, 2, _, S).", "S"), vec!["hé", "él", "ll", "lo"]);
+  assert!(solve("", "sub_atom(abc, B, 2, 2, S).", "S").is_empty());
+  assert_eq!(solve("", "sub_string(\"abcab\", B, _, _, \"b\").", "B"), vec!["1", "4"]);
+  // a call that fixes the sub-text or the position goes straight to it
+  let program = "long(A) :- format(atom(A), '~`at~200000|', []).";
+  assert_eq!(solve(program, "long(A), atom_concat(A, abc, T), sub_atom(T, B, _, _, abc).", "B"), vec!["200000"]);
+  let mut query = runner(program, "long(A), sub_atom(A, 0, 1, _, S).");
+  assert_eq!(query.next_solution().unwrap().unwrap()["S"].to_string(), "a");
+  assert!(query.choicepoints.is_empty());
+}
+
+#[test]
+fn it_converts_terms_to_atoms() {
+  assert_eq!(solve("", "term_to_atom(f(X, 'A b', \"s\", [1, 2]), A).", "A"), vec!["f(_G1,'A b',\"s\",[1,2])"]);
+  assert_eq!(solve("", "term_to_atom((a :- b, c ; \\+ d), A).", "A"), vec!["a:-b,c;\\+d"]);
+  assert_eq!(solve("", "term_to_atom(X is 1 - (-1) * (2 + 3) mod 4, A).", "A"), vec!["_G1 is 1- -1*(2+3)mod 4"]);
+  assert_eq!(solve("", "term_to_atom(- (1) - (- a), A).", "A"), vec!["- 1- -a"]);
+  assert_eq!(solve("", "term_to_atom(f((a, b), '[]', [], 'it''s'), A).", "A"), vec!["f((a,b),[],[],'it\\'s')"]);
+  assert_eq!(solve("", "term_to_atom(T, 'foo(X, Y, X)'), T = foo(1, 2, Z).", "Z"), vec!["1"]);
+  assert_eq!(solve("", "term_to_atom(T, '1 + 2 * 3'), V is T.", "V"), vec!["7"]);
+  assert_eq!(
+    solve_error("", "term_to_atom(T, 'foo(')."),
+    "error(syntax_error(No token),context(/(term_to_atom,2),_G2))"
+  );
+}
+
+#[test]
+fn it_handles_strings() {
+  assert_eq!(solve("", "X = \"abc\", string(X).", "X"), vec!["abc"]);
+  assert!(solve("", "string(abc).", "X").is_empty());
+  assert!(solve("", "\"abc\" = abc.", "X").is_empty());
+  assert_eq!(solve("", "compare(O, abc, \"abc\").", "O"), vec!["<"]);
+  assert_eq!(solve("", "compare(O, \"abc\", f(x)).", "O"), vec!["<"]);
+  assert_eq!(solve("", "string_concat(\"ab\", cd, S), string(S).", "S"), vec!["abcd"]);
+  assert_eq!(solve("", "string_concat(X, Y, \"ab\"), string(X).", "Y"), vec!["ab", "b", ""]);
+  assert_eq!(solve("", "split_string(\"a,b,,c\", \",\", \"\", L).", "L"), vec!["[a,b,,c]"]);
+  assert_eq!(solve("", "split_string(\"/home//jan///nice/path\", \"/\", \"\", L).", "L"), vec!["[,home,,jan,,,nice,path]"]);
+  assert_eq!(solve("", "split_string(\"  a word \", \"\", \" \", L).", "L"), vec!["[a word]"]);
+  assert_eq!(solve("", "split_string(\"SWI-Prolog, 7.0\", \",\", \" \", L).", "L"), vec!["[SWI-Prolog,7.0]"]);
+  assert_eq!(solve("", "sub_string(\"hello\", 1, 3, _, S), string(S).", "S"), vec!["ell"]);
+  assert_eq!(solve("", "string_code(1, \"abc\", C).", "C"), vec!["97"]);
+  assert!(solve("", "string_code(4, \"abc\", C).", "C").is_empty());
+  assert_eq!(solve("", "string_chars(S, [h, i]), string_length(S, N).", "N"), vec!["2"]);
+  assert_eq!(solve("", "atom_string(A, \"xyz\"), atom(A).", "A"), vec!["xyz"]);
+  assert_eq!(solve("", "X is \"a\" + 1.", "X"), vec!["98"]);
+}
+
+#[test]
+fn it_collects_all_solutions() {
+  let program = "
+    age(peter, 7). age(ann, 11). age(pat, 8). age(tom, 5). age(mike, 11).
+    class(a, peter). class(b, ann). class(a, pat). class(b, tom). class(b, mike).
+  ";
+  assert_eq!(solve(program, "findall(N-A, age(N, A), L).", "L"), vec!["[-(peter,7),-(ann,11),-(pat,8),-(tom,5),-(mike,11)]"]);
+  assert_eq!(solve(program, "findall(X, age(X, 99), L).", "L"), vec!["[]"]);
+  assert_eq!(solve(program, "findall(X, (age(X, _), !), L).", "L"), vec!["[peter]"]);
+  assert_eq!(solve(program, "findall(A, age(_, A), L, [end]).", "L"), vec!["[7,11,8,5,11,end]"]);
+  assert_eq!(solve(program, "findall(X, age(X, 7), L) ; L = none.", "L"), vec!["[peter]", "none"]);
+  assert_eq!(solve(program, "bagof(N, age(N, 11), L).", "L"), vec!["[ann,mike]"]);
+  assert!(solve(program, "bagof(N, age(N, 99), L).", "L").is_empty());
+  assert_eq!(solve(program, "bagof(N, class(C, N), L).", "L"), vec!["[peter,pat]", "[ann,tom,mike]"]);
+  assert_eq!(solve(program, "bagof(N, C^class(C, N), L).", "L"), vec!["[peter,ann,pat,tom,mike]"]);
+  assert_eq!(solve(program, "setof(A, N^age(N, A), L).", "L"), vec!["[5,7,8,11]"]);
+  assert_eq!(solve(program, "setof(A-N, age(N, A), [_-Youngest|_]).", "Youngest"), vec!["tom"]);
+  assert_eq!(solve(program, "setof(C-Ns, setof(N, class(C, N), Ns), L).", "L"), vec!["[-(a,[pat,peter]),-(b,[ann,mike,tom])]"]);
+  assert_eq!(
+    solve_error(program, "findall(X, G, L)."),
+    "error(instantiation_error,context(/(findall,3),_G4))"
+  );
+}
+
+#[test]
+fn it_checks_all_solutions() {
+  let program = "
+    age(peter, 7). age(ann, 11). age(tom, 5).
+  ";
+  assert_eq!(solve(program, "forall(age(_, A), A > 4).", "X"), vec![""]);
+  assert!(solve(program, "forall(age(_, A), A > 5).", "X").is_empty());
+  assert_eq!(solve(program, "forall(age(N, _), atom(N)), X = ok.", "X"), vec!["ok"]);
+}
+
+#[test]
+fn it_aggregates_solutions() {
+  let program = "
+    age(peter, 7). age(ann, 11). age(pat, 8). age(tom, 5). age(mike, 11).
+  ";
+  assert_eq!(solve(program, "aggregate_all(count, age(_, _), C).", "C"), vec!["5"]);
+  assert_eq!(solve(program, "aggregate_all(count, age(_, 99), C).", "C"), vec!["0"]);
+  assert_eq!(solve(program, "aggregate_all(sum(A), age(_, A), S).", "S"), vec!["42"]);
+  assert_eq!(solve(program, "aggregate_all(sum(A * 0.5), age(_, A), S).", "S"), vec!["21.0"]);
+  assert_eq!(solve(program, "aggregate_all(max(A), age(_, A), M).", "M"), vec!["11"]);
+  assert_eq!(solve(program, "aggregate_all(min(A, N), age(N, A), M).", "M"), vec!["min(5,tom)"]);
+  assert_eq!(solve(program, "aggregate_all(max(A, N), age(N, A), M).", "M"), vec!["max(11,ann)"]);
+  assert!(solve(program, "aggregate_all(max(A), age(_, A), 12).", "M").is_empty());
+  assert!(solve(program, "aggregate_all(max(A), age(_, 99), M).", "M").is_empty());
+  assert_eq!(solve(program, "aggregate_all(bag(A), age(_, A), B).", "B"), vec!["[7,11,8,5,11]"]);
+  assert_eq!(solve(program, "aggregate_all(set(A), age(_, A), B).", "B"), vec!["[5,7,8,11]"]);
+  assert_eq!(solve(program, "aggregate_all(max(A-N), age(N, A), M).", "M"), vec!["-(11,mike)"]);
+  assert_eq!(solve(program, "aggregate_all(min(N, A), age(N, A), M).", "M"), vec!["min(ann,11)"]);
+  assert_eq!(
+    solve_error(program, "aggregate_all(sum(N), age(N, _), S)."),
+    "error(type_error(evaluable,/(peter,0)),context(/(aggregate_all,3),_G5))"
+  );
+  assert_eq!(
+    solve_error(program, "aggregate_all(avg(A), age(_, A), B)."),
+    "error(domain_error(aggregate_spec,avg(_G1)),context(/(aggregate_all,3),_G4))"
+  );
+}
+
+#[test]
+fn it_asserts_and_retracts_clauses() {
+  let program = "
+    :- dynamic counter/1, (seen/1, log/1).
+    counter(0).
+    bump :- retract(counter(N)), M is N + 1, assertz(counter(M)).
+  ";
+  assert_eq!(solve(program, "bump, bump, counter(X).", "X"), vec!["2"]);
+  assert_eq!(solve(program, "assertz(seen(a)), asserta(seen(b)), assert(seen(c)), findall(X, seen(X), L).", "L"), vec!["[b,a,c]"]);
+  assert_eq!(solve(program, "assertz((double(X, Y) :- Y is X * 2)), double(4, Y).", "Y"), vec!["8"]);
+  assert_eq!(solve(program, "retract(seen(_)) ; X = none.", "X"), vec!["none"]);
+  assert_eq!(solve(program, "assertz(seen(a)), assertz(seen(b)), retract(seen(X)).", "X"), vec!["a", "b"]);
+  assert_eq!(solve(program, "assertz(seen(a)), assertz(seen(b)), retract(seen(a)), findall(X, seen(X), L).", "L"), vec!["[b]"]);
+  assert_eq!(solve(program, "assertz((p(1) :- true)), retract((p(X) :- true)).", "X"), vec!["1"]);
+  assert_eq!(solve(program, "assertz(seen(a)), assertz(seen(b)), assertz(log(x)), retractall(seen(_)), findall(X, (seen(X) ; log(X)), L).", "L"), vec!["[x]"]);
+  assert_eq!(solve(program, "retractall(fresh(_)), findall(X, fresh(X), L).", "L"), vec!["[]"]);
+  assert_eq!(solve(program, "assertz(seen(a)), abolish(seen/1), assertz(log(x)), findall(X, log(X), L).", "L"), vec!["[x]"]);
+  assert_eq!(solve(program, "assertz((q(X) :- X > 1, !)), clause(q(A), (C, !)), C == (A > 1), R = ok.", "R"), vec!["ok"]);
+}
+
+#[test]
+fn it_keeps_logical_update_view() {
+  let program = "
+    :- dynamic item/1.
+    item(1). item(2).
+  ";
+  assert_eq!(solve(program, "item(X), Y is X + 10, assertz(item(Y)), fail ; findall(X, item(X), L).", "L"), vec!["[1,2,11,12]"]);
+  assert_eq!(solve(program, "item(X), retract(item(2)).", "X"), vec!["1"]);
+  assert_eq!(solve(program, "findall(X, (item(X), retractall(item(_))), L).", "L"), vec!["[1,2]"]);
+  assert_eq!(solve(program, "retract(item(X)), retractall(item(_)).", "X"), vec!["1"]);
+}
+
+#[test]
+fn it_protects_static_procedures() {
+  let program = "
+    fixed(1).
+  ";
+  assert_eq!(
+    solve_error(program, "assertz(fixed(2))."),
+    "error(permission_error(modify,static_procedure,/(fixed,1)),context(/(assertz,1),_G1))"
+  );
+  assert_eq!(
+    solve_error(program, "retract(fixed(X))."),
+    "error(permission_error(modify,static_procedure,/(fixed,1)),context(/(retract,1),_G2))"
+  );
+  assert_eq!(
+    solve_error(program, "asserta(atom(x))."),
+    "error(permission_error(modify,static_procedure,/(atom,1)),context(/(asserta,1),_G1))"
+  );
+  assert_eq!(
+    solve_error(program, "abolish(foo/bar)."),
+    "error(type_error(integer,bar),context(/(abolish,1),_G1))"
+  );
+  assert_eq!(
+    solve_error(program, "assertz((foo :- 1))."),
+    "error(type_error(callable,:-(foo,1)),context(/(assertz,1),_G1))"
+  );
+  assert_eq!(
+    solve_error(program, "assertz(X)."),
+    "error(instantiation_error,context(/(assertz,1),_G3))"
+  );
+  assert_eq!(solve(program, "clause(fixed(X), true).", "X"), vec!["1"]);
+
+  let path = std::env::temp_dir().join(format!("prolog_builtin_clause_test_{}.pl", std::process::id()));
+  std::fs::write(&path, "succ(a, b).\nplus(1, 1, 3).\nkept(1).\n").unwrap();
+  let mut runner = runner("", &format!("consult('{}'), succ(X, 2), plus(1, 1, Y), kept(Z).", path.display()));
+  let warnings = Buffer::default();
+  runner.set_user_error(Box::new(warnings.clone()));
+  let answer = runner.next_solution().unwrap().unwrap();
+  assert_eq!((answer["X"].to_string(), answer["Y"].to_string(), answer["Z"].to_string()), ("1".into(), "2".into(), "1".into()));
+  let warnings = String::from_utf8(warnings.0.borrow().clone()).unwrap();
+  assert!(warnings.contains(":1: succ(a,b) raised error(permission_error(modify,static_procedure,/(succ,2))"));
+  assert!(warnings.contains(":2: plus(1,1,3) raised error(permission_error(modify,static_procedure,/(plus,3))"));
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn it_calls_closures() {
+  let program = "
+    p(1). p(2). p(3).
+    add(X, Y, Z) :- Z is X + Y.
+    run(G) :- G.
+    twice(G, X) :- call(G, X), call(G, X).
+  ";
+  assert_eq!(solve(program, "call(add(1), 2, X).", "X"), vec!["3"]);
+  assert_eq!(solve(program, "G = add, call(G, 1, 2, X).", "X"), vec!["3"]);
+  assert_eq!(solve(program, "apply(add(5), [1, X]).", "X"), vec!["6"]);
+  assert_eq!(solve(program, "run(p(X)).", "X"), vec!["1", "2", "3"]);
+  assert_eq!(solve(program, "G = p(X), G.", "X"), vec!["1", "2", "3"]);
+  assert_eq!(solve(program, "G = !, (G, p(X) ; X = none).", "X"), vec!["1", "2", "3", "none"]);
+  assert_eq!(solve(program, "twice(p, X).", "X"), vec!["1", "2", "3"]);
+  assert!(solve_error(program, "call(1, a).").starts_with("error(type_error(callable,1),context(/(call,2)"));
+  assert!(solve_error(program, "run(_).").starts_with("error(instantiation_error"));
+}
+
+#[test]
+fn it_runs_goals_once() {
+  let program = "p(1). p(2).";
+  assert_eq!(solve(program, "once(p(X)).", "X"), vec!["1"]);
+  assert_eq!(solve(program, "ignore(p(3)), X = done.", "X"), vec!["done"]);
+  assert_eq!(solve(program, "ignore(p(X)).", "X"), vec!["1"]);
+  assert_eq!(solve(program, "not(p(3)), X = yes.", "X"), vec!["yes"]);
+  assert!(solve(program, "not(p(1)).", "X").is_empty());
+}
+
+#[test]
+fn it_loads_the_lists_library() {
+  assert_eq!(solve("", "append(X, Y, [1, 2]).", "X"), vec!["[]", "[1]", "[1,2]"]);
+  assert_eq!(solve("", "member(X, [a, b]).", "X"), vec!["a", "b"]);
+  assert_eq!(solve("", "memberchk(X, [a, b]).", "X"), vec!["a"]);
+  assert_eq!(solve("", "length([a, b, c], N).", "N"), vec!["3"]);
+  assert_eq!(solve("", "length(L, 2), L = [a, b].", "L"), vec!["[a,b]"]);
+  assert_eq!(solve("", "length(L, N), N >= 2, !.", "N"), vec!["2"]);
+  assert_eq!(solve("", "length([a|T], N), N >= 3, !, length(T, K).", "K"), vec!["2"]);
+  assert!(solve("", "length(L, L).", "L").is_empty());
+  assert!(solve("", "length([a|T], T).", "T").is_empty());
+  assert!(solve_error("", "length(L, -1).").starts_with("error(domain_error(not_less_than_zero,-1)"));
+  assert_eq!(solve("", "nth0(1, [a, b, c], X).", "X"), vec!["b"]);
+  assert_eq!(solve("", "nth1(I, [a, b, c], c).", "I"), vec!["3"]);
+  assert_eq!(solve("", "reverse([1, 2, 3], X).", "X"), vec!["[3,2,1]"]);
+  assert_eq!(solve("", "last([1, 2, 3], X).", "X"), vec!["3"]);
+  assert_eq!(solve("", "permutation([1, 2, 3], X).", "X").len(), 6);
+  assert_eq!(solve("", "select(b, [a, b, c], X).", "X"), vec!["[a,c]"]);
+  assert_eq!(solve("", "sum_list([1, 2, 3.5], X).", "X"), vec!["6.5"]);
+  assert_eq!(solve("", "max_list([1, 5, 3], X).", "X"), vec!["5"]);
+  assert_eq!(solve("", "list_to_set([a, b, a, c, b], X).", "X"), vec!["[a,b,c]"]);
+  assert_eq!(solve("", "subtract([1, 2, 3, 4], [2, 4], X).", "X"), vec!["[1,3]"]);
+  assert_eq!(solve("", "delete([a, f(1), b, f(2)], f(_), X).", "X"), vec!["[a,b]"]);
+  assert_eq!(solve("", "exclude(integer, [a, 1, b, 2], X).", "X"), vec!["[a,b]"]);
+  assert_eq!(solve("", "partition(atom, [a, 1, b, 2], I, E), X = I-E.", "X"), vec!["-([a,b],[1,2])"]);
+}
+
+#[test]
+fn it_sorts_lists() {
+  assert_eq!(solve("", "msort([b, 1, a, 1], X).", "X"), vec!["[1,1,a,b]"]);
+  assert_eq!(solve("", "sort([b, 1, a, 1], X).", "X"), vec!["[1,a,b]"]);
+  assert_eq!(solve("", "sort(1, @>=, [f(1, a), f(2, b), f(1, c)], X).", "X"), vec!["[f(2,b),f(1,a),f(1,c)]"]);
+  assert_eq!(solve("", "sort(1, @<, [f(1, a), f(2, b), f(1, c)], X).", "X"), vec!["[f(1,a),f(2,b)]"]);
+  let program = "by_length(O, A, B) :- atom_length(A, X), atom_length(B, Y), compare(O, X, Y).";
+  assert_eq!(solve(program, "predsort(by_length, [ccc, a, bb, dd], X).", "X"), vec!["[a,bb,ccc]"]);
+  assert!(solve_error("", "sort(0, foo, [], X).").starts_with("error(domain_error(order,foo)"));
+}
+
+#[test]
+fn it_lets_programs_define_library_predicates() {
+  let program = "
+    append(mine, _, _).
+    :- use_module(library(lists)).
+  ";
+  assert_eq!(solve(program, "append(X, [], []).", "X"), vec!["mine"]);
+  assert_eq!(solve(program, "reverse([1, 2], X).", "X"), vec!["[2,1]"]);
+  assert!(solve_error("", "use_module(library(nope)).").starts_with("error(existence_error(source_sink,library(nope))"));
+}
+
+#[test]
+fn it_applies_closures_to_lists() {
+  let program = "
+    double(X, Y) :- Y is X * 2.
+    add(X, Y, Z) :- Z is X + Y.
+  ";
+  assert_eq!(solve(program, "maplist(double, [1, 2, 3], X).", "X"), vec!["[2,4,6]"]);
+  assert_eq!(solve(program, "maplist(add, [1, 2], [10, 20], X).", "X"), vec!["[11,22]"]);
+  assert_eq!(solve(program, "maplist(integer, [1, 2]), X = yes.", "X"), vec!["yes"]);
+  assert!(solve(program, "maplist(integer, [1, a]).", "X").is_empty());
+  assert_eq!(solve(program, "length(L, 2), maplist(=(z), L).", "L"), vec!["[z,z]"]);
+  assert_eq!(solve(program, "foldl(add, [1, 2, 3], 0, X).", "X"), vec!["6"]);
+  assert_eq!(solve(program, "foldl([X, Y, A0, A]>>(A is A0 + X * Y), [1, 2], [3, 4], 0, S).", "S"), vec!["11"]);
+  assert_eq!(solve(program, "include(integer, [a, 1, b, 2], X).", "X"), vec!["[1,2]"]);
+}
+
+#[test]
+fn it_calls_lambdas() {
+  assert_eq!(solve("", "maplist([X, Y]>>(Y is X + 1), [1, 2], L).", "L"), vec!["[2,3]"]);
+  assert_eq!(solve("", "N = 10, maplist([X, Y]>>(Y is X + N), [1, 2], L).", "L"), vec!["[11,12]"]);
+  assert_eq!(solve("", "maplist([X]>>(Z = X), [1, 2]), var(Z), R = unbound.", "R"), vec!["unbound"]);
+  assert_eq!(solve("", "maplist(\\X^(X > 0), [1, 2]), R = ok.", "R"), vec!["ok"]);
+  assert_eq!(solve("", "maplist(\\X^Y^(Y is X * X), [2, 3], L).", "L"), vec!["[4,9]"]);
+  assert_eq!(solve("", "call(Z/[X]>>(Z = X), 5).", "Z"), vec!["5"]);
+  assert_eq!(solve("", "call([X, Y]>>atom_length(X, Y), abc, N).", "N"), vec!["3"]);
+  assert_eq!(solve("", "call([X]>>atom_length(X), abc, N).", "N"), vec!["3"]);
+}
+
+#[test]
+fn it_enumerates_integers() {
+  assert_eq!(solve("", "between(1, 3, X).", "X"), vec!["1", "2", "3"]);
+  assert_eq!(solve("", "between(3, 1, X).", "X"), Vec::<String>::new());
+  assert_eq!(solve("", "between(1, inf, X), X * X > 50, !.", "X"), vec!["8"]);
+  assert_eq!(solve("", "between(1, 3, 2), X = yes.", "X"), vec!["yes"]);
+  assert_eq!(solve("", "succ(X, 4).", "X"), vec!["3"]);
+  assert_eq!(solve("", "succ(3, X).", "X"), vec!["4"]);
+  assert!(solve("", "succ(X, 0).", "X").is_empty());
+  assert!(solve_error("", "succ(X, Y).").starts_with("error(instantiation_error"));
+  assert!(solve_error("", "succ(-1, X).").starts_with("error(type_error(not_less_than_zero,-1)"));
+  assert_eq!(solve("", "plus(2, X, 5).", "X"), vec!["3"]);
+  assert_eq!(solve("", "plus(2, 3, X).", "X"), vec!["5"]);
+  assert_eq!(solve("", "numlist(1, 4, X).", "X"), vec!["[1,2,3,4]"]);
+  assert!(solve("", "numlist(4, 1, X).", "X").is_empty());
+  assert_eq!(solve("", "numlist(3, 3, X).", "X"), vec!["[3]"]);
+}
+
+#[test]
+fn it_classifies_characters() {
+  assert_eq!(solve("", "char_type(a, alpha), X = yes.", "X"), vec!["yes"]);
+  assert_eq!(solve("", "char_type('7', digit(W)).", "W"), vec!["7"]);
+  assert_eq!(solve("", "char_type('A', upper(L)).", "L"), vec!["a"]);
+  assert_eq!(solve("", "char_type(b, to_upper(U)).", "U"), vec!["B"]);
+  assert_eq!(solve("", "code_type(0'a, to_upper(U)).", "U"), vec!["65"]);
+  assert_eq!(solve("", "code_type(0' , space), X = yes.", "X"), vec!["yes"]);
+  assert!(solve("", "char_type(a, digit(_)).", "X").is_empty());
+  assert_eq!(solve("", "findall(C, char_type(C, digit(5)), X).", "X"), vec!["[5]"]);
+}
+
+#[test]
+fn it_draws_reproducible_random_numbers() {
+  let query = "set_random(seed(42)), random_between(1, 100, A), random_between(1, 100, B), random(F), X = A-B-F.";
+  let first = solve("", query, "X");
+  assert_eq!(first, solve("", query, "X"));
+  assert_eq!(solve("", "set_random(seed(7)), findall(N, (between(1, 50, _), random_between(3, 5, N)), L), msort(L, S), list_to_set(S, X).", "X"), vec!["[3,4,5]"]);
+  assert!(solve("", "random_between(5, 1, X).", "X").is_empty());
+  assert_eq!(solve("", "random(X), X >= 0, X < 1, R = ok.", "R"), vec!["ok"]);
+}
+
+#[test]
+fn it_keeps_association_lists() {
+  assert_eq!(solve("", "list_to_assoc([b-2, a-1, c-3], A), get_assoc(b, A, X).", "X"), vec!["2"]);
+  assert_eq!(solve("", "empty_assoc(E), put_assoc(k, E, v, A), put_assoc(k, A, w, B), get_assoc(k, B, X).", "X"), vec!["w"]);
+  assert!(solve("", "list_to_assoc([a-1], A), get_assoc(z, A, X).", "X").is_empty());
+  let duplicate = solve_error("", "list_to_assoc([a-1, b-3, a-2], A).");
+  assert!(duplicate.starts_with("error(domain_error(unique_key_pairs,[-(a,1),-(b,3),-(a,2)]),context(/(list_to_assoc,2)"));
+  let query = "numlist(1, 100, L), reverse(L, R), findall(K-K, member(K, R), Ps), list_to_assoc(Ps, A), assoc_to_keys(A, Ks), Ks == L, get_assoc(64, A, X).";
+  assert_eq!(solve("", query, "X"), vec!["64"]);
+  let depth = "
+    depth(t, 0).
+    depth(t(_, _, _, L, R), D) :- depth(L, DL), depth(R, DR), D is max(DL, DR) + 1.
+  ";
+  assert_eq!(solve(depth, "numlist(1, 127, L), findall(K-x, member(K, L), Ps), list_to_assoc(Ps, A), depth(A, X).", "X"), vec!["7"]);
+  assert_eq!(solve("", "list_to_assoc([b-2, a-1], A), assoc_to_list(A, X).", "X"), vec!["[-(a,1),-(b,2)]"]);
+  assert_eq!(solve("", "list_to_assoc([b-2, a-1], A), assoc_to_values(A, X).", "X"), vec!["[1,2]"]);
+}
+
+#[test]
+fn it_handles_ordered_sets_and_pairs() {
+  assert_eq!(solve("", "list_to_ord_set([c, a, b, a], X).", "X"), vec!["[a,b,c]"]);
+  assert_eq!(solve("", "ord_union([a, c, e], [b, c, d], X).", "X"), vec!["[a,b,c,d,e]"]);
+  assert_eq!(solve("", "ord_subtract([a, b, c, d], [b, d, e], X).", "X"), vec!["[a,c]"]);
+  assert_eq!(solve("", "ord_memberchk(c, [a, b, c]), X = yes.", "X"), vec!["yes"]);
+  assert!(solve("", "ord_memberchk(bb, [a, b, c]).", "X").is_empty());
+  assert_eq!(solve("", "pairs_keys_values(P, [a, b], [1, 2]).", "P"), vec!["[-(a,1),-(b,2)]"]);
+  assert_eq!(solve("", "pairs_keys_values([a-1, b-2], K, V), X = K/V.", "X"), vec!["/([a,b],[1,2])"]);
+  assert_eq!(solve("", "transpose_pairs([a-2, b-1, c-2], X).", "X"), vec!["[-(1,b),-(2,a),-(2,c)]"]);
+  assert_eq!(solve("", "keysort([b-1, a-2, b-0], X).", "X"), vec!["[-(a,2),-(b,1),-(b,0)]"]);
+  assert!(solve_error("", "keysort([a], X).").starts_with("error(type_error(pair,a)"));
+}
+
+#[test]
+fn it_handles_dicts() {
+  assert_eq!(solve("", "D = point{y: 2, x: 1}, get_dict(x, D, X).", "X"), vec!["1"]);
+  assert_eq!(solve("", "X = point{y: 2, x: 1}.", "X"), vec!["point{x:1,y:2}"]);
+  assert_eq!(solve("", "get_dict(K, _{b: 2, a: 1}, V), X = K-V.", "X"), vec!["-(a,1)", "-(b,2)"]);
+  assert!(solve("", "get_dict(z, _{a: 1}, V).", "V").is_empty());
+  assert_eq!(solve("", "put_dict(c, t{a: 1}, 3, D), put_dict(a, D, 0, X).", "X"), vec!["t{a:0,c:3}"]);
+  assert_eq!(solve("", "put_dict(t{b: 5}, t{a: 1, b: 2}, X).", "X"), vec!["t{a:1,b:5}"]);
+  assert_eq!(solve("", "del_dict(a, t{a: 1, b: 2}, V, D), X = V/D.", "X"), vec!["/(1,t{b:2})"]);
+  assert_eq!(solve("", "T{a: X, b: 2} = p{b: B, a: 1}, R = T/X/B.", "R"), vec!["/(/(p,1),2)"]);
+  assert!(solve("", "_{a: 1} = _{a: 1, b: 2}.", "X").is_empty());
+  assert_eq!(solve("", "dict_pairs(D, t, [b-2, a-1]), dict_pairs(D, T, P), X = T/P.", "X"), vec!["/(t,[-(a,1),-(b,2)])"]);
+  assert_eq!(solve("", "is_dict(t{}, T).", "T"), vec!["t"]);
+  assert!(solve_error("", "get_dict(a, foo, V).").starts_with("error(type_error(dict,foo)"));
+  assert!(solve_error("", "put_dict(f(x), t{}, 1, D).").starts_with("error(type_error(dict_key,f(x))"));
+  let build = "numlist(1, 200, L), foldl([K, D0, D1]>>put_dict(K, D0, K, D1), L, t{}, D), get_dict(150, D, X).";
+  assert_eq!(solve("", build, "X"), vec!["150"]);
+  let remove = "numlist(1, 50, L), foldl([K, D0, D1]>>put_dict(K, D0, x, D1), L, t{}, D), \
+    foldl([K, D0, D1]>>del_dict(K, D0, _, D1), L, D, E), dict_pairs(E, _, X).";
+  assert_eq!(solve("", remove, "X"), vec!["[]"]);
+}
+
+#[test]
+fn it_converts_dicts_to_and_from_json() {
+  let read = "atom_json_dict('{\"name\": \"Ann\", \"tags\": [1, 2.5, true, null], \"nested\": {\"ok\": false}}', D, []), \
+    get_dict(name, D, N), get_dict(tags, D, T), get_dict(nested, D, Nested), get_dict(ok, Nested, Ok), X = N/T/Ok.";
+  assert_eq!(solve("", read, "X"), vec!["/(/(Ann,[1,2.5,true,null]),false)"]);
+  assert_eq!(solve("", "atom_json_dict(A, t{b: [1, \"x\"], a: \"q\\\"\"}, []).", "A"), vec!["{\"a\":\"q\\\"\",\"b\":[1,\"x\"]}"]);
+  assert_eq!(solve("", "atom_json_dict('{\"s\": \"\\\\u00e9\\\\n\"}', D, []), get_dict(s, D, S), string_length(S, X).", "X"), vec!["2"]);
+  assert!(solve_error("", "atom_json_dict('{\"a\": }', D, []).").starts_with("error(syntax_error(illegal_json)"));
+  assert!(solve_error("", "atom_json_dict(A, t{a: f(x)}, []).").starts_with("error(type_error(json_term,f(x))"));
+}
+
+#[test]
+fn it_writes_to_streams() {
+  let query = "write(f('A', \"s\", [1])), nl, writeq(f('A', \"s\", 1 + 2)), put_char(x), nl(user_output).";
+  assert_eq!(output("", query), "f(A,s,[1])\nf('A',\"s\",1+2)x\n");
+  assert_eq!(output("p(1). p(2).", "p(X), write(X), nl."), "1\n2\n");
+  assert_eq!(output("", "current_output(S), write(S, ok), stream_property(S, alias(A)), write(A)."), "okuser_output");
+  let mut runner = runner("", "write(log, hello), current_output(S), set_output(log), write(there), set_output(S).");
+  let log = Buffer::default();
+  runner.add_output_stream("log", Box::new(log.clone()));
+  assert!(runner.next_solution().unwrap().is_some());
+  assert_eq!(log.0.borrow().as_slice(), b"hellothere");
+  assert!(solve_error("", "put_char(user_input, a).").starts_with("error(permission_error(output,stream,user_input)"));
+  assert!(solve_error("", "put_char(ab).").starts_with("error(type_error(character,ab)"));
+  assert!(solve_error("", "write(nowhere, a).").starts_with("error(existence_error(stream,nowhere)"));
+}
+
+#[test]
+fn it_reads_from_streams() {
+  let query = "read(X), read(Y), get_char(C), peek_char(D), get_char(E), read(Z), at_end_of_stream.";
+  let mut reader = runner("", query);
+  reader.set_user_input(Box::new("foo(A, 'b.c', 0'.). % done\n/* list */ [1,2]. xy".as_bytes()));
+  let solution = reader.next_solution().unwrap().unwrap();
+  let values: Vec<String> = ["X", "Y", "C", "D", "E", "Z"].iter().map(|v| solution[*v].to_string()).collect();
+  assert!(values[0].starts_with("foo(_G") && values[0].ends_with(",b.c,46)"));
+  assert_eq!(values[1..], ["[1,2]", "x", "y", "y", "end_of_file"]);
+  let mut partial = runner("", "read(X).");
+  partial.set_user_input(Box::new("foo(".as_bytes()));
+  assert!(partial.next_solution().unwrap_err().to_string().starts_with("error(syntax_error("));
+  assert!(solve_error("", "get_char(user_output, C).").starts_with("error(permission_error(input,stream,user_output)"));
+  assert!(solve_error("", "get_byte(user_input, B).").starts_with("error(permission_error(input,text_stream,user_input)"));
+}
+
+#[test]
+fn it_opens_files() {
+  let path = std::env::temp_dir().join(format!("prolog_io_test_{}.pl", std::process::id()));
+  let file = path.to_str().unwrap().replace('\\', "/");
+  let write = format!("open('{}', write, S, [alias(out)]), write(out, f(x)), write(S, '.'), nl(S), close(S).", file);
+  assert_eq!(solve("", &write, "X"), vec![""]);
+  let read = format!(
+    "open('{}', read, S), stream_property(S, file_name(F)), stream_property(S, mode(M)), \
+     read(S, T), read(S, E), stream_property(S, end_of_stream(P)), close(S), X = M/T/E/P.",
+    file
+  );
+  assert_eq!(solve("", &read, "X"), vec!["/(/(/(read,f(x)),end_of_file),past)"]);
+  let past = format!("open('{}', read, S, [eof_action(error)]), read(S, _), read(S, _), read(S, _).", file);
+  assert!(solve_error("", &past).starts_with("error(permission_error(input,past_end_of_stream,$stream("));
+  let bytes = format!(
+    "open('{0}', write, S, [type(binary)]), put_byte(S, 200), close(S), \
+     open('{0}', read, R, [type(binary)]), get_byte(R, B), get_byte(R, E), close(R), X = B/E.",
+    file
+  );
+  assert_eq!(solve("", &bytes, "X"), vec!["/(200,-1)"]);
+  let stale = format!(
+    "with_output_to(string(_), current_output(S)), open('{}', write, F), catch(write(S, leaked), E, true), close(F), X = E.",
+    file
+  );
+  assert!(solve("", &stale, "X")[0].starts_with("error(existence_error(stream,$stream("));
+  let mut cycles = runner("", &format!("between(1, 1000, _), open('{}', read, S), close(S), fail ; true.", file));
+  assert!(cycles.next_solution().unwrap().is_some());
+  assert_eq!(cycles.streams.ids().count(), 3);
+  assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+  std::fs::remove_file(&path).unwrap();
+  assert!(solve_error("", &read).starts_with("error(existence_error(source_sink,"));
+  assert!(solve_error("", "open(f, update, S).").starts_with("error(domain_error(io_mode,update)"));
+  assert!(solve_error("", "open(f, read, s).").starts_with("error(uninstantiation_error(s)"));
+}
+
+#[test]
+fn it_formats_text() {
+  let format = |query: &str| solve("", &format!("format(atom(X), {}).", query), "X");
+  assert_eq!(format("\"~w and ~q~n\", [f('A'), 'B c']"), vec!["f(A) and 'B c'\n"]);
+  assert_eq!(format("\"~a|~d|~2d|~D|~2D\", [abc, -42, 5, 1234567, 1234567]"), vec!["abc|-42|0.05|1,234,567|12,345.67"]);
+  assert_eq!(format("\"~4f ~e ~3e ~g ~g\", [3.14159, 1.5, 123456, 0.0001, 1.0e10]"), vec!["3.1416 1.500000e+00 1.235e+05 0.0001 1e+10"]);
+  assert_eq!(format("\"~s ~c~3c ~8r ~16R ~i~w ~~\", [\"str\", 0'x, 0'y, 64, 255, skipped, shown]"), vec!["str xyyy 100 FF shown ~"]);
+  assert_eq!(format("\"~*c\", [3, 0'z]"), vec!["zzz"]);
+  assert_eq!(format("'~w', hello"), vec!["hello"]);
+}
+
+#[test]
+fn it_aligns_columns() {
+  let format = |query: &str| solve("", &format!("format(atom(X), {}).", query), "X");
+  assert_eq!(format("\"~w~10|~w\", [name, value]"), vec!["name      value"]);
+  assert_eq!(format("\"~t~w~10|~w\", [right, x]"), vec!["     rightx"]);
+  assert_eq!(format("\"~t~w~t~11|\", [mid]"), vec!["    mid    "]);
+  assert_eq!(format("\"~`-t~30|\", []"), vec!["------------------------------"]);
+  assert_eq!(format("\"~w~t~6+~t~w~6+\", [ab, 12]"), vec!["ab        12"]);
+  assert_eq!(format("\"~t~d~6|~n~t~d~6|\", [1, 100]"), vec!["     1\n   100"]);
+  assert_eq!(output("", "write(abc), format(\"~t~w~6|\", [x])."), "abc  x");
+}
+
+#[test]
+fn it_captures_output() {
+  assert_eq!(solve("", "with_output_to(string(S), (write(a), print(\"b\"), nl)).", "S"), vec!["a\"b\"\n"]);
+  assert_eq!(solve("", "with_output_to(codes(C, T), write(hi)), T = [].", "C"), vec!["[104,105]"]);
+  assert_eq!(solve("", "with_output_to(chars(C), format(\"~w\", [xy])).", "C"), vec!["[x,y]"]);
+  assert_eq!(solve("p(1). p(2).", "with_output_to(atom(A), (p(X), write(X))).", "A"), vec!["1"]);
+  assert!(solve("", "with_output_to(atom(_), fail).", "X").is_empty());
+  let fresh = "with_output_to(string(_), current_output(S)), format(atom(_), \"~w\", [x]), with_output_to(string(_), current_output(T)), S \\== T.";
+  assert_eq!(solve("", fresh, "X"), vec![""]);
+  let stale = "with_output_to(string(_), current_output(S)), write(S, leaked).";
+  assert!(solve_error("", stale).starts_with("error(existence_error(stream,$stream("));
+  let restored = "catch(with_output_to(atom(_), (write(lost), throw(oops))), oops, true), write(kept).";
+  assert_eq!(output("", restored), "kept");
+  assert_eq!(output("", "with_output_to(atom(A), write(inner)), write(A)."), "inner");
+  assert!(solve_error("", "format(\"~d\", [a]).").starts_with("error(type_error(integer,a)"));
+  assert!(solve_error("", "format(\"~w ~w\", [a]).").starts_with("error(format(not enough arguments)"));
+  assert!(solve_error("", "format(\"~w\", [a, b]).").starts_with("error(format(too many arguments)"));
+  assert!(solve_error("", "with_output_to(file, true).").starts_with("error(domain_error(output_sink,file)"));
+}
+
+#[test]
+fn it_consults_files() {
+  let dir = std::env::temp_dir().join(format!("prolog_consult_test_{}", std::process::id()));
+  std::fs::create_dir_all(dir.join("sub")).unwrap();
+  std::fs::write(dir.join("main.pl"), "% main\n:- include('sub/part').\n\np(1).\np(2).\n:- ensure_loaded('sub/other').\n").unwrap();
+  std::fs::write(dir.join("sub/part.pl"), "q(a).\n").unwrap();
+  std::fs::write(dir.join("sub/other.pl"), ":- include(more).\n:- assertz(counter(x)).\n").unwrap();
+  std::fs::write(dir.join("sub/more.pl"), "\n\ns(y).\n").unwrap();
+  std::fs::write(dir.join("bad.pl"), "p(1).\nq(.\nr('x).\ns(2).\n").unwrap();
+  let main = dir.join("main").to_str().unwrap().to_string();
+  let base = dir.to_str().unwrap().to_string();
+
+  assert_eq!(solve("", &format!("consult('{}'), findall(X, (p(X) ; q(X) ; s(X)), X).", main), "X"), vec!["[1,2,a,y]"]);
+  assert_eq!(solve("", &format!("['{0}', '{0}'], findall(X, p(X), X).", main), "X"), vec!["[1,2]"]);
+  let twice = format!("consult('{0}'), ensure_loaded('{0}'), ensure_loaded('{1}/sub/other'), findall(C, counter(C), X).", main, base);
+  assert_eq!(solve("", &twice, "X"), vec!["[x]"]);
+  let located = format!(
+    "consult('{}'), clause(p(2), true, R), clause_property(R, line_count(L)), clause_property(R, file(F)), \
+     clause(q(_), _, Q), clause_property(Q, file(QF)), clause_property(Q, source(QO)), \
+     clause(s(_), _, S), clause_property(S, line_count(M)), clause_property(S, source(SO)), \
+     X = L/M/F/QF/QO/SO.",
+    main
+  );
+  let located = solve("", &located, "X");
+  let main_pl = format!("{}/main.pl", base);
+  let expected = format!("/(/(/(/(/(5,3),{0}),{1}/sub/part.pl),{0}),{1}/sub/other.pl)", main_pl, base);
+  assert_eq!(located, vec![expected]);
+  let files = format!("consult('{}'), findall(F, source_file(s(_), F), X).", main);
+  assert_eq!(solve("", &files, "X"), vec![format!("[{}/sub/other.pl]", base)]);
+
+  let rewrite = |clauses: &str| format!("open('{0}/re.pl', write, S), write(S, '{1}'), close(S)", base, clauses);
+  let rewrite_again = |clauses: &str| format!("open('{0}/re.pl', write, T), write(T, '{1}'), close(T)", base, clauses);
+  let reconsult = format!(
+    "{}, consult('{2}/re'), {}, consult('{2}/re'), findall(X, t(X), X), catch(u, error(existence_error(_, _), _), true).",
+    rewrite("t(1). t(2). u."),
+    rewrite_again("t(3)."),
+    base
+  );
+  assert_eq!(solve("", &reconsult, "X"), vec!["[3]"]);
+
+  let mut runner = runner("", &format!("consult('{}/bad'), findall(X, (p(X) ; s(X)), L).", base));
+  let warnings = Buffer::default();
+  runner.set_user_error(Box::new(warnings.clone()));
+  assert_eq!(runner.next_solution().unwrap().unwrap()["L"].to_string(), "[1,2]");
+  let warnings = String::from_utf8(warnings.0.borrow().clone()).unwrap();
+  let lines: Vec<&str> = warnings.lines().collect();
+  assert_eq!(lines.len(), 2);
+  assert!(lines[0].starts_with("Warning: error(syntax_error(") && lines[0].contains("bad.pl:2:"));
+  assert!(lines[1].contains("bad.pl:3: Unterminated quoted"));
+  assert!(solve_error("", &format!("consult('{}/missing').", base)).starts_with("error(existence_error(source_sink,"));
+  std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn it_keeps_module_predicates_apart() {
+  let program = "
+    :- module(shapes, [area/2]).
+    area(square(S), A) :- side(S, L), A is L * L.
+    side(S, S).
+  ";
+  assert_eq!(solve(program, "area(square(3), A).", "A"), vec!["9"]);
+  assert_eq!(solve(program, "shapes:side(2, X).", "X"), vec!["2"]);
+  assert!(solve_error(program, "side(2, X).").starts_with("error(existence_error(procedure,/(side,2))"));
+  assert_eq!(solve(program, "catch(M:true, error(E, _), true).", "E"), vec!["instantiation_error"]);
+  assert_eq!(solve(program, "findall(M, current_module(M), X).", "X"), vec!["[shapes,system,user]"]);
+  let asserted = "shapes:assertz(count(1)), shapes:count(X), catch(count(_), error(existence_error(_, P), _), true).";
+  assert_eq!(solve(program, asserted, "P"), vec!["/(count,1)"]);
+  assert_eq!(solve(program, "assertz(shapes:count(2)), shapes:retract(count(X)).", "X"), vec!["2"]);
+  assert_eq!(solve(program, "assertz(shapes:count(3)), clause(shapes:count(X), true).", "X"), vec!["3"]);
+}
+
+#[test]
+fn it_imports_module_files() {
+  let dir = std::env::temp_dir().join(format!("prolog_module_test_{}", std::process::id()));
+  std::fs::create_dir_all(&dir).unwrap();
+  std::fs::write(
+    dir.join("a.pl"),
+    ":- module(a, [p/1, apply_to/2, direct/2]).\n:- meta_predicate apply_to(1, ?).\n\
+     p(X) :- q(X).\nq(from_a).\napply_to(G, X) :- call(G, X).\ndirect(G, X) :- call(G, X).\n",
+  )
+  .unwrap();
+  std::fs::write(
+    dir.join("b.pl"),
+    ":- module(b, [r/1, s/1, t/1, doubled/2]).\n:- use_module(a).\n\
+     q(from_b).\nr(X) :- p(X).\ns(X) :- apply_to(q, X).\nt(X) :- direct(q, X).\n\
+     doubled(L, R) :- maplist(double, L, R).\ndouble(X, Y) :- Y is 2 * X.\n",
+  )
+  .unwrap();
+  let base = dir.to_str().unwrap().to_string();
+
+  let query = |goal: &str| format!("use_module('{}/b'), {}.", base, goal);
+  assert_eq!(solve("", &query("r(X)"), "X"), vec!["from_a"]);
+  assert_eq!(solve("", &query("b:q(X)"), "X"), vec!["from_b"]);
+  assert_eq!(solve("", &query("s(X)"), "X"), vec!["from_b"]);
+  assert_eq!(solve("", &query("t(X)"), "X"), vec!["from_a"]);
+  assert_eq!(solve("", &query("catch(a:direct(z, _), error(existence_error(procedure, P), _), true)"), "P"), vec![":(a,/(z,1))"]);
+  assert_eq!(solve("", &query("doubled([1, 2], X)"), "X"), vec!["[2,4]"]);
+  assert!(solve_error("", &query("q(X)")).starts_with("error(existence_error(procedure,/(q,1))"));
+  assert!(solve_error("", &query("p(X)")).starts_with("error(existence_error(procedure,/(p,1))"));
+  assert_eq!(solve("", &query("findall(H, source_file(H, _), X)"), "X").len(), 1);
+
+  let selected = format!("use_module('{0}/a', [p/1]), p(X), catch(direct(true, _), error(E, _), true).", base);
+  assert_eq!(solve("", &selected, "X"), vec!["from_a"]);
+  assert_eq!(solve("", &selected, "E"), vec!["existence_error(procedure,/(direct,2))"]);
+  std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,25 @@
+use super::{Builtin, Term};
+
+pub fn lookup(name: &str, arity: usize) -> Option<Builtin> {
+    let builtin: Builtin = match (name, arity) {
+        ("var", 1) => |_, args| Ok(matches!(args[0].deref(), Term::Var(_))),
+        ("nonvar", 1) => |_, args| Ok(!matches!(args[0].deref(), Term::Var(_))),
+        ("atom", 1) => |_, args| Ok(matches!(args[0].deref(), Term::Atom(_))),
+        ("number", 1) => |_, args| {
+            Ok(matches!(args[0].deref(), Term::Int(_) | Term::Rational(_) | Term::Float(_)))
+        },
+        ("integer", 1) => |_, args| Ok(matches!(args[0].deref(), Term::Int(_))),
+        ("rational", 1) => |_, args| Ok(matches!(args[0].deref(), Term::Int(_) | Term::Rational(_))),
+        ("float", 1) => |_, args| Ok(matches!(args[0].deref(), Term::Float(_))),
+        ("string", 1) => |_, args| Ok(matches!(args[0].deref(), Term::Str(_))),
+        ("atomic", 1) => |_, args| {
+            Ok(!matches!(args[0].deref(), Term::Var(_) | Term::Compound(_) | Term::Dict(_)))
+        },
+        ("compound", 1) => |_, args| Ok(matches!(args[0].deref(), Term::Compound(_))),
+        ("callable", 1) => |_, args| Ok(args[0].is_callable()),
+        ("is_list", 1) => |_, args| Ok(args[0].is_list()),
+        ("ground", 1) => |_, args| Ok(args[0].variables().is_empty()),
+        _ => return None,
+    };
+    Some(builtin)
+}
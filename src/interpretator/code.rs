@@ -0,0 +1,711 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+};
+
+use super::{
+    builtins::{self, Builtin},
+    machine::Resolved,
+    term::Term,
+};
+
+// Clauses compiled to the instructions of the abstract machine in
+// machine.rs, after the Warren Abstract Machine. A call puts its arguments
+// in the first X registers; the head code of a clause unifies them with the
+// head, then its body code puts the arguments of each goal in turn and
+// calls it, the last one with execute so the clause is done with before.
+// A variable the head shares only with the first goal, or that only one goal
+// uses, lives in X registers. The others are permanent: they live in the Y
+// registers of an environment frame the body allocates, as does the choice
+// point height an if-then-else or a negation cuts back to. Control
+// constructs compile to jumps around a choice point of their own.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Reg {
+    X(usize),
+    Y(usize),
+}
+
+// the choice point height a cut goes back to
+#[derive(Clone, Copy)]
+pub enum Barrier {
+    // that of the call, while the clause has no environment
+    Call,
+    // that of the call, as the environment keeps it
+    Clause,
+    // the height a Y register recorded, or the one just above it for a cut
+    // inside the condition of an if-then-else or a negation
+    Level(usize),
+    Above(usize),
+}
+
+pub enum Instruction {
+    // head: unify argument register `arg` with a new variable, one seen
+    // before, a ground term or a structure whose arguments the unify
+    // instructions after it give
+    GetVariable { reg: Reg, arg: usize },
+    GetValue { reg: Reg, arg: usize },
+    GetConstant { constant: Term, arg: usize },
+    GetStructure { name: Rc<str>, arity: usize, arg: usize },
+    // a term the instructions cannot take apart, a dict with variables
+    GetTemplate { template: Term, arg: usize, bindings: Rc<[(u64, Reg)]> },
+    UnifyVariable(Reg),
+    UnifyValue(Reg),
+    UnifyConstant(Term),
+    UnifyVoid,
+    // body: an environment of so many Y registers, which a GetVariable right
+    // after fills from the head
+    Allocate(usize),
+    Deallocate,
+    // put the arguments of a goal, building structures inner terms first
+    PutVariable(Reg),
+    PutValue { reg: Reg, arg: usize },
+    PutConstant { constant: Term, arg: usize },
+    PutStructure { name: Rc<str>, arity: usize, arg: usize },
+    PutTemplate { template: Term, arg: usize, bindings: Rc<[(u64, Reg)]> },
+    SetVariable(Reg),
+    SetValue(Reg),
+    SetConstant(Term),
+    SetVoid,
+    Call(Box<Procedure>),
+    Execute(Box<Procedure>),
+    Proceed,
+    // control constructs: record the choice point height, cut, push a choice
+    // point that resumes at the label, disable the one at a recorded height
+    GetLevel(usize),
+    Cut(Barrier),
+    TryMeElse(usize),
+    Jump(usize),
+    SoftCut(usize),
+    Fail,
+    // the routines of the engine, over the Y registers of a frame it pushed:
+    // call a goal, leave a catch/3, add a findall/3 solution, end a query
+    MetaCall,
+    ExitCatch,
+    Collect,
+    Stop,
+}
+
+// What a goal of a body calls, and the module it is called in.
+pub struct Procedure {
+    pub name: Rc<str>,
+    pub arity: usize,
+    pub module: Rc<str>,
+    pub target: Target,
+}
+
+pub enum Target {
+    // a predicate, with where the module of the call found it last
+    Predicate(RefCell<Option<Resolved>>),
+    Builtin(Builtin),
+    // call/1, catch/3, throw/1, and :/2 when the module is only known once
+    // it runs
+    Call,
+    Catch,
+    Throw,
+    Qualified,
+}
+
+pub struct Code {
+    pub instructions: Vec<Instruction>,
+    // where the body starts
+    pub neck: usize,
+    // the X registers the code uses
+    pub registers: usize,
+}
+
+impl Code {
+    pub fn routine(instructions: Vec<Instruction>) -> Code {
+        Code { instructions, neck: 0, registers: 0 }
+    }
+}
+
+// A body as the control constructs in it arrange its goals.
+enum Body {
+    Goal(Term, Rc<str>),
+    Cut,
+    True,
+    Fail,
+    And(Vec<Body>),
+    Or(Box<Body>, Box<Body>),
+    IfThenElse { cond: Box<Body>, then: Box<Body>, otherwise: Box<Body>, soft: bool },
+    Not(Box<Body>),
+}
+
+impl Body {
+    // A variable goal runs as call/1, so a cut it is bound to stays local,
+    // as does anything else that is not callable, which call/1 then reports.
+    // A goal qualified with a module calls in that module.
+    fn parse(term: &Term, module: &Rc<str>) -> Body {
+        let call = |goal: &Term| Body::Goal(Term::compound("call", vec![goal.clone()]), module.clone());
+        let c = match term {
+            Term::Atom(name) => {
+                return match &**name {
+                    "true" => Body::True,
+                    "fail" | "false" => Body::Fail,
+                    "!" => Body::Cut,
+                    _ => Body::Goal(term.clone(), module.clone()),
+                }
+            }
+            Term::Compound(c) => c,
+            _ => return call(term),
+        };
+        let arg = |i: usize| Box::new(Body::parse(&c.args[i], module));
+        match (&*c.name, c.args.len()) {
+            (",", 2) => {
+                let mut goals = Vec::new();
+                for goal in [arg(0), arg(1)] {
+                    match *goal {
+                        Body::And(inner) => goals.extend(inner),
+                        goal => goals.push(goal),
+                    }
+                }
+                Body::And(goals)
+            }
+            (";", 2) => match c.args[0].deref() {
+                Term::Compound(lhs) if matches!((&*lhs.name, lhs.args.len()), ("->" | "*->", 2)) => Body::IfThenElse {
+                    cond: Box::new(Body::parse(&lhs.args[0], module)),
+                    then: Box::new(Body::parse(&lhs.args[1], module)),
+                    otherwise: arg(1),
+                    soft: &*lhs.name == "*->",
+                },
+                _ => Body::Or(arg(0), arg(1)),
+            },
+            ("->", 2) | ("*->", 2) => Body::IfThenElse {
+                cond: arg(0),
+                then: arg(1),
+                otherwise: Box::new(Body::Fail),
+                soft: &*c.name == "*->",
+            },
+            ("\\+", 1) => Body::Not(arg(0)),
+            (":", 2) => match c.args[0].deref() {
+                Term::Atom(module) => Body::parse(&c.args[1], &module),
+                _ => Body::Goal(term.clone(), module.clone()),
+            },
+            _ => Body::Goal(term.clone(), module.clone()),
+        }
+    }
+
+    // the goals run one after the other at the top of the body
+    fn items(&self) -> &[Body] {
+        match self {
+            Body::And(items) => items,
+            body => std::slice::from_ref(body),
+        }
+    }
+
+    fn is_control(&self) -> bool {
+        match self {
+            Body::Or(..) | Body::IfThenElse { .. } | Body::Not(_) => true,
+            Body::And(items) => items.iter().any(Body::is_control),
+            _ => false,
+        }
+    }
+}
+
+fn args(goal: &Term) -> &[Term] {
+    match goal {
+        Term::Compound(c) => &c.args,
+        _ => &[],
+    }
+}
+
+// Compiles a clause of `module`. Its variables get fresh values for every
+// call.
+pub fn compile(head: &Term, body: &Term, module: &Rc<str>) -> Code {
+    let body = Body::parse(body, module);
+    let head = head.deref();
+    let mut compiler = Compiler::default();
+    compiler.analyse(args(&head), &body);
+    let first = compiler.first_goal(&body).map(|goal| args(goal).to_vec()).unwrap_or_default();
+    for (i, arg) in args(&head).iter().enumerate() {
+        compiler.head_arg(arg, i, Some(&first));
+    }
+    compiler.finish(&body)
+}
+
+// Compiles a goal that is called as it is, with control constructs in it;
+// its variables are the ones it has.
+pub fn compile_goal(goal: &Term, module: &Rc<str>) -> Code {
+    let body = Body::parse(goal, module);
+    let mut compiler = Compiler { frozen: true, ..Compiler::default() };
+    compiler.analyse(&[], &body);
+    compiler.finish(&body)
+}
+
+fn procedure(goal: &Term, module: &Rc<str>) -> Procedure {
+    let (name, arity) = goal.name_arity().expect("a callable goal");
+    let target = match (&*name, arity) {
+        ("call", 1) => Target::Call,
+        ("catch", 3) => Target::Catch,
+        ("throw", 1) => Target::Throw,
+        (":", 2) => Target::Qualified,
+        _ => match builtins::lookup(&name, arity) {
+            Some(builtin) => Target::Builtin(builtin),
+            None => Target::Predicate(RefCell::new(None)),
+        },
+    };
+    Procedure { name, arity, module: module.clone(), target }
+}
+
+#[derive(Default)]
+struct Compiler {
+    code: Vec<Instruction>,
+    // the variables are those of the running goal, not ones to create
+    frozen: bool,
+    occurrences: HashMap<u64, usize>,
+    // the Y register of every permanent variable, in the order they occur,
+    // and those first seen inside a control construct, which get their
+    // variable as the environment is allocated
+    permanent: HashMap<u64, usize>,
+    order: Vec<u64>,
+    early: Vec<u64>,
+    registers: HashMap<u64, Reg>,
+    slots: usize,
+    next: usize,
+    environment: bool,
+}
+
+impl Compiler {
+    fn count(&mut self, term: &Term) {
+        match term.deref() {
+            Term::Var(v) => *self.occurrences.entry(v.id).or_default() += 1,
+            Term::Compound(c) => c.args.iter().for_each(|arg| self.count(arg)),
+            dict @ Term::Dict(_) => {
+                for variable in dict.variables() {
+                    self.count(&variable);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // The goal the head shares its X registers with: the first of the
+    // body, unless a control construct comes before it.
+    fn first_goal<'a>(&self, body: &'a Body) -> Option<&'a Term> {
+        match body.items().iter().find(|item| !matches!(item, Body::Cut | Body::True)) {
+            Some(Body::Goal(goal, _)) => Some(goal),
+            _ => None,
+        }
+    }
+
+    // Sorts the variables into temporary and permanent ones and decides
+    // whether the body needs an environment: for permanent variables,
+    // control constructs, or a goal that is not the last.
+    fn analyse(&mut self, head: &[Term], body: &Body) {
+        let mut arity = head.len();
+        // the goal every variable occurs in, or none once it is in two
+        let mut groups: HashMap<u64, Option<usize>> = HashMap::new();
+        let mut group = 0;
+        for arg in head {
+            self.count(arg);
+            self.note(arg, 0, false, &mut groups);
+        }
+        let first = self.first_goal(body).map(|goal| goal as *const Term);
+        self.scan(body, first, false, &mut group, &mut groups, &mut arity);
+        for id in std::mem::take(&mut self.order) {
+            if groups[&id].is_none() && !self.permanent.contains_key(&id) {
+                self.permanent.insert(id, self.permanent.len());
+                self.order.push(id);
+            }
+        }
+        let permanent = &self.permanent;
+        self.early.retain(|id| permanent.contains_key(id));
+        self.slots = self.permanent.len();
+        self.next = arity;
+        let items: Vec<&Body> = body.items().iter().filter(|item| !matches!(item, Body::True)).collect();
+        let inner_goal = items.iter().rev().skip(1).any(|item| matches!(item, Body::Goal(..)));
+        self.environment = !self.permanent.is_empty() || body.is_control() || inner_goal;
+    }
+
+    fn scan(
+        &mut self,
+        body: &Body,
+        first: Option<*const Term>,
+        inside: bool,
+        group: &mut usize,
+        groups: &mut HashMap<u64, Option<usize>>,
+        arity: &mut usize,
+    ) {
+        match body {
+            Body::Goal(goal, _) => {
+                let at = match first == Some(goal as *const Term) {
+                    true => 0,
+                    false => {
+                        *group += 1;
+                        *group
+                    }
+                };
+                *arity = (*arity).max(args(goal).len());
+                for arg in args(goal) {
+                    self.count(arg);
+                    self.note(arg, at, inside, groups);
+                }
+            }
+            Body::And(items) => {
+                for item in items {
+                    self.scan(item, first, inside, group, groups, arity);
+                }
+            }
+            Body::Or(lhs, rhs) => {
+                self.scan(lhs, first, true, group, groups, arity);
+                self.scan(rhs, first, true, group, groups, arity);
+            }
+            Body::IfThenElse { cond, then, otherwise, .. } => {
+                for part in [cond, then, otherwise] {
+                    self.scan(part, first, true, group, groups, arity);
+                }
+            }
+            Body::Not(goal) => self.scan(goal, first, true, group, groups, arity),
+            Body::Cut | Body::True | Body::Fail => {}
+        }
+    }
+
+    fn note(&mut self, term: &Term, group: usize, inside: bool, groups: &mut HashMap<u64, Option<usize>>) {
+        if self.frozen {
+            return;
+        }
+        for variable in term.variables() {
+            if let Term::Var(v) = variable {
+                match groups.get(&v.id) {
+                    None => {
+                        groups.insert(v.id, Some(group));
+                        self.order.push(v.id);
+                        if inside {
+                            self.early.push(v.id);
+                        }
+                    }
+                    Some(Some(seen)) if *seen != group => {
+                        groups.insert(v.id, None);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        self.code.push(instruction);
+        self.code.len() - 1
+    }
+
+    fn temporary(&mut self) -> usize {
+        self.next += 1;
+        self.next - 1
+    }
+
+    fn slot(&mut self) -> usize {
+        self.slots += 1;
+        self.slots - 1
+    }
+
+    fn is_void(&self, id: u64) -> bool {
+        self.occurrences.get(&id) == Some(&1)
+    }
+
+    // the register for a variable that occurs for the first time, where a
+    // new variable is put by the instruction that sees it
+    fn register(&mut self, id: u64) -> Reg {
+        let reg = match self.permanent.get(&id) {
+            Some(slot) => Reg::Y(*slot),
+            None => Reg::X(self.temporary()),
+        };
+        self.registers.insert(id, reg);
+        reg
+    }
+
+    // the registers of the variables of a template, with new variables put
+    // in the ones that occur there first
+    fn bindings(&mut self, template: &Term, head: bool) -> Rc<[(u64, Reg)]> {
+        let mut bindings = Vec::new();
+        for variable in template.variables() {
+            if let Term::Var(v) = variable {
+                let reg = match self.registers.get(&v.id) {
+                    Some(reg) => *reg,
+                    None => {
+                        // the head only has X registers; a permanent
+                        // variable moves to its Y register after it
+                        let reg = match head {
+                            true => Reg::X(self.temporary()),
+                            false => self.register(v.id),
+                        };
+                        self.registers.insert(v.id, reg);
+                        self.emit(Instruction::PutVariable(reg));
+                        reg
+                    }
+                };
+                bindings.push((v.id, reg));
+            }
+        }
+        bindings.into()
+    }
+
+    // Compiles head argument `i`. A temporary variable stays in the
+    // argument register unless the first goal puts something else there.
+    fn head_arg(&mut self, arg: &Term, i: usize, first: Option<&[Term]>) {
+        match arg.deref() {
+            Term::Var(v) => match self.registers.get(&v.id) {
+                Some(reg) => {
+                    self.emit(Instruction::GetValue { reg: *reg, arg: i });
+                }
+                None => {
+                    let in_place = |args: &[Term]| match args.get(i).map(Term::deref) {
+                        Some(Term::Var(w)) => Rc::ptr_eq(&v, &w),
+                        _ => false,
+                    };
+                    let stays = self.permanent.contains_key(&v.id)
+                        || first.is_none_or(|args| in_place(args) || !args.iter().any(|arg| occurs(v.id, arg)));
+                    let reg = match stays {
+                        true => Reg::X(i),
+                        false => {
+                            let reg = Reg::X(self.temporary());
+                            self.emit(Instruction::GetVariable { reg, arg: i });
+                            reg
+                        }
+                    };
+                    self.registers.insert(v.id, reg);
+                }
+            },
+            ground if is_ground(&ground) => {
+                self.emit(Instruction::GetConstant { constant: ground, arg: i });
+            }
+            Term::Compound(c) => {
+                self.emit(Instruction::GetStructure { name: c.name.clone(), arity: c.args.len(), arg: i });
+                let mut nested = Vec::new();
+                for arg in c.args.iter() {
+                    let instruction = match arg.deref() {
+                        Term::Var(v) if self.is_void(v.id) => Instruction::UnifyVoid,
+                        Term::Var(v) => match self.registers.get(&v.id) {
+                            Some(reg) => Instruction::UnifyValue(*reg),
+                            None => {
+                                let reg = Reg::X(self.temporary());
+                                self.registers.insert(v.id, reg);
+                                Instruction::UnifyVariable(reg)
+                            }
+                        },
+                        ground if is_ground(&ground) => Instruction::UnifyConstant(ground),
+                        term => {
+                            let temp = self.temporary();
+                            nested.push((term, temp));
+                            Instruction::UnifyVariable(Reg::X(temp))
+                        }
+                    };
+                    self.emit(instruction);
+                }
+                for (term, temp) in nested {
+                    self.head_arg(&term, temp, None);
+                }
+            }
+            template => {
+                let bindings = self.bindings(&template, true);
+                self.emit(Instruction::GetTemplate { template, arg: i, bindings });
+            }
+        }
+    }
+
+    // Ends the head and compiles the body: the environment first, with the
+    // permanent variables the head has, then the goals.
+    fn finish(mut self, body: &Body) -> Code {
+        let neck = self.code.len();
+        let allocate = self.environment.then(|| self.emit(Instruction::Allocate(0)));
+        for id in self.order.clone() {
+            let slot = self.permanent[&id];
+            match self.registers.get(&id) {
+                Some(Reg::X(x)) => {
+                    self.emit(Instruction::GetVariable { reg: Reg::Y(slot), arg: *x });
+                }
+                _ if self.early.contains(&id) => {
+                    self.emit(Instruction::PutVariable(Reg::Y(slot)));
+                }
+                _ => continue,
+            }
+            self.registers.insert(id, Reg::Y(slot));
+        }
+        let barrier = match self.environment {
+            true => Barrier::Clause,
+            false => Barrier::Call,
+        };
+        self.body(body, true, barrier);
+        if let Some(at) = allocate {
+            self.code[at] = Instruction::Allocate(self.slots);
+        }
+        Code { instructions: self.code, neck, registers: self.next }
+    }
+
+    // the end of a body that does not end with a call
+    fn proceed(&mut self) {
+        if self.environment {
+            self.emit(Instruction::Deallocate);
+        }
+        self.emit(Instruction::Proceed);
+    }
+
+    // Compiles a part of the body. In the last position, `tail`, it ends
+    // the clause itself, so every way through it leaves with execute,
+    // proceed or a failure.
+    fn body(&mut self, body: &Body, tail: bool, barrier: Barrier) {
+        match body {
+            Body::Goal(goal, module) => {
+                for (j, arg) in args(goal).iter().enumerate() {
+                    self.put(arg, j);
+                }
+                let procedure = Box::new(procedure(goal, module));
+                if tail {
+                    if self.environment {
+                        self.emit(Instruction::Deallocate);
+                    }
+                    self.emit(Instruction::Execute(procedure));
+                } else {
+                    self.emit(Instruction::Call(procedure));
+                }
+            }
+            Body::Cut => {
+                self.emit(Instruction::Cut(barrier));
+                if tail {
+                    self.proceed();
+                }
+            }
+            Body::True => {
+                if tail {
+                    self.proceed();
+                }
+            }
+            Body::Fail => {
+                self.emit(Instruction::Fail);
+            }
+            Body::And(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    self.body(item, tail && i + 1 == items.len(), barrier);
+                }
+            }
+            Body::Or(lhs, rhs) => {
+                let try_else = self.emit(Instruction::TryMeElse(0));
+                self.body(lhs, tail, barrier);
+                self.alternative(try_else, rhs, tail, barrier);
+            }
+            Body::IfThenElse { cond, then, otherwise, soft } => {
+                let level = self.slot();
+                self.emit(Instruction::GetLevel(level));
+                let try_else = self.emit(Instruction::TryMeElse(0));
+                self.body(cond, false, Barrier::Above(level));
+                match soft {
+                    true => self.emit(Instruction::SoftCut(level)),
+                    false => self.emit(Instruction::Cut(Barrier::Level(level))),
+                };
+                self.body(then, tail, barrier);
+                self.alternative(try_else, otherwise, tail, barrier);
+            }
+            Body::Not(goal) => {
+                let level = self.slot();
+                self.emit(Instruction::GetLevel(level));
+                let try_else = self.emit(Instruction::TryMeElse(0));
+                self.body(goal, false, Barrier::Above(level));
+                self.emit(Instruction::Cut(Barrier::Level(level)));
+                self.emit(Instruction::Fail);
+                self.code[try_else] = Instruction::TryMeElse(self.code.len());
+                if tail {
+                    self.proceed();
+                }
+            }
+        }
+    }
+
+    // the branch a TryMeElse goes to, with a jump over it from the end of
+    // the branch before unless that one left the clause already
+    fn alternative(&mut self, try_else: usize, branch: &Body, tail: bool, barrier: Barrier) {
+        let jump = (!tail).then(|| self.emit(Instruction::Jump(0)));
+        self.code[try_else] = Instruction::TryMeElse(self.code.len());
+        self.body(branch, tail, barrier);
+        if let Some(jump) = jump {
+            self.code[jump] = Instruction::Jump(self.code.len());
+        }
+    }
+
+    // puts argument `j` of a goal
+    fn put(&mut self, arg: &Term, j: usize) {
+        if self.frozen {
+            self.emit(Instruction::PutConstant { constant: arg.clone(), arg: j });
+            return;
+        }
+        match arg.deref() {
+            Term::Var(v) => match self.registers.get(&v.id) {
+                Some(Reg::X(x)) if *x == j => {}
+                Some(reg) => {
+                    self.emit(Instruction::PutValue { reg: *reg, arg: j });
+                }
+                None => match self.permanent.get(&v.id) {
+                    Some(slot) => {
+                        let reg = Reg::Y(*slot);
+                        self.registers.insert(v.id, reg);
+                        self.emit(Instruction::PutVariable(reg));
+                        self.emit(Instruction::PutValue { reg, arg: j });
+                    }
+                    None => {
+                        self.registers.insert(v.id, Reg::X(j));
+                        self.emit(Instruction::PutVariable(Reg::X(j)));
+                    }
+                },
+            },
+            ground if is_ground(&ground) => {
+                self.emit(Instruction::PutConstant { constant: ground, arg: j });
+            }
+            Term::Compound(c) => self.structure(&c.name, &c.args, j),
+            template => {
+                let bindings = self.bindings(&template, false);
+                self.emit(Instruction::PutTemplate { template, arg: j, bindings });
+            }
+        }
+    }
+
+    // Builds a structure in X register `target`. Nested terms are built
+    // first, so a variable they share with the arguments around them is set
+    // up by whichever comes first.
+    fn structure(&mut self, name: &Rc<str>, args: &[Term], target: usize) {
+        let nested: Vec<Option<usize>> = args
+            .iter()
+            .map(|arg| match arg.deref() {
+                Term::Var(_) => None,
+                ground if is_ground(&ground) => None,
+                Term::Compound(c) => {
+                    let temp = self.temporary();
+                    self.structure(&c.name, &c.args, temp);
+                    Some(temp)
+                }
+                template => {
+                    let temp = self.temporary();
+                    let bindings = self.bindings(&template, false);
+                    self.emit(Instruction::PutTemplate { template, arg: temp, bindings });
+                    Some(temp)
+                }
+            })
+            .collect();
+        let args: Vec<Instruction> = args
+            .iter()
+            .zip(nested)
+            .map(|(arg, nested)| match arg.deref() {
+                Term::Var(v) if self.is_void(v.id) => Instruction::SetVoid,
+                Term::Var(v) => match self.registers.get(&v.id) {
+                    Some(reg) => Instruction::SetValue(*reg),
+                    None => Instruction::SetVariable(self.register(v.id)),
+                },
+                ground if is_ground(&ground) => Instruction::SetConstant(ground),
+                _ => Instruction::SetValue(Reg::X(nested.expect("nested terms are built"))),
+            })
+            .collect();
+        self.emit(Instruction::PutStructure { name: name.clone(), arity: args.len(), arg: target });
+        self.code.extend(args);
+    }
+}
+
+fn occurs(id: u64, term: &Term) -> bool {
+    term.variables().iter().any(|v| matches!(v, Term::Var(v) if v.id == id))
+}
+
+fn is_ground(term: &Term) -> bool {
+    match term {
+        Term::Var(_) => false,
+        Term::Compound(c) => c.args.iter().all(|arg| is_ground(&arg.deref())),
+        Term::Dict(_) => term.variables().is_empty(),
+        _ => true,
+    }
+}
@@ -0,0 +1,279 @@
+use std::{cell::RefCell, cmp::Reverse, collections::HashMap, rc::Rc};
+
+use super::{
+    code::{compile, Code},
+    index::{bound_paths, subterm, ArgIndex, Path},
+    term::Term,
+};
+
+pub struct ClauseRecord {
+    pub id: usize,
+    pub head: Term,
+    pub body: Term,
+    pub source: Option<Source>,
+    // what a call runs; head and body are kept for clause/2 and listing/1
+    pub code: Rc<Code>,
+}
+
+// Where a loaded clause came from: the file and line its text is on, and the
+// file that was consulted to load it, which differs for included files.
+#[derive(Clone)]
+pub struct Source {
+    pub owner: Rc<str>,
+    pub file: Rc<str>,
+    pub line: usize,
+}
+
+// The clauses of a predicate as they were when a call started. Changes to the
+// database replace the list instead of editing it, so a running call keeps
+// iterating over its own snapshot: the ISO logical update view.
+pub type Clauses = Rc<Vec<Rc<ClauseRecord>>>;
+
+pub struct Predicate {
+    clauses: Clauses,
+    // Indexes by head position. The first argument always has one; others
+    // are built once calls show they would narrow the clauses down.
+    indexes: Vec<(Path, ArgIndex)>,
+    // how often each argument was bound in a call, and the positions found
+    // not worth an index, with the number of clauses at the time
+    bound: Vec<usize>,
+    unselective: HashMap<Path, usize>,
+    dynamic: bool,
+}
+
+// predicates with fewer clauses, or calls left with fewer candidates, are not
+// worth building another index for
+const JIT_MIN_CLAUSES: usize = 8;
+
+pub type Key = (Rc<str>, usize);
+
+// The predicates of every module, each module with a table of its own.
+#[derive(Default)]
+pub struct Database {
+    modules: HashMap<Rc<str>, HashMap<Key, Rc<RefCell<Predicate>>>>,
+    next_id: usize,
+    // changes whenever a predicate or module comes or goes, which is when
+    // calls have to look up what they call again
+    generation: u64,
+}
+
+impl ClauseRecord {
+    // splits `Head :- Body`; anything else is a fact
+    pub fn parts(term: &Term) -> (Term, Term) {
+        if let Term::Compound(c) = term.deref() {
+            if &*c.name == ":-" && c.args.len() == 2 {
+                return (c.args[0].clone(), c.args[1].clone());
+            }
+        }
+        (term.clone(), Term::atom("true"))
+    }
+}
+
+impl Predicate {
+    fn new(arity: usize) -> Predicate {
+        let mut indexes = Vec::new();
+        if arity > 0 {
+            indexes.push((vec![0], ArgIndex::new()));
+        }
+        Predicate {
+            clauses: Rc::new(Vec::new()),
+            indexes,
+            bound: vec![0; arity],
+            unselective: HashMap::new(),
+            dynamic: false,
+        }
+    }
+
+    fn insert(&mut self, record: Rc<ClauseRecord>, front: bool) {
+        for (path, index) in self.indexes.iter_mut() {
+            index.insert(path, &record, front);
+        }
+        let clauses = Rc::make_mut(&mut self.clauses);
+        if front {
+            clauses.insert(0, record);
+        } else {
+            clauses.push(record);
+        }
+    }
+
+    fn retain(&mut self, keep: impl Fn(&Rc<ClauseRecord>) -> bool) {
+        for (_, index) in self.indexes.iter_mut() {
+            index.retain(&keep);
+        }
+        Rc::make_mut(&mut self.clauses).retain(keep);
+    }
+
+    // The clauses that could match a call with `args`, by whichever index
+    // narrows them down most: the switch on the arguments. When none narrows
+    // them enough, an index is built on the bound position of the call whose
+    // argument calls bind most often.
+    pub fn candidates(&mut self, args: &[Term]) -> Clauses {
+        if self.clauses.len() < 2 {
+            return self.clauses.clone();
+        }
+        // too few clauses for another index to pay off
+        if self.clauses.len() < JIT_MIN_CLAUSES {
+            return self.best(args);
+        }
+        for (count, arg) in self.bound.iter_mut().zip(args) {
+            if !matches!(arg.deref(), Term::Var(_)) {
+                *count += 1;
+            }
+        }
+        let best = self.best(args);
+        if best.len() < JIT_MIN_CLAUSES {
+            return best;
+        }
+        match self.unassessed(args) {
+            Some(path) => {
+                let mut index = ArgIndex::new();
+                for record in self.clauses.iter() {
+                    index.insert(&path, record, false);
+                }
+                if index.is_selective() {
+                    self.indexes.push((path, index));
+                    self.best(args)
+                } else {
+                    self.unselective.insert(path, self.clauses.len());
+                    best
+                }
+            }
+            None => best,
+        }
+    }
+
+    fn best(&self, args: &[Term]) -> Clauses {
+        self.indexes
+            .iter()
+            .filter_map(|(path, index)| match subterm(args, path)? {
+                Term::Var(_) => None,
+                term => Some(index.lookup(&term)),
+            })
+            .min_by_key(|clauses| clauses.len())
+            .unwrap_or(&self.clauses)
+            .clone()
+    }
+
+    // a bound position of a call without an index, that was not found
+    // unselective since the predicate last doubled in size
+    fn unassessed(&self, args: &[Term]) -> Option<Path> {
+        let mut paths: Vec<Path> = bound_paths(args)
+            .into_iter()
+            .filter(|path| self.indexes.iter().all(|(indexed, _)| indexed != path))
+            .filter(|path| self.unselective.get(path).is_none_or(|n| self.clauses.len() >= 2 * n))
+            .collect();
+        paths.sort_by_key(|path| (Reverse(self.bound.get(path[0]).copied().unwrap_or(0)), path.len()));
+        paths.into_iter().next()
+    }
+}
+
+impl Database {
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // for changes to modules, which change what calls find too
+    pub fn touch(&mut self) {
+        self.generation += 1;
+    }
+
+    pub fn predicate(&self, module: &str, key: &Key) -> Option<Rc<RefCell<Predicate>>> {
+        self.modules.get(module)?.get(key).cloned()
+    }
+
+    fn entry(&mut self, module: &str, key: Key) -> std::cell::RefMut<'_, Predicate> {
+        let generation = &mut self.generation;
+        self.modules
+            .entry(Rc::from(module))
+            .or_default()
+            .entry(key.clone())
+            .or_insert_with(|| {
+                *generation += 1;
+                Rc::new(RefCell::new(Predicate::new(key.1)))
+            })
+            .borrow_mut()
+    }
+
+    pub fn clauses(&self, module: &str, key: &Key) -> Option<Clauses> {
+        Some(self.modules.get(module)?.get(key)?.borrow().clauses.clone())
+    }
+
+    pub fn contains(&self, module: &str, key: &Key) -> bool {
+        self.modules.get(module).is_some_and(|m| m.contains_key(key))
+    }
+
+    pub fn is_dynamic(&self, module: &str, key: &Key) -> bool {
+        self.modules.get(module).and_then(|m| m.get(key)).is_some_and(|p| p.borrow().dynamic)
+    }
+
+    // adds a clause to the front or the back of its predicate, creating a
+    // static predicate if there is none yet
+    pub fn add(&mut self, module: &str, key: Key, head: Term, body: Term, front: bool, source: Option<Source>) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let code = Rc::new(compile(&head, &body, &Rc::from(module)));
+        let record = Rc::new(ClauseRecord { id, head, body, source, code });
+        self.entry(module, key).insert(record, front);
+    }
+
+    pub fn declare_dynamic(&mut self, module: &str, key: Key) {
+        self.entry(module, key).dynamic = true;
+    }
+
+    // false when the clause is not there (any more)
+    pub fn remove(&mut self, module: &str, key: &Key, id: usize) -> bool {
+        if let Some(predicate) = self.modules.get(module).and_then(|m| m.get(key)) {
+            let mut predicate = predicate.borrow_mut();
+            if predicate.clauses.iter().any(|c| c.id == id) {
+                predicate.retain(|c| c.id != id);
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn abolish(&mut self, module: &str, key: &Key) {
+        if let Some(predicates) = self.modules.get_mut(module) {
+            if predicates.remove(key).is_some() {
+                self.generation += 1;
+            }
+        }
+    }
+
+    // Removes the clauses loaded from `owner`, ahead of consulting it again.
+    // Static predicates it alone defined go away with them.
+    pub fn unload(&mut self, owner: &str) {
+        let from = |c: &Rc<ClauseRecord>| c.source.as_ref().is_some_and(|s| &*s.owner == owner);
+        let generation = &mut self.generation;
+        for predicates in self.modules.values_mut() {
+            predicates.retain(|_, predicate| {
+                let mut predicate = predicate.borrow_mut();
+                if predicate.clauses.iter().any(from) {
+                    predicate.retain(|c| !from(c));
+                    if !predicate.dynamic && predicate.clauses.is_empty() {
+                        *generation += 1;
+                        return false;
+                    }
+                }
+                true
+            });
+        }
+    }
+
+    pub fn clause(&self, module: &str, key: &Key, id: usize) -> Option<Rc<ClauseRecord>> {
+        self.clauses(module, key)?.iter().find(|c| c.id == id).cloned()
+    }
+
+    // the predicates with clauses loaded from `owner`, with their modules
+    pub fn defined_in(&self, owner: &str) -> Vec<(Rc<str>, Key)> {
+        let mut keys: Vec<(Rc<str>, Key)> = self
+            .modules
+            .iter()
+            .flat_map(|(module, predicates)| predicates.iter().map(move |(key, p)| (module, key, p)))
+            .filter(|(_, _, p)| p.borrow().clauses.iter().any(|c| c.source.as_ref().is_some_and(|s| &*s.owner == owner)))
+            .map(|(module, key, _)| (module.clone(), key.clone()))
+            .collect();
+        keys.sort();
+        keys
+    }
+}
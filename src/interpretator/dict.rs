@@ -0,0 +1,211 @@
+use std::{cmp::Ordering, rc::Rc};
+
+use super::term::Term;
+
+// A dict key: an atom or an integer. Integers sort before atoms, as in the
+// standard order of terms.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Key {
+    Int(i64),
+    Atom(Rc<str>),
+}
+
+impl Key {
+    pub fn from_term(term: &Term) -> Option<Key> {
+        match term.deref() {
+            Term::Int(n) => Some(Key::Int(n)),
+            Term::Atom(name) => Some(Key::Atom(name)),
+            _ => None,
+        }
+    }
+
+    pub fn to_term(&self) -> Term {
+        match self {
+            Key::Int(n) => Term::Int(*n),
+            Key::Atom(name) => Term::Atom(name.clone()),
+        }
+    }
+}
+
+type Link = Option<Rc<Node>>;
+
+struct Node {
+    key: Key,
+    value: Term,
+    height: u32,
+    left: Link,
+    right: Link,
+}
+
+// An immutable dict: a tag and a persistent AVL tree of key-value pairs.
+// Changes copy only the path to the key they touch, so lookups, puts and
+// deletes are all O(log n) and older versions stay valid for backtracking.
+#[derive(Clone)]
+pub struct Dict {
+    pub tag: Term,
+    root: Link,
+    len: usize,
+}
+
+impl Dict {
+    pub fn new(tag: Term) -> Dict {
+        Dict { tag, root: None, len: 0 }
+    }
+
+    // `None` when a key appears twice
+    pub fn from_pairs(tag: Term, pairs: Vec<(Key, Term)>) -> Option<Dict> {
+        let mut dict = Dict::new(tag);
+        for (key, value) in pairs {
+            if dict.get(&key).is_some() {
+                return None;
+            }
+            dict = dict.put(key, value);
+        }
+        Some(dict)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&Term> {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            link = match key.cmp(&node.key) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return Some(&node.value),
+            };
+        }
+        None
+    }
+
+    pub fn put(&self, key: Key, value: Term) -> Dict {
+        let added = self.get(&key).is_none();
+        Dict {
+            tag: self.tag.clone(),
+            root: Some(insert(&self.root, key, value)),
+            len: self.len + added as usize,
+        }
+    }
+
+    // `None` when the key is not there
+    pub fn remove(&self, key: &Key) -> Option<Dict> {
+        self.get(key)?;
+        Some(Dict {
+            tag: self.tag.clone(),
+            root: remove(&self.root, key),
+            len: self.len - 1,
+        })
+    }
+
+    pub fn with_tag(&self, tag: Term) -> Dict {
+        Dict { tag, ..self.clone() }
+    }
+
+    // the pairs in key order
+    pub fn pairs(&self) -> Vec<(Key, Term)> {
+        let mut pairs = Vec::with_capacity(self.len);
+        let mut stack: Vec<&Rc<Node>> = Vec::new();
+        let mut link = &self.root;
+        loop {
+            while let Some(node) = link {
+                stack.push(node);
+                link = &node.left;
+            }
+            match stack.pop() {
+                Some(node) => {
+                    pairs.push((node.key.clone(), node.value.clone()));
+                    link = &node.right;
+                }
+                None => return pairs,
+            }
+        }
+    }
+
+    // the same keys with every value replaced by `f` of it
+    pub fn map(&self, tag: Term, f: &mut dyn FnMut(&Term) -> Term) -> Dict {
+        Dict { tag, root: map(&self.root, f), len: self.len }
+    }
+}
+
+fn height(link: &Link) -> u32 {
+    link.as_ref().map_or(0, |node| node.height)
+}
+
+fn node(key: Key, value: Term, left: Link, right: Link) -> Rc<Node> {
+    let height = height(&left).max(height(&right)) + 1;
+    Rc::new(Node { key, value, height, left, right })
+}
+
+// rebuilds a node whose subtrees differ in height by at most two
+fn balance(key: Key, value: Term, left: Link, right: Link) -> Rc<Node> {
+    let (hl, hr) = (height(&left), height(&right));
+    if hl > hr + 1 {
+        let l = left.expect("a deeper left subtree is not empty");
+        if height(&l.left) >= height(&l.right) {
+            let right = node(key, value, l.right.clone(), right);
+            return node(l.key.clone(), l.value.clone(), l.left.clone(), Some(right));
+        }
+        let m = l.right.clone().expect("a deeper inner subtree is not empty");
+        let left = node(l.key.clone(), l.value.clone(), l.left.clone(), m.left.clone());
+        let right = node(key, value, m.right.clone(), right);
+        return node(m.key.clone(), m.value.clone(), Some(left), Some(right));
+    }
+    if hr > hl + 1 {
+        let r = right.expect("a deeper right subtree is not empty");
+        if height(&r.right) >= height(&r.left) {
+            let left = node(key, value, left, r.left.clone());
+            return node(r.key.clone(), r.value.clone(), Some(left), r.right.clone());
+        }
+        let m = r.left.clone().expect("a deeper inner subtree is not empty");
+        let left = node(key, value, left, m.left.clone());
+        let right = node(r.key.clone(), r.value.clone(), m.right.clone(), r.right.clone());
+        return node(m.key.clone(), m.value.clone(), Some(left), Some(right));
+    }
+    node(key, value, left, right)
+}
+
+fn insert(link: &Link, key: Key, value: Term) -> Rc<Node> {
+    let n = match link {
+        None => return node(key, value, None, None),
+        Some(n) => n,
+    };
+    match key.cmp(&n.key) {
+        Ordering::Less => balance(n.key.clone(), n.value.clone(), Some(insert(&n.left, key, value)), n.right.clone()),
+        Ordering::Greater => balance(n.key.clone(), n.value.clone(), n.left.clone(), Some(insert(&n.right, key, value))),
+        Ordering::Equal => node(key, value, n.left.clone(), n.right.clone()),
+    }
+}
+
+fn remove(link: &Link, key: &Key) -> Link {
+    let n = link.as_ref()?;
+    Some(match key.cmp(&n.key) {
+        Ordering::Less => balance(n.key.clone(), n.value.clone(), remove(&n.left, key), n.right.clone()),
+        Ordering::Greater => balance(n.key.clone(), n.value.clone(), n.left.clone(), remove(&n.right, key)),
+        Ordering::Equal => match (&n.left, &n.right) {
+            (None, right) => return right.clone(),
+            (left, None) => return left.clone(),
+            (left, Some(right)) => {
+                let (key, value) = first(right);
+                let right = remove(&n.right, &key);
+                balance(key, value, left.clone(), right)
+            }
+        },
+    })
+}
+
+fn first(node: &Rc<Node>) -> (Key, Term) {
+    match &node.left {
+        Some(left) => first(left),
+        None => (node.key.clone(), node.value.clone()),
+    }
+}
+
+fn map(link: &Link, f: &mut dyn FnMut(&Term) -> Term) -> Link {
+    let n = link.as_ref()?;
+    let left = map(&n.left, f);
+    let value = f(&n.value);
+    let right = map(&n.right, f);
+    Some(Rc::new(Node { key: n.key.clone(), value, height: n.height, left, right }))
+}
@@ -0,0 +1,134 @@
+use std::fmt::{self, Display, Formatter};
+
+use super::term::Term;
+
+// An exception that reached the top of a query. `kind` picks apart the ISO
+// error terms; `ball` is the term exactly as it was thrown.
+#[derive(Clone, Debug)]
+pub struct PrologError {
+    pub kind: ErrorKind,
+    pub ball: Term,
+}
+
+#[derive(Clone, Debug)]
+pub enum ErrorKind {
+    Instantiation,
+    Type { valid_type: String, culprit: Term },
+    Domain { domain: String, culprit: Term },
+    Existence { kind: String, culprit: Term },
+    Permission { action: String, kind: String, culprit: Term },
+    Representation { limit: String },
+    Evaluation { error: String },
+    Resource { resource: String },
+    Syntax { message: String },
+    // `error(Formal, Context)` with a formal term outside the ISO set
+    Other(Term),
+    // a ball that is not an error term at all
+    Thrown(Term),
+}
+
+impl PrologError {
+    pub fn from_ball(ball: Term) -> PrologError {
+        let kind = match ball.deref() {
+            Term::Compound(c) if &*c.name == "error" && c.args.len() == 2 => ErrorKind::from_formal(&c.args[0]),
+            thrown => ErrorKind::Thrown(thrown),
+        };
+        PrologError { kind, ball }
+    }
+}
+
+impl ErrorKind {
+    fn from_formal(formal: &Term) -> ErrorKind {
+        let formal = formal.deref();
+        let (name, args) = match &formal {
+            Term::Atom(name) if &**name == "instantiation_error" => return ErrorKind::Instantiation,
+            Term::Compound(c) => (&*c.name, &c.args[..]),
+            _ => return ErrorKind::Other(formal.clone()),
+        };
+        let text = |term: &Term| term.to_string();
+        match (name, args) {
+            ("type_error", [valid_type, culprit]) => ErrorKind::Type {
+                valid_type: text(valid_type),
+                culprit: culprit.clone(),
+            },
+            ("domain_error", [domain, culprit]) => ErrorKind::Domain {
+                domain: text(domain),
+                culprit: culprit.clone(),
+            },
+            ("existence_error", [kind, culprit]) => ErrorKind::Existence {
+                kind: text(kind),
+                culprit: culprit.clone(),
+            },
+            ("permission_error", [action, kind, culprit]) => ErrorKind::Permission {
+                action: text(action),
+                kind: text(kind),
+                culprit: culprit.clone(),
+            },
+            ("representation_error", [limit]) => ErrorKind::Representation { limit: text(limit) },
+            ("evaluation_error", [error]) => ErrorKind::Evaluation { error: text(error) },
+            ("resource_error", [resource]) => ErrorKind::Resource { resource: text(resource) },
+            ("syntax_error", [message]) => ErrorKind::Syntax { message: text(message) },
+            _ => ErrorKind::Other(formal.clone()),
+        }
+    }
+}
+
+impl Display for PrologError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.ball)
+    }
+}
+
+impl std::error::Error for PrologError {}
+
+// Constructors for the formal part of ISO error terms. The engine wraps them
+// into `error(Formal, Context)` when a builtin reports one.
+
+pub fn instantiation_error() -> Term {
+    Term::atom("instantiation_error")
+}
+
+pub fn type_error(valid_type: &str, culprit: Term) -> Term {
+    Term::compound("type_error", vec![Term::atom(valid_type), culprit])
+}
+
+pub fn evaluation_error(error: &str) -> Term {
+    Term::compound("evaluation_error", vec![Term::atom(error)])
+}
+
+pub fn indicator(name: &str, arity: usize) -> Term {
+    Term::compound("/", vec![Term::atom(name), Term::Int(arity as i64)])
+}
+
+pub fn domain_error(domain: &str, culprit: Term) -> Term {
+    Term::compound("domain_error", vec![Term::atom(domain), culprit])
+}
+
+pub fn representation_error(limit: &str) -> Term {
+    Term::compound("representation_error", vec![Term::atom(limit)])
+}
+
+pub fn syntax_error(message: &str) -> Term {
+    Term::compound("syntax_error", vec![Term::atom(message)])
+}
+
+pub fn permission_error(action: &str, kind: &str, culprit: Term) -> Term {
+    Term::compound("permission_error", vec![Term::atom(action), Term::atom(kind), culprit])
+}
+
+pub fn existence_error(kind: &str, culprit: Term) -> Term {
+    Term::compound("existence_error", vec![Term::atom(kind), culprit])
+}
+
+pub fn resource_error(resource: &str) -> Term {
+    Term::compound("resource_error", vec![Term::atom(resource)])
+}
+
+pub fn uninstantiation_error(culprit: Term) -> Term {
+    Term::compound("uninstantiation_error", vec![culprit])
+}
+
+// a read or write the operating system refused
+pub fn io_error(action: &str, stream: Term) -> Term {
+    Term::compound("io_error", vec![Term::atom(action), stream])
+}
@@ -0,0 +1,93 @@
+use super::{
+    error::{domain_error, instantiation_error, type_error},
+    term::Term,
+};
+
+// Prolog flags that change how the engine behaves, settable from programs
+// through set_prolog_flag/2.
+#[derive(Default)]
+pub struct Flags {
+    pub prefer_rationals: bool,
+    pub unknown: Unknown,
+}
+
+// what calling a predicate without clauses or a dynamic declaration does
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Unknown {
+    #[default]
+    Error,
+    Warning,
+    Fail,
+}
+
+impl Unknown {
+    fn name(self) -> &'static str {
+        match self {
+            Unknown::Error => "error",
+            Unknown::Warning => "warning",
+            Unknown::Fail => "fail",
+        }
+    }
+
+    fn from_term(value: &Term) -> Option<Unknown> {
+        match value {
+            Term::Atom(name) => match &**name {
+                "error" => Some(Unknown::Error),
+                "warning" => Some(Unknown::Warning),
+                "fail" => Some(Unknown::Fail),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Flags {
+    pub const NAMES: [&'static str; 2] = ["prefer_rationals", "unknown"];
+
+    pub fn get(&self, name: &str) -> Option<Term> {
+        match name {
+            "prefer_rationals" => Some(bool_term(self.prefer_rationals)),
+            "unknown" => Some(Term::atom(self.unknown.name())),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, name: &Term, value: &Term) -> Result<(), Term> {
+        let (name, value) = (name.deref(), value.deref());
+        if let Term::Var(_) = name {
+            return Err(instantiation_error());
+        }
+        if let Term::Var(_) = value {
+            return Err(instantiation_error());
+        }
+        let flag = match &name {
+            Term::Atom(flag) => flag.clone(),
+            _ => return Err(type_error("atom", name)),
+        };
+        let bad_value = || {
+            domain_error(
+                "flag_value",
+                Term::compound("+", vec![name.clone(), value.clone()]),
+            )
+        };
+        match &*flag {
+            "prefer_rationals" => self.prefer_rationals = as_bool(&value).ok_or_else(bad_value)?,
+            "unknown" => self.unknown = Unknown::from_term(&value).ok_or_else(bad_value)?,
+            _ => return Err(domain_error("prolog_flag", name)),
+        }
+        Ok(())
+    }
+}
+
+fn bool_term(value: bool) -> Term {
+    Term::atom(if value { "true" } else { "false" })
+}
+
+fn as_bool(value: &Term) -> Option<bool> {
+    match value {
+        Term::Atom(name) if &**name == "true" => Some(true),
+        Term::Atom(name) if &**name == "false" => Some(false),
+        _ => None,
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasherDefault, Hasher},
+    rc::Rc,
+};
+
+use super::{
+    database::{ClauseRecord, Clauses},
+    term::Term,
+};
+
+// A position in a clause head: the argument, then the argument of each
+// compound term on the way down.
+pub type Path = Vec<usize>;
+
+// how deep into compound arguments indexes go
+pub const MAX_DEPTH: usize = 3;
+
+// The principal functor of the term at an indexed position. Floats,
+// rationals and dicts are left out: clauses with one of them there are tried
+// by every call, as are clauses with a variable there.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum IndexKey {
+    Atom(Rc<str>),
+    Int(i64),
+    Str(Rc<str>),
+    Functor(Rc<str>, usize),
+}
+
+impl IndexKey {
+    fn of(term: &Term) -> Option<IndexKey> {
+        match term {
+            Term::Atom(name) => Some(IndexKey::Atom(name.clone())),
+            Term::Int(n) => Some(IndexKey::Int(*n)),
+            Term::Str(text) => Some(IndexKey::Str(text.clone())),
+            Term::Compound(c) => Some(IndexKey::Functor(c.name.clone(), c.args.len())),
+            _ => None,
+        }
+    }
+
+    // whether `term` has this key, without making a key of it
+    fn is_key_of(&self, term: &Term) -> bool {
+        let same = |l: &Rc<str>, r: &Rc<str>| Rc::ptr_eq(l, r) || l == r;
+        match (self, term) {
+            (IndexKey::Atom(l), Term::Atom(r)) | (IndexKey::Str(l), Term::Str(r)) => same(l, r),
+            (IndexKey::Int(l), Term::Int(r)) => l == r,
+            (IndexKey::Functor(name, arity), Term::Compound(c)) => *arity == c.args.len() && same(name, &c.name),
+            _ => false,
+        }
+    }
+}
+
+// FNV-1a, as every call hashes the key of its argument and keys are short
+struct KeyHasher(u64);
+
+impl Default for KeyHasher {
+    fn default() -> KeyHasher {
+        KeyHasher(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for KeyHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// the term at `path` in the arguments of a head or a call, if it goes that
+// deep
+pub fn subterm(args: &[Term], path: &[usize]) -> Option<Term> {
+    let mut term = args.get(path[0])?.deref();
+    for &arg in &path[1..] {
+        term = match &term {
+            Term::Compound(c) => c.args.get(arg)?.deref(),
+            _ => return None,
+        };
+    }
+    Some(term)
+}
+
+// The bound positions of a call, down to MAX_DEPTH, each argument before the
+// positions inside it.
+pub fn bound_paths(args: &[Term]) -> Vec<Path> {
+    let mut paths = Vec::new();
+    collect_bound(args, &mut Vec::new(), &mut paths);
+    paths
+}
+
+fn collect_bound(args: &[Term], path: &mut Path, paths: &mut Vec<Path>) {
+    for (i, arg) in args.iter().enumerate() {
+        let arg = arg.deref();
+        if matches!(arg, Term::Var(_)) {
+            continue;
+        }
+        path.push(i);
+        paths.push(path.clone());
+        if let (true, Term::Compound(c)) = (path.len() < MAX_DEPTH, &arg) {
+            collect_bound(&c.args, path, paths);
+        }
+        path.pop();
+    }
+}
+
+fn put(clauses: &mut Clauses, record: &Rc<ClauseRecord>, front: bool) {
+    let clauses = Rc::make_mut(clauses);
+    if front {
+        clauses.insert(0, record.clone());
+    } else {
+        clauses.push(record.clone());
+    }
+}
+
+// an index with up to this many keys finds a key by comparing it with each,
+// which beats hashing it for the few keys most predicates have
+const FEW_KEYS: usize = 8;
+
+// An index on one position: for every key the clauses have there, the
+// clauses a call with that key can match, and the clauses that match any
+// call. Each list keeps the order of the predicate.
+pub struct ArgIndex {
+    keyed: Vec<(IndexKey, Clauses)>,
+    // where each key is in `keyed`, once there are more than a few
+    positions: HashMap<IndexKey, usize, BuildHasherDefault<KeyHasher>>,
+    unindexed: Clauses,
+}
+
+impl ArgIndex {
+    pub fn new() -> ArgIndex {
+        ArgIndex { keyed: Vec::new(), positions: HashMap::default(), unindexed: Rc::new(Vec::new()) }
+    }
+
+    pub fn insert(&mut self, path: &[usize], record: &Rc<ClauseRecord>, front: bool) {
+        let head = record.head.deref();
+        let args: &[Term] = match &head {
+            Term::Compound(c) => &c.args,
+            _ => &[],
+        };
+        match subterm(args, path).and_then(|term| IndexKey::of(&term)) {
+            Some(key) => match self.position(&key) {
+                Some(at) => put(&mut self.keyed[at].1, record, front),
+                None => {
+                    let mut clauses = self.unindexed.clone();
+                    put(&mut clauses, record, front);
+                    self.keyed.push((key.clone(), clauses));
+                    match self.positions.is_empty() {
+                        true => self.reposition(),
+                        false => {
+                            self.positions.insert(key, self.keyed.len() - 1);
+                        }
+                    }
+                }
+            },
+            None => {
+                for (_, clauses) in self.keyed.iter_mut() {
+                    put(clauses, record, front);
+                }
+                put(&mut self.unindexed, record, front);
+            }
+        }
+    }
+
+    pub fn retain(&mut self, keep: &impl Fn(&Rc<ClauseRecord>) -> bool) {
+        for clauses in self.keyed.iter_mut().map(|(_, clauses)| clauses).chain([&mut self.unindexed]) {
+            if !clauses.iter().all(keep) {
+                Rc::make_mut(clauses).retain(keep);
+            }
+        }
+        let unindexed = self.unindexed.len();
+        self.keyed.retain(|(_, clauses)| clauses.len() > unindexed);
+        self.reposition();
+    }
+
+    fn position(&self, key: &IndexKey) -> Option<usize> {
+        match self.positions.is_empty() {
+            true => self.keyed.iter().position(|(k, _)| k == key),
+            false => self.positions.get(key).copied(),
+        }
+    }
+
+    fn reposition(&mut self) {
+        self.positions.clear();
+        if self.keyed.len() > FEW_KEYS {
+            self.positions = self.keyed.iter().enumerate().map(|(at, (key, _))| (key.clone(), at)).collect();
+        }
+    }
+
+    // the clauses a call with `term`, which is bound, at the position can
+    // match
+    pub fn lookup(&self, term: &Term) -> &Clauses {
+        let at = match self.positions.is_empty() {
+            true => self.keyed.iter().position(|(key, _)| key.is_key_of(term)),
+            false => IndexKey::of(term).and_then(|key| self.positions.get(&key).copied()),
+        };
+        at.map_or(&self.unindexed, |at| &self.keyed[at].1)
+    }
+
+    // an index that tells fewer than two groups of clauses apart saves nothing
+    pub fn is_selective(&self) -> bool {
+        self.keyed.len() > 1
+    }
+}
@@ -0,0 +1,263 @@
+use std::rc::Rc;
+
+use super::{
+    dict::{Dict, Key},
+    error::{instantiation_error, syntax_error, type_error},
+    term::{format_float, Term},
+    IdFactory,
+};
+
+// Reads JSON text: objects become dicts with an unbound tag, arrays lists,
+// strings Prolog strings, and `true`, `false` and `null` atoms.
+pub fn parse(text: &str, id_factory: &mut IdFactory) -> Result<Term, Term> {
+    let mut reader = Reader { input: text.chars().collect(), pos: 0, id_factory };
+    let value = reader.value()?;
+    reader.skip_layout();
+    if reader.pos < reader.input.len() {
+        return Err(syntax_error("illegal_json"));
+    }
+    Ok(value)
+}
+
+struct Reader<'a> {
+    input: Vec<char>,
+    pos: usize,
+    id_factory: &'a mut IdFactory,
+}
+
+impl Reader<'_> {
+    fn skip_layout(&mut self) {
+        while self.input.get(self.pos).is_some_and(|ch| ch.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_layout();
+        self.input.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Term> {
+        if self.peek() != Some(expected) {
+            return Err(syntax_error("illegal_json"));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Term, Term> {
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(Term::string(&self.string()?)),
+            Some(ch) if ch == '-' || ch.is_ascii_digit() => self.number(),
+            Some(ch) if ch.is_ascii_alphabetic() => {
+                let start = self.pos;
+                while self.input.get(self.pos).is_some_and(|ch| ch.is_ascii_alphabetic()) {
+                    self.pos += 1;
+                }
+                let word: String = self.input[start..self.pos].iter().collect();
+                match word.as_str() {
+                    "true" | "false" | "null" => Ok(Term::atom(&word)),
+                    _ => Err(syntax_error("illegal_json")),
+                }
+            }
+            Some(_) => Err(syntax_error("illegal_json")),
+            None => Err(syntax_error("end_of_file")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Term, Term> {
+        self.expect('{')?;
+        let mut pairs = Vec::new();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+        } else {
+            loop {
+                if self.peek() != Some('"') {
+                    return Err(syntax_error("illegal_json"));
+                }
+                let key = Key::Atom(Rc::from(self.string()?.as_str()));
+                self.expect(':')?;
+                pairs.push((key, self.value()?));
+                match self.peek() {
+                    Some(',') => self.pos += 1,
+                    _ => {
+                        self.expect('}')?;
+                        break;
+                    }
+                }
+            }
+        }
+        let tag = Term::var(self.id_factory);
+        match Dict::from_pairs(tag, pairs) {
+            Some(dict) => Ok(Term::Dict(Rc::new(dict))),
+            None => Err(syntax_error("duplicate_key")),
+        }
+    }
+
+    fn array(&mut self) -> Result<Term, Term> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Term::from_list(items));
+        }
+        loop {
+            items.push(self.value()?);
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                _ => {
+                    self.expect(']')?;
+                    return Ok(Term::from_list(items));
+                }
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Term> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            let ch = self.next().ok_or_else(|| syntax_error("unterminated_string"))?;
+            match ch {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escaped = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode_escape()?,
+                        _ => return Err(syntax_error("illegal_escape")),
+                    };
+                    text.push(escaped);
+                }
+                ch => text.push(ch),
+            }
+        }
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.input.get(self.pos).copied()?;
+        self.pos += 1;
+        Some(ch)
+    }
+
+    fn hex4(&mut self) -> Result<u32, Term> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.next().and_then(|ch| ch.to_digit(16));
+            code = code * 16 + digit.ok_or_else(|| syntax_error("illegal_escape"))?;
+        }
+        Ok(code)
+    }
+
+    // `\uXXXX`, reading a second escape for the low half of a surrogate pair
+    fn unicode_escape(&mut self) -> Result<char, Term> {
+        let high = self.hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if self.next() != Some('\\') || self.next() != Some('u') {
+                return Err(syntax_error("illegal_escape"));
+            }
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(syntax_error("illegal_escape"));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| syntax_error("illegal_escape"))
+    }
+
+    fn number(&mut self) -> Result<Term, Term> {
+        let start = self.pos;
+        let mut is_float = false;
+        if self.input.get(self.pos) == Some(&'-') {
+            self.pos += 1;
+        }
+        while let Some(&ch) = self.input.get(self.pos) {
+            match ch {
+                '0'..='9' => {}
+                '.' | 'e' | 'E' => is_float = true,
+                '+' | '-' if is_float => {}
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let text: String = self.input[start..self.pos].iter().collect();
+        if !is_float {
+            if let Ok(n) = text.parse::<i64>() {
+                return Ok(Term::Int(n));
+            }
+        }
+        match text.parse::<f64>() {
+            Ok(n) => Ok(Term::Float(n)),
+            Err(_) => Err(syntax_error("illegal_number")),
+        }
+    }
+}
+
+// Writes a term as compact JSON: dicts as objects, proper lists as arrays,
+// strings and atoms other than `true`, `false` and `null` as strings.
+pub fn write(term: &Term) -> Result<String, Term> {
+    let mut out = String::new();
+    write_value(term, &mut out)?;
+    Ok(out)
+}
+
+fn write_value(term: &Term, out: &mut String) -> Result<(), Term> {
+    match term.deref() {
+        Term::Var(_) => return Err(instantiation_error()),
+        Term::Int(n) => out.push_str(&n.to_string()),
+        Term::Float(n) if n.is_finite() => out.push_str(&format_float(n)),
+        Term::Atom(name) if matches!(&*name, "true" | "false" | "null") => out.push_str(&name),
+        Term::Atom(name) if &*name == "[]" => out.push_str("[]"),
+        Term::Atom(text) | Term::Str(text) => write_string(&text, out),
+        Term::Dict(d) => {
+            out.push('{');
+            for (i, (key, value)) in d.pairs().iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(&key.to_term().to_string(), out);
+                out.push(':');
+                write_value(value, out)?;
+            }
+            out.push('}');
+        }
+        list @ Term::Compound(_) if list.is_list() => {
+            out.push('[');
+            for (i, item) in list.list_items().0.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(item, out)?;
+            }
+            out.push(']');
+        }
+        culprit => return Err(type_error("json_term", culprit)),
+    }
+    Ok(())
+}
+
+fn write_string(text: &str, out: &mut String) {
+    out.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,101 @@
+// Libraries bundled with the interpreter as Prolog source. A library is
+// loaded the first time one of its exported predicates is called, or by
+// `use_module(library(Name))`, and never replaces a predicate the program
+// defines itself.
+pub struct Library {
+    pub name: &'static str,
+    pub exports: &'static [(&'static str, usize)],
+    pub source: &'static str,
+}
+
+pub const LIBRARIES: [Library; 6] = [
+    Library {
+        name: "lists",
+        exports: &[
+            ("append", 3),
+            ("member", 2),
+            ("memberchk", 2),
+            ("length", 2),
+            ("nth0", 3),
+            ("nth1", 3),
+            ("reverse", 2),
+            ("last", 2),
+            ("predsort", 3),
+            ("permutation", 2),
+            ("select", 3),
+            ("sum_list", 2),
+            ("max_list", 2),
+            ("list_to_set", 2),
+            ("subtract", 3),
+            ("delete", 3),
+        ],
+        source: include_str!("library/lists.pl"),
+    },
+    Library {
+        name: "apply",
+        exports: &[
+            ("maplist", 2),
+            ("maplist", 3),
+            ("maplist", 4),
+            ("maplist", 5),
+            ("maplist", 6),
+            ("maplist", 7),
+            ("foldl", 4),
+            ("foldl", 5),
+            ("foldl", 6),
+            ("include", 3),
+            ("exclude", 3),
+            ("partition", 4),
+        ],
+        source: include_str!("library/apply.pl"),
+    },
+    Library {
+        name: "assoc",
+        exports: &[
+            ("empty_assoc", 1),
+            ("get_assoc", 3),
+            ("put_assoc", 4),
+            ("list_to_assoc", 2),
+            ("assoc_to_list", 2),
+            ("assoc_to_keys", 2),
+            ("assoc_to_values", 2),
+        ],
+        source: include_str!("library/assoc.pl"),
+    },
+    Library {
+        name: "ordsets",
+        exports: &[
+            ("list_to_ord_set", 2),
+            ("ord_union", 3),
+            ("ord_subtract", 3),
+            ("ord_memberchk", 2),
+        ],
+        source: include_str!("library/ordsets.pl"),
+    },
+    Library {
+        name: "pairs",
+        exports: &[
+            ("pairs_keys_values", 3),
+            ("pairs_keys", 2),
+            ("pairs_values", 2),
+            ("transpose_pairs", 2),
+        ],
+        source: include_str!("library/pairs.pl"),
+    },
+    // lambdas are run by the engine, there is nothing to load
+    Library {
+        name: "yall",
+        exports: &[],
+        source: "",
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Library> {
+    LIBRARIES.iter().find(|library| library.name == name)
+}
+
+pub fn exporting(name: &str, arity: usize) -> Option<&'static Library> {
+    LIBRARIES
+        .iter()
+        .find(|library| library.exports.contains(&(name, arity)))
+}
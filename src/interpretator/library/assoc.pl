@@ -0,0 +1,87 @@
+% library(assoc): association lists as AVL trees, loaded on first use. A tree
+% is `t` when empty or `t(Key, Value, Balance, Left, Right)`, where Balance
+% is `<` when the right subtree is deeper, `>` when the left one is and `=`
+% when they are even.
+
+empty_assoc(t).
+
+get_assoc(Key, t(K, V, _, L, R), Value) :-
+    compare(O, Key, K),
+    '$get_assoc'(O, Key, V, Value, L, R).
+
+'$get_assoc'(=, _, V, V, _, _).
+'$get_assoc'(<, Key, _, V, L, _) :- get_assoc(Key, L, V).
+'$get_assoc'(>, Key, _, V, _, R) :- get_assoc(Key, R, V).
+
+put_assoc(Key, A0, Value, A) :- '$put_assoc'(A0, Key, Value, A, _).
+
+% the last argument tells whether the tree got deeper
+'$put_assoc'(t, K, V, t(K, V, =, t, t), yes).
+'$put_assoc'(t(K0, V0, B, L, R), K, V, A, Grew) :-
+    compare(O, K, K0),
+    '$put_assoc'(O, K0, V0, B, L, R, K, V, A, Grew).
+
+'$put_assoc'(=, K0, _, B, L, R, _, V, t(K0, V, B, L, R), no).
+'$put_assoc'(<, K0, V0, B, L, R, K, V, A, Grew) :-
+    '$put_assoc'(L, K, V, L1, LeftGrew),
+    '$left_grew'(LeftGrew, K0, V0, B, L1, R, A, Grew).
+'$put_assoc'(>, K0, V0, B, L, R, K, V, A, Grew) :-
+    '$put_assoc'(R, K, V, R1, RightGrew),
+    '$right_grew'(RightGrew, K0, V0, B, L, R1, A, Grew).
+
+'$left_grew'(no, K, V, B, L, R, t(K, V, B, L, R), no).
+'$left_grew'(yes, K, V, <, L, R, t(K, V, =, L, R), no).
+'$left_grew'(yes, K, V, =, L, R, t(K, V, >, L, R), yes).
+'$left_grew'(yes, K, V, >, L, R, A, no) :- '$rotate_right'(L, K, V, R, A).
+
+'$right_grew'(no, K, V, B, L, R, t(K, V, B, L, R), no).
+'$right_grew'(yes, K, V, >, L, R, t(K, V, =, L, R), no).
+'$right_grew'(yes, K, V, =, L, R, t(K, V, <, L, R), yes).
+'$right_grew'(yes, K, V, <, L, R, A, no) :- '$rotate_left'(L, K, V, R, A).
+
+'$rotate_right'(t(LK, LV, >, LL, LR), K, V, R, t(LK, LV, =, LL, t(K, V, =, LR, R))).
+'$rotate_right'(t(LK, LV, <, LL, t(MK, MV, MB, ML, MR)), K, V, R,
+        t(MK, MV, =, t(LK, LV, B1, LL, ML), t(K, V, B2, MR, R))) :-
+    '$double_rotation'(MB, B1, B2).
+
+'$rotate_left'(L, K, V, t(RK, RV, <, RL, RR), t(RK, RV, =, t(K, V, =, L, RL), RR)).
+'$rotate_left'(L, K, V, t(RK, RV, >, t(MK, MV, MB, ML, MR), RR),
+        t(MK, MV, =, t(K, V, B1, L, ML), t(RK, RV, B2, MR, RR))) :-
+    '$double_rotation'(MB, B1, B2).
+
+% the balances of the two subtrees after a double rotation around a middle
+% node that had balance MB
+'$double_rotation'(>, =, <).
+'$double_rotation'(=, =, =).
+'$double_rotation'(<, >, =).
+
+list_to_assoc(Pairs, Assoc) :- '$list_to_assoc'(Pairs, Pairs, t, Assoc).
+
+'$list_to_assoc'(_, [], A, A).
+'$list_to_assoc'(Pairs, [K-V|Ps], A0, A) :-
+    (   get_assoc(K, A0, _)
+    ->  throw(error(domain_error(unique_key_pairs, Pairs), context(list_to_assoc/2, _)))
+    ;   put_assoc(K, A0, V, A1),
+        '$list_to_assoc'(Pairs, Ps, A1, A)
+    ).
+
+assoc_to_list(A, Pairs) :- '$assoc_to_list'(A, Pairs, []).
+
+'$assoc_to_list'(t, Ps, Ps).
+'$assoc_to_list'(t(K, V, _, L, R), Ps, Rest) :-
+    '$assoc_to_list'(L, Ps, [K-V|Ps1]),
+    '$assoc_to_list'(R, Ps1, Rest).
+
+assoc_to_keys(A, Keys) :- '$assoc_to_keys'(A, Keys, []).
+
+'$assoc_to_keys'(t, Ks, Ks).
+'$assoc_to_keys'(t(K, _, _, L, R), Ks, Rest) :-
+    '$assoc_to_keys'(L, Ks, [K|Ks1]),
+    '$assoc_to_keys'(R, Ks1, Rest).
+
+assoc_to_values(A, Values) :- '$assoc_to_values'(A, Values, []).
+
+'$assoc_to_values'(t, Vs, Vs).
+'$assoc_to_values'(t(_, V, _, L, R), Vs, Rest) :-
+    '$assoc_to_values'(L, Vs, [V|Vs1]),
+    '$assoc_to_values'(R, Vs1, Rest).
@@ -0,0 +1,126 @@
+% library(lists): list predicates loaded on first use. msort/2, sort/2 and
+% sort/4 are native builtins.
+
+append([], L, L).
+append([H|T], L, [H|R]) :- append(T, L, R).
+
+member(X, [X|_]).
+member(X, [_|T]) :- member(X, T).
+
+memberchk(X, L) :- member(X, L), !.
+
+% a partial list whose tail is the length itself has no length
+length(List, N) :- var(N), !, '$skip_list'(List, 0, K, Tail), Tail \== N, '$length'(Tail, K, N).
+length(_, N) :- integer(N), N < 0, !, throw(error(domain_error(not_less_than_zero, N), context(length/2, _))).
+length(List, N) :- integer(N), !, '$make_list'(N, List).
+length(_, N) :- throw(error(type_error(integer, N), context(length/2, _))).
+
+'$skip_list'(L, K, K, L) :- var(L), !.
+'$skip_list'([_|T], K0, K, Tail) :- !, K1 is K0 + 1, '$skip_list'(T, K1, K, Tail).
+'$skip_list'(L, K, K, L).
+
+'$length'([], N, N).
+'$length'([_|T], N0, N) :- N1 is N0 + 1, '$length'(T, N1, N).
+
+'$make_list'(0, []) :- !.
+'$make_list'(N, [_|T]) :- M is N - 1, '$make_list'(M, T).
+
+nth0(I, L, E) :- integer(I), !, I >= 0, '$nth'(I, L, E).
+nth0(I, L, E) :- var(I), !, '$nth_gen'(L, E, 0, I).
+nth0(I, _, _) :- throw(error(type_error(integer, I), context(nth0/3, _))).
+
+nth1(I, L, E) :- integer(I), !, I >= 1, I0 is I - 1, '$nth'(I0, L, E).
+nth1(I, L, E) :- var(I), !, '$nth_gen'(L, E, 1, I).
+nth1(I, _, _) :- throw(error(type_error(integer, I), context(nth1/3, _))).
+
+'$nth'(0, [E|_], E) :- !.
+'$nth'(I, [_|T], E) :- I1 is I - 1, '$nth'(I1, T, E).
+
+'$nth_gen'([E|_], E, B, B).
+'$nth_gen'([_|T], E, B0, B) :- B1 is B0 + 1, '$nth_gen'(T, E, B1, B).
+
+reverse(L, R) :- '$reverse'(L, [], R).
+
+'$reverse'([], R, R).
+'$reverse'([H|T], A, R) :- '$reverse'(T, [H|A], R).
+
+last([X|Xs], Last) :- '$last'(Xs, X, Last).
+
+'$last'([], Last, Last).
+'$last'([X|Xs], _, Last) :- '$last'(Xs, X, Last).
+
+:- meta_predicate predsort(3, +, -).
+
+% merge sort on the order `Pred` reports, dropping elements it finds equal
+predsort(P, L, Sorted) :-
+    length(L, N),
+    (   N < 2
+    ->  Sorted = L
+    ;   H is N // 2,
+        length(Front, H),
+        append(Front, Back, L),
+        predsort(P, Front, S1),
+        predsort(P, Back, S2),
+        '$predmerge'(P, S1, S2, Sorted)
+    ).
+
+'$predmerge'(_, [], L, L) :- !.
+'$predmerge'(_, L, [], L) :- !.
+'$predmerge'(P, [H1|T1], [H2|T2], R) :-
+    call(P, O, H1, H2),
+    '$predmerge'(O, P, H1, H2, T1, T2, R).
+
+'$predmerge'(<, P, H1, H2, T1, T2, [H1|R]) :- '$predmerge'(P, T1, [H2|T2], R).
+'$predmerge'(>, P, H1, H2, T1, T2, [H2|R]) :- '$predmerge'(P, [H1|T1], T2, R).
+'$predmerge'(=, P, H1, _, T1, T2, [H1|R]) :- '$predmerge'(P, T1, T2, R).
+
+permutation(L, P) :-
+    length(L, N),
+    length(P, N),
+    '$permutation'(L, P).
+
+'$permutation'([], []).
+'$permutation'(L, [H|T]) :- select(H, L, R), '$permutation'(R, T).
+
+select(X, [X|T], T).
+select(X, [H|T], [H|R]) :- select(X, T, R).
+
+sum_list(L, S) :- '$sum_list'(L, 0, S).
+
+'$sum_list'([], S, S).
+'$sum_list'([X|Xs], S0, S) :- S1 is S0 + X, '$sum_list'(Xs, S1, S).
+
+max_list([H|T], M) :- '$max_list'(T, H, M).
+
+'$max_list'([], M, M).
+'$max_list'([H|T], M0, M) :- M1 is max(M0, H), '$max_list'(T, M1, M).
+
+% keeps the first of every group of identical elements
+list_to_set(L, S) :- '$list_to_set'(L, [], S).
+
+'$list_to_set'([], _, []).
+'$list_to_set'([H|T], Seen, R) :-
+    (   '$memberchk_eq'(H, Seen)
+    ->  R = R1
+    ;   R = [H|R1]
+    ),
+    '$list_to_set'(T, [H|Seen], R1).
+
+'$memberchk_eq'(X, [Y|T]) :- ( X == Y -> true ; '$memberchk_eq'(X, T) ).
+
+subtract([], _, []).
+subtract([H|T], L, R) :-
+    (   memberchk(H, L)
+    ->  R = R1
+    ;   R = [H|R1]
+    ),
+    subtract(T, L, R1).
+
+% removes every element that unifies with `X`
+delete([], _, []).
+delete([H|T], X, R) :-
+    (   H \= X
+    ->  R = [H|R1]
+    ;   R = R1
+    ),
+    delete(T, X, R1).
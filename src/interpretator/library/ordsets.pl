@@ -0,0 +1,31 @@
+% library(ordsets): sets as lists in the standard order of terms without
+% duplicates, loaded on first use.
+
+list_to_ord_set(List, Set) :- sort(List, Set).
+
+ord_union([], B, B).
+ord_union([A|As], [], [A|As]).
+ord_union([A|As], [B|Bs], U) :-
+    compare(O, A, B),
+    '$ord_union'(O, A, As, B, Bs, U).
+
+'$ord_union'(<, A, As, B, Bs, [A|U]) :- ord_union(As, [B|Bs], U).
+'$ord_union'(=, A, As, _, Bs, [A|U]) :- ord_union(As, Bs, U).
+'$ord_union'(>, A, As, B, Bs, [B|U]) :- ord_union([A|As], Bs, U).
+
+ord_subtract([], _, []).
+ord_subtract([A|As], [], [A|As]).
+ord_subtract([A|As], [B|Bs], D) :-
+    compare(O, A, B),
+    '$ord_subtract'(O, A, As, B, Bs, D).
+
+'$ord_subtract'(<, A, As, B, Bs, [A|D]) :- ord_subtract(As, [B|Bs], D).
+'$ord_subtract'(=, _, As, _, Bs, D) :- ord_subtract(As, Bs, D).
+'$ord_subtract'(>, A, As, _, Bs, D) :- ord_subtract([A|As], Bs, D).
+
+ord_memberchk(X, [Y|Ys]) :-
+    compare(O, X, Y),
+    '$ord_memberchk'(O, X, Ys).
+
+'$ord_memberchk'(=, _, _).
+'$ord_memberchk'(>, X, Ys) :- ord_memberchk(X, Ys).
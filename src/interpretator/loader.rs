@@ -0,0 +1,79 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    parser::{Parser, ParserError},
+    tokenizer::{Tokenizer, TokenizerError},
+};
+
+use super::{
+    error::{existence_error, permission_error, syntax_error},
+    term::Term,
+    Clause,
+};
+
+// The file a specification names. A relative path is taken from the
+// directory of `base`, the file being loaded when there is one, and `.pl` is
+// added when only the file with that extension exists. Paths come back
+// canonical so that consulting a file twice finds the same file.
+pub fn resolve(spec: &str, base: Option<&str>) -> String {
+    let mut path = PathBuf::from(spec);
+    if path.is_relative() {
+        if let Some(directory) = base.and_then(|base| Path::new(base).parent()) {
+            path = directory.join(path);
+        }
+    }
+    if !path.exists() && path.extension().is_none() {
+        let with_extension = path.with_extension("pl");
+        if with_extension.exists() {
+            path = with_extension;
+        }
+    }
+    let path = fs::canonicalize(&path).unwrap_or(path);
+    path.to_string_lossy().into_owned()
+}
+
+// the clauses of a file, each with the line it starts on
+pub type Lines = Vec<(usize, Box<dyn Clause>)>;
+
+// Reads a program file into its clauses, along with the syntax errors of
+// the clauses that had to be left out.
+pub fn read(path: &str) -> Result<(Lines, Vec<(usize, Term)>), Term> {
+    let text = fs::read_to_string(path).map_err(|error| match error.kind() {
+        ErrorKind::NotFound => existence_error("source_sink", Term::atom(path)),
+        _ => permission_error("open", "source_sink", Term::atom(path)),
+    })?;
+    Ok(parse(&text, path))
+}
+
+// The clauses of program text. A clause with a syntax error is left out and
+// reading goes on after its end. The errors come with their lines and name
+// `name` and the line in their message.
+pub fn parse(text: &str, name: &str) -> (Lines, Vec<(usize, Term)>) {
+    let (tokens, errors) = Tokenizer::from_str(text).parse_recovering();
+    let mut errors: Vec<(usize, Term)> = errors
+        .iter()
+        .map(|error| {
+            let TokenizerError::BaseError { msg, .. } = error;
+            let line = error.line(text);
+            (line, syntax_error(&format!("{}:{}: {}", name, line, msg)))
+        })
+        .collect();
+    let lines: Vec<usize> = tokens.iter().map(|(_, line)| *line).collect();
+    let mut parser = Parser::from_tokens(tokens.into_iter().map(|(token, _)| token).collect());
+    let mut clauses = Vec::new();
+    while let Some(start) = parser.next_clause_start() {
+        match parser.parse_clause() {
+            Ok(clause) => clauses.push((lines[start], clause)),
+            Err(ParserError::BaseError { msg }) => {
+                errors.push((lines[start], syntax_error(&format!("{}:{}: {}", name, lines[start], msg))));
+                parser.skip_clause();
+            }
+        }
+    }
+    errors.sort_by_key(|(line, _)| *line);
+    (clauses, errors)
+}
@@ -0,0 +1,922 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use super::{
+    builtins,
+    code::{compile_goal, Barrier, Code, Instruction, Procedure, Reg, Target},
+    database::{Clauses, Key, Predicate},
+    error,
+    flags::Unknown,
+    library,
+    module::{self, USER},
+    term::{bind, undo_trail, unify, Args, Compound, Term, Trail, Var},
+    write, IdFactory, Runner,
+};
+
+// the continuation environment of a frame at the bottom of the stack
+pub const NONE: usize = usize::MAX;
+
+// A place in some code: where the machine goes on, or where a call returns.
+#[derive(Clone)]
+pub struct Ptr {
+    code: Rc<Code>,
+    pc: usize,
+}
+
+// An environment frame: the Y registers of a running body, where its call
+// returns to, and the choice point height a cut in it goes back to. The
+// frames form a stack; a frame stays as long as the environment after it
+// or a choice point needs it.
+pub struct Env {
+    ce: usize,
+    cp: Ptr,
+    cut_barrier: usize,
+    ys: usize,
+    size: usize,
+    // a catch/3 frame: its catcher is active while the frame is part of the
+    // continuation
+    catch: bool,
+}
+
+enum Alternative {
+    // retry the clauses from `next` on, with the arguments of the call
+    Clauses { clauses: Clauses, next: usize, args: Vec<Term> },
+    // go on at the code the choice point resumes at
+    Code,
+    // the remaining candidates, last one first
+    Unify { term: Term, candidates: Vec<Term> },
+    Goal { goal: Term, cut_barrier: usize, module: Rc<str> },
+    // reached once the goal of a findall has no more solutions
+    Solutions { found: Vec<Term>, list: Term, tail: Term },
+    Disabled,
+}
+
+// A choice point saves the machine state to go back to: the trail height,
+// the continuation and the top of the environment stack.
+pub struct ChoicePoint {
+    alternative: Alternative,
+    trail_len: usize,
+    horizon: u64,
+    p: Ptr,
+    cp: Ptr,
+    e: usize,
+    envs: usize,
+    ys: usize,
+}
+
+// Where a call found the predicate it runs, for as long as no predicate or
+// module is added or taken away. A predicate of another module can declare
+// meta arguments that calls qualify with the module of the caller.
+pub struct Resolved {
+    generation: u64,
+    predicate: Rc<RefCell<Predicate>>,
+    meta: Option<Vec<Term>>,
+}
+
+// The code the engine runs goals with: a frame of `meta` calls the goal in
+// its Y registers, a frame of `catch` ends a catch/3, one of `collect` adds
+// a solution to a findall/3, and `stop` ends a query.
+pub struct Routines {
+    meta: Rc<Code>,
+    catch: Rc<Code>,
+    collect: Rc<Code>,
+    stop: Rc<Code>,
+}
+
+impl Routines {
+    pub fn new() -> Routines {
+        let routine = |instructions| Rc::new(Code::routine(instructions));
+        Routines {
+            meta: routine(vec![Instruction::MetaCall]),
+            catch: routine(vec![Instruction::ExitCatch, Instruction::Deallocate, Instruction::Proceed]),
+            collect: routine(vec![Instruction::Collect]),
+            stop: routine(vec![Instruction::Stop]),
+        }
+    }
+
+    pub fn stop(&self) -> Ptr {
+        Ptr { code: self.stop.clone(), pc: 0 }
+    }
+}
+
+// The registers and stacks of a query, put aside while a directive runs.
+pub struct Machine {
+    p: Ptr,
+    cp: Ptr,
+    e: usize,
+    b0: usize,
+    envs: Vec<Env>,
+    ys: Vec<Term>,
+    choicepoints: Vec<ChoicePoint>,
+    registers: Vec<Term>,
+}
+
+// a structure being built by PutStructure and the Set instructions after it
+struct Building {
+    name: Rc<str>,
+    arity: usize,
+    arg: usize,
+    args: Vec<Term>,
+}
+
+// The arguments go to a vector that is kept for the next structure once
+// they are in place.
+fn set_arg(building: &mut Option<Building>, registers: &mut [Term], spare: &mut Vec<Term>, arg: Term) {
+    if let Some(b) = building {
+        b.args.push(arg);
+        if b.args.len() == b.arity {
+            let mut b = building.take().expect("a structure being built");
+            registers[b.arg] = Term::new_compound(b.name, Args::take(&mut b.args));
+            *spare = b.args;
+        }
+    }
+}
+
+// where the arguments of the head structure being unified come from
+enum Mode {
+    Read { args: Rc<Compound>, next: usize },
+    Write { name: Rc<str>, arity: usize, args: Vec<Term>, target: Rc<Var> },
+}
+
+fn next_arg(args: &Rc<Compound>, next: &mut usize) -> Term {
+    *next += 1;
+    args.args[*next - 1].clone()
+}
+
+// adds an argument to the structure being built in write mode, binding the
+// variable it replaces once it is complete
+fn push_arg(mode: &mut Option<Mode>, arg: &Term, spare: &mut Vec<Term>, trail: &mut Trail) -> bool {
+    if let Some(Mode::Write { name, arity, args, target }) = mode {
+        args.push(arg.clone());
+        if args.len() == *arity {
+            // nothing binds the variable while its structure is built
+            bind(target, Term::new_compound(name.clone(), Args::take(args)), trail);
+            *spare = std::mem::take(args);
+            *mode = None;
+        }
+    }
+    true
+}
+
+fn unify_constant(term: &Term, constant: &Term, trail: &mut Trail) -> bool {
+    match (term.deref(), constant) {
+        (Term::Atom(l), Term::Atom(r)) => Rc::ptr_eq(&l, r) || l == *r,
+        (Term::Int(l), Term::Int(r)) => l == *r,
+        (term, _) => unify(&term, constant, trail),
+    }
+}
+
+fn x(reg: Reg) -> usize {
+    match reg {
+        Reg::X(x) => x,
+        Reg::Y(_) => unreachable!("head code only uses X registers"),
+    }
+}
+
+fn height(term: &Term) -> usize {
+    match term {
+        Term::Int(n) => *n as usize,
+        _ => unreachable!("a choice point height"),
+    }
+}
+
+fn instantiate(template: &Term, bindings: &[(u64, Reg)], value: impl Fn(Reg) -> Term, ids: &mut IdFactory) -> Term {
+    let mut mapping: HashMap<u64, Term> = bindings.iter().map(|(id, reg)| (*id, value(*reg))).collect();
+    template.rename(&mut mapping, ids)
+}
+
+impl Runner {
+    // Runs from the current instruction until the query succeeds, failing
+    // into the choice points and handing errors to catch/3 on the way.
+    pub(crate) fn run(&mut self) -> Result<bool, Term> {
+        loop {
+            match self.execute() {
+                Ok(true) => return Ok(true),
+                Ok(false) => {
+                    if !self.backtrack() {
+                        return Ok(false);
+                    }
+                }
+                Err(ball) => self.recover(ball)?,
+            }
+        }
+    }
+
+    // The instruction loop: runs until the query succeeds, fails, or raises
+    // an error. A jump to other code comes back here through `p`.
+    fn execute(&mut self) -> Result<bool, Term> {
+        loop {
+            let code = self.p.code.clone();
+            let mut pc = self.p.pc;
+            let mut building: Option<Building> = None;
+            loop {
+                match &code.instructions[pc] {
+                    Instruction::Allocate(size) => self.allocate(*size),
+                    Instruction::Deallocate => self.deallocate(),
+                    Instruction::GetVariable { reg, arg } => {
+                        let term = self.registers[*arg].clone();
+                        self.set(*reg, term);
+                    }
+                    Instruction::PutVariable(reg) => {
+                        let var = Term::var(&mut self.id_factory);
+                        self.set(*reg, var);
+                    }
+                    Instruction::PutValue { reg, arg } => {
+                        let term = self.get(*reg).clone();
+                        self.registers[*arg] = term;
+                    }
+                    Instruction::PutConstant { constant, arg } => self.registers[*arg] = constant.clone(),
+                    Instruction::PutStructure { name, arity, arg } => {
+                        let args = std::mem::take(&mut self.spare);
+                        building = Some(Building { name: name.clone(), arity: *arity, arg: *arg, args });
+                    }
+                    Instruction::PutTemplate { template, arg, bindings } => {
+                        let term = instantiate(template, bindings, |reg| self.get(reg).clone(), &mut IdFactory { id_counter: 0 });
+                        let term = term.rename(&mut HashMap::new(), &mut self.id_factory);
+                        self.registers[*arg] = term;
+                    }
+                    Instruction::SetVariable(reg) => {
+                        let var = Term::var(&mut self.id_factory);
+                        self.set(*reg, var.clone());
+                        set_arg(&mut building, &mut self.registers, &mut self.spare, var);
+                    }
+                    Instruction::SetValue(reg) => {
+                        let term = self.get(*reg).clone();
+                        set_arg(&mut building, &mut self.registers, &mut self.spare, term);
+                    }
+                    Instruction::SetConstant(constant) => set_arg(&mut building, &mut self.registers, &mut self.spare, constant.clone()),
+                    Instruction::SetVoid => {
+                        let var = Term::var(&mut self.id_factory);
+                        set_arg(&mut building, &mut self.registers, &mut self.spare, var);
+                    }
+                    Instruction::Call(procedure) => {
+                        let resume = Ptr { code: code.clone(), pc: pc + 1 };
+                        match self.call(procedure, resume)? {
+                            true => break,
+                            false => return Ok(false),
+                        }
+                    }
+                    Instruction::Execute(procedure) => {
+                        let resume = self.cp.clone();
+                        match self.call(procedure, resume)? {
+                            true => break,
+                            false => return Ok(false),
+                        }
+                    }
+                    Instruction::Proceed => {
+                        self.p = self.cp.clone();
+                        break;
+                    }
+                    Instruction::GetLevel(y) => {
+                        let level = Term::Int(self.choicepoints.len() as i64);
+                        self.set(Reg::Y(*y), level);
+                    }
+                    Instruction::Cut(barrier) => {
+                        let height = self.barrier(*barrier);
+                        self.cut(height);
+                    }
+                    Instruction::TryMeElse(label) => {
+                        let trail_len = self.trail.mark(&self.id_factory);
+                        self.push_choicepoint(Alternative::Code, trail_len, Ptr { code: code.clone(), pc: *label });
+                    }
+                    Instruction::Jump(label) => {
+                        pc = *label;
+                        continue;
+                    }
+                    Instruction::SoftCut(y) => {
+                        let level = height(self.get(Reg::Y(*y)));
+                        self.choicepoints[level].alternative = Alternative::Disabled;
+                    }
+                    Instruction::Fail => return Ok(false),
+                    Instruction::MetaCall => {
+                        let goal = self.ys[self.y].clone();
+                        let cut_barrier = height(&self.ys[self.y + 1]);
+                        let module = match &self.ys[self.y + 2] {
+                            Term::Atom(module) => module.clone(),
+                            _ => unreachable!("the module of a goal"),
+                        };
+                        self.deallocate();
+                        self.p = self.cp.clone();
+                        match self.call_goal(goal, cut_barrier, module)? {
+                            true => break,
+                            false => return Ok(false),
+                        }
+                    }
+                    // a goal that left no choice points takes the barrier of
+                    // its catch/3 along
+                    Instruction::ExitCatch => {
+                        let height = height(&self.ys[self.y + 3]);
+                        if self.choicepoints.len() == height + 1 {
+                            self.cut(height);
+                        }
+                    }
+                    Instruction::Collect => {
+                        let copy = self.ys[self.y].rename(&mut HashMap::new(), &mut self.id_factory);
+                        let height = height(&self.ys[self.y + 1]);
+                        if let Alternative::Solutions { found, .. } = &mut self.choicepoints[height].alternative {
+                            found.push(copy);
+                        }
+                        return Ok(false);
+                    }
+                    Instruction::Stop => return Ok(true),
+                    _ => unreachable!("head code runs in unify_head"),
+                }
+                pc += 1;
+            }
+        }
+    }
+
+    fn get(&self, reg: Reg) -> &Term {
+        match reg {
+            Reg::X(x) => &self.registers[x],
+            Reg::Y(y) => &self.ys[self.y + y],
+        }
+    }
+
+    fn set(&mut self, reg: Reg, term: Term) {
+        match reg {
+            Reg::X(x) => self.registers[x] = term,
+            Reg::Y(y) => self.ys[self.y + y] = term,
+        }
+    }
+
+    fn set_env(&mut self, e: usize) {
+        self.e = e;
+        self.y = self.envs.get(e).map_or(0, |env| env.ys);
+    }
+
+    fn barrier(&self, barrier: Barrier) -> usize {
+        match barrier {
+            Barrier::Call => self.b0,
+            Barrier::Clause => self.envs[self.e].cut_barrier,
+            Barrier::Level(y) => height(self.get(Reg::Y(y))),
+            Barrier::Above(y) => height(self.get(Reg::Y(y))) + 1,
+        }
+    }
+
+    // The tops of the environment stack and its Y registers that are in
+    // use: by the current environment and the ones it continues with, or by
+    // the last choice point.
+    fn stack_top(&self) -> (usize, usize) {
+        let (mut envs, mut ys) = self.choicepoints.last().map_or((0, 0), |choicepoint| (choicepoint.envs, choicepoint.ys));
+        if let Some(env) = self.envs.get(self.e) {
+            envs = envs.max(self.e + 1);
+            ys = ys.max(env.ys + env.size);
+        }
+        (envs, ys)
+    }
+
+    fn push_env(&mut self, cp: Ptr, slots: impl IntoIterator<Item = Term>, catch: bool) {
+        let (envs, ys) = self.stack_top();
+        self.envs.truncate(envs);
+        self.ys.truncate(ys);
+        self.ys.extend(slots);
+        let env = Env { ce: self.e, cp, cut_barrier: self.b0, ys, size: self.ys.len() - ys, catch };
+        self.envs.push(env);
+        self.set_env(envs);
+    }
+
+    fn allocate(&mut self, size: usize) {
+        self.push_env(self.cp.clone(), std::iter::repeat_n(Term::Int(0), size), false);
+    }
+
+    fn deallocate(&mut self) {
+        let env = &self.envs[self.e];
+        self.cp = env.cp.clone();
+        self.set_env(env.ce);
+    }
+
+    fn push_choicepoint(&mut self, alternative: Alternative, trail_len: usize, p: Ptr) {
+        let (envs, ys) = self.stack_top();
+        self.choicepoints.push(ChoicePoint {
+            alternative,
+            trail_len,
+            horizon: self.trail.horizon(),
+            p,
+            cp: self.cp.clone(),
+            e: self.e,
+            envs,
+            ys,
+        });
+    }
+
+    // Calls what a goal of a body calls, going on at `resume` afterwards.
+    // The arguments are in the X registers.
+    fn call(&mut self, procedure: &Procedure, resume: Ptr) -> Result<bool, Term> {
+        let arity = procedure.arity;
+        match &procedure.target {
+            Target::Predicate(resolved) => {
+                let generation = self.database.generation();
+                let clauses = match &*resolved.borrow() {
+                    Some(r) if r.generation == generation => Some(self.candidates(r, &procedure.module, arity)),
+                    _ => None,
+                };
+                let clauses = match clauses {
+                    Some(clauses) => clauses,
+                    None => match self.resolve(&(procedure.name.clone(), arity), &procedure.module)? {
+                        Some(r) => {
+                            let clauses = self.candidates(&r, &procedure.module, arity);
+                            *resolved.borrow_mut() = Some(r);
+                            clauses
+                        }
+                        None => return Ok(false),
+                    },
+                };
+                self.cp = resume;
+                Ok(self.try_clauses(clauses, 0, arity))
+            }
+            Target::Builtin(builtin) => {
+                self.p = resume;
+                if !Rc::ptr_eq(&self.module, &procedure.module) {
+                    self.module = procedure.module.clone();
+                }
+                // a builtin that loads a file runs directives with
+                // registers of their own
+                let registers = std::mem::take(&mut self.registers);
+                let result = builtin(self, &registers[..arity]);
+                self.registers = registers;
+                result.map_err(|formal| {
+                    let (name, arity) = builtins::reported_as(&procedure.name, arity);
+                    self.error(formal, name, arity)
+                })
+            }
+            Target::Call => {
+                self.p = resume;
+                let goal = self.registers[0].clone();
+                self.check_body(&goal)?;
+                // a cut inside the called goal is local to it
+                let cut_barrier = self.choicepoints.len();
+                self.call_goal(goal, cut_barrier, procedure.module.clone())
+            }
+            Target::Catch => {
+                self.p = resume;
+                self.module = procedure.module.clone();
+                let args = self.registers[..3].to_vec();
+                self.catch(args[0].clone(), args[1].clone(), args[2].clone());
+                Ok(true)
+            }
+            Target::Throw => {
+                let ball = self.registers[0].clone();
+                Err(self.throw(&ball))
+            }
+            Target::Qualified => {
+                self.p = resume;
+                let args = self.registers[..2].to_vec();
+                let cut_barrier = self.choicepoints.len();
+                self.call_goal(Term::compound(":", args), cut_barrier, procedure.module.clone())
+            }
+        }
+    }
+
+    // the clauses a call of a resolved predicate tries, its meta arguments
+    // qualified first
+    fn candidates(&mut self, resolved: &Resolved, caller: &Rc<str>, arity: usize) -> Clauses {
+        if let Some(specs) = &resolved.meta {
+            let args = module::qualify_meta_args(&self.registers[..arity], specs, caller);
+            self.registers[..arity].clone_from_slice(&args);
+        }
+        resolved.predicate.borrow_mut().candidates(&self.registers[..arity])
+    }
+
+    fn throw(&mut self, ball: &Term) -> Term {
+        if let Term::Var(_) = ball.deref() {
+            return self.error(error::instantiation_error(), "throw", 1);
+        }
+        ball.rename(&mut HashMap::new(), &mut self.id_factory)
+    }
+
+    // Calls a goal given as a term, in `module`, going on at `p` afterwards.
+    // A cut in it goes back to `cut_barrier`; control constructs are
+    // compiled for the call.
+    fn call_goal(&mut self, goal: Term, cut_barrier: usize, module: Rc<str>) -> Result<bool, Term> {
+        // a variable goal runs as call/1, so a cut it is bound to stays local
+        if let (Term::Var(_), goal @ (Term::Atom(_) | Term::Compound(_))) = (&goal, goal.deref()) {
+            self.check_body(&goal)?;
+            return self.call_goal(goal, self.choicepoints.len(), module);
+        }
+        let goal = goal.deref();
+        let (name, arity) = match &goal {
+            Term::Var(_) => return Err(self.context_free(error::instantiation_error())),
+            goal => match goal.name_arity() {
+                Some(key) => key,
+                None => return Err(self.context_free(error::type_error("callable", goal.clone()))),
+            },
+        };
+        let args: &[Term] = match &goal {
+            Term::Compound(c) => &c.args,
+            _ => &[],
+        };
+        match (&*name, arity) {
+            ("true", 0) => Ok(true),
+            ("fail", 0) | ("false", 0) => Ok(false),
+            ("!", 0) => {
+                self.cut(cut_barrier);
+                Ok(true)
+            }
+            (",", 2) | (";", 2) | ("->", 2) | ("*->", 2) | ("\\+", 1) => {
+                let code = Rc::new(compile_goal(&goal, &module));
+                self.b0 = cut_barrier;
+                self.enter(code, 0);
+                Ok(true)
+            }
+            ("call", 1) => {
+                self.check_body(&args[0])?;
+                self.call_goal(args[0].clone(), self.choicepoints.len(), module)
+            }
+            (":", 2) => {
+                let module = match args[0].deref() {
+                    Term::Var(_) => return Err(self.context_free(error::instantiation_error())),
+                    Term::Atom(module) => module,
+                    culprit => return Err(self.context_free(error::type_error("module", culprit))),
+                };
+                self.call_goal(args[1].clone(), cut_barrier, module)
+            }
+            ("catch", 3) => {
+                self.module = module;
+                self.catch(args[0].clone(), args[1].clone(), args[2].clone());
+                Ok(true)
+            }
+            ("throw", 1) => Err(self.throw(&args[0])),
+            _ => match builtins::lookup(&name, arity) {
+                Some(builtin) => {
+                    self.module = module;
+                    builtin(self, args).map_err(|formal| {
+                        let (name, arity) = builtins::reported_as(&name, arity);
+                        self.error(formal, name, arity)
+                    })
+                }
+                None => {
+                    let resolved = match self.resolve(&(name, arity), &module)? {
+                        Some(resolved) => resolved,
+                        None => return Ok(false),
+                    };
+                    if self.registers.len() < arity {
+                        self.registers.resize(arity, Term::Int(0));
+                    }
+                    self.registers[..arity].clone_from_slice(args);
+                    let clauses = self.candidates(&resolved, &module, arity);
+                    self.cp = self.p.clone();
+                    Ok(self.try_clauses(clauses, 0, arity))
+                }
+            },
+        }
+    }
+
+    // calls code that returns to `p`
+    fn enter(&mut self, code: Rc<Code>, pc: usize) {
+        if self.registers.len() < code.registers {
+            self.registers.resize(code.registers, Term::Int(0));
+        }
+        self.cp = self.p.clone();
+        self.p = Ptr { code, pc };
+    }
+
+    // Predicates that were never defined nor declared dynamic are handled by
+    // the `unknown` flag, by default raising an existence error. A bundled
+    // library that exports the predicate is loaded and imported into `user`
+    // first. Arguments the predicate declares as meta arguments are qualified
+    // with the module of the caller.
+    fn resolve(&mut self, key: &Key, caller: &Rc<str>) -> Result<Option<Resolved>, Term> {
+        let mut defining = self.visible(caller, key);
+        if defining.is_none() {
+            if let Some(library) = library::exporting(&key.0, key.1) {
+                let library = self.load_library(library);
+                self.modules.entry(Rc::from(USER)).or_default().imports.insert(key.clone(), library);
+                self.database.touch();
+                defining = self.visible(caller, key);
+            }
+        }
+        let module = match defining {
+            Some(module) => module,
+            None => {
+                let procedure = match &**caller {
+                    USER => error::indicator(&key.0, key.1),
+                    _ => module::qualify(&error::indicator(&key.0, key.1), caller),
+                };
+                match self.flags.unknown {
+                    Unknown::Error => {
+                        return Err(self.context_free(error::existence_error("procedure", procedure)));
+                    }
+                    Unknown::Warning => {
+                        let name = write::format_term(&procedure, &self.ops, true);
+                        self.warn(&format!("Unknown procedure: {}", name));
+                    }
+                    Unknown::Fail => {}
+                }
+                return Ok(None);
+            }
+        };
+        let meta = match module != *caller {
+            true => self.modules.get(&module).and_then(|m| m.meta.get(key)).cloned(),
+            false => None,
+        };
+        let predicate = self.database.predicate(&module, key).expect("a visible predicate");
+        Ok(Some(Resolved { generation: self.database.generation(), predicate, meta }))
+    }
+
+    // Tries the clauses of a snapshot from `next` on against the arguments
+    // in the X registers: try, retry and trust of the WAM. The first whose
+    // head unifies runs its body, with a choice point to retry the ones
+    // after it if there are any.
+    fn try_clauses(&mut self, clauses: Clauses, mut next: usize, arity: usize) -> bool {
+        let horizon = self.trail.horizon();
+        while next < clauses.len() {
+            // only a clause with others after it leaves a choice point to
+            // return to
+            let last = next + 1 == clauses.len();
+            let trail_len = match last {
+                true => self.trail.len(),
+                false => self.trail.mark(&self.id_factory),
+            };
+            let code = clauses[next].code.clone();
+            if self.registers.len() < code.registers {
+                self.registers.resize(code.registers, Term::Int(0));
+            }
+            if self.unify_head(&code) {
+                self.b0 = self.choicepoints.len();
+                if !last {
+                    let args = self.registers[..arity].to_vec();
+                    let alternative = Alternative::Clauses { clauses, next: next + 1, args };
+                    self.push_choicepoint(alternative, trail_len, self.cp.clone());
+                }
+                let neck = code.neck;
+                self.p = Ptr { code, pc: neck };
+                return true;
+            }
+            // a head that does not match leaves no mark behind
+            undo_trail(&mut self.trail, trail_len);
+            self.trail.prune(trail_len, horizon);
+            next += 1;
+        }
+        false
+    }
+
+    // Runs the head code of a clause against the argument registers. The
+    // bindings it makes are on the trail for the caller to undo on failure.
+    fn unify_head(&mut self, code: &Code) -> bool {
+        let regs = &mut self.registers;
+        let trail = &mut self.trail;
+        let ids = &mut self.id_factory;
+        let spare = &mut self.spare;
+        let mut mode: Option<Mode> = None;
+        for instruction in &code.instructions[..code.neck] {
+            let ok = match instruction {
+                Instruction::GetVariable { reg, arg } => {
+                    regs[x(*reg)] = regs[*arg].clone();
+                    true
+                }
+                Instruction::GetValue { reg, arg } => unify(&regs[x(*reg)], &regs[*arg], trail),
+                Instruction::GetConstant { constant, arg } => unify_constant(&regs[*arg], constant, trail),
+                Instruction::GetStructure { name, arity, arg } => match regs[*arg].deref() {
+                    Term::Compound(c) if c.args.len() == *arity && (Rc::ptr_eq(&c.name, name) || c.name == *name) => {
+                        mode = Some(Mode::Read { args: c, next: 0 });
+                        true
+                    }
+                    Term::Var(target) => {
+                        let args = std::mem::take(spare);
+                        mode = Some(Mode::Write { name: name.clone(), arity: *arity, args, target });
+                        true
+                    }
+                    _ => false,
+                },
+                Instruction::GetTemplate { template, arg, bindings } => {
+                    let term = instantiate(template, bindings, |reg| regs[x(reg)].clone(), ids);
+                    unify(&regs[*arg], &term, trail)
+                }
+                Instruction::UnifyVariable(reg) => {
+                    regs[x(*reg)] = match &mut mode {
+                        Some(Mode::Read { args, next }) => next_arg(args, next),
+                        _ => Term::var(ids),
+                    };
+                    push_arg(&mut mode, &regs[x(*reg)], spare, trail)
+                }
+                Instruction::UnifyValue(reg) => match &mut mode {
+                    Some(Mode::Read { args, next }) => unify(&regs[x(*reg)], &next_arg(args, next), trail),
+                    _ => push_arg(&mut mode, &regs[x(*reg)], spare, trail),
+                },
+                Instruction::UnifyConstant(constant) => match &mut mode {
+                    Some(Mode::Read { args, next }) => unify_constant(&next_arg(args, next), constant, trail),
+                    _ => push_arg(&mut mode, constant, spare, trail),
+                },
+                Instruction::UnifyVoid => match &mut mode {
+                    Some(Mode::Read { next, .. }) => {
+                        *next += 1;
+                        true
+                    }
+                    _ => push_arg(&mut mode, &Term::var(ids), spare, trail),
+                },
+                Instruction::PutVariable(reg) => {
+                    regs[x(*reg)] = Term::var(ids);
+                    true
+                }
+                _ => unreachable!("body code runs in execute"),
+            };
+            if !ok {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub(crate) fn backtrack(&mut self) -> bool {
+        while let Some(choicepoint) = self.choicepoints.pop() {
+            undo_trail(&mut self.trail, choicepoint.trail_len);
+            self.set_env(choicepoint.e);
+            self.cp = choicepoint.cp;
+            self.p = choicepoint.p;
+            match choicepoint.alternative {
+                Alternative::Clauses { clauses, next, args } => {
+                    self.registers[..args.len()].clone_from_slice(&args);
+                    if self.try_clauses(clauses, next, args.len()) {
+                        return true;
+                    }
+                }
+                Alternative::Code => return true,
+                Alternative::Unify { term, candidates } => {
+                    if self.unify_next(term, candidates) {
+                        return true;
+                    }
+                }
+                Alternative::Goal { goal, cut_barrier, module } => {
+                    self.push_goal_in(goal, cut_barrier, module);
+                    return true;
+                }
+                Alternative::Solutions { found, list, tail } => {
+                    if self.unify(&list, &Term::list(found, tail)) {
+                        return true;
+                    }
+                }
+                Alternative::Disabled => {}
+            }
+        }
+        false
+    }
+
+    // Unwinds to the innermost catch/3 that is still running its goal and
+    // whose catcher unifies with a copy of the ball, and goes on with its
+    // recovery goal. Without one the ball ends the query.
+    fn recover(&mut self, ball: Term) -> Result<(), Term> {
+        let ball = ball.resolve();
+        let mut e = self.e;
+        while let Some(env) = self.envs.get(e) {
+            let ce = env.ce;
+            if env.catch {
+                let cp = env.cp.clone();
+                let slots = self.ys[env.ys..env.ys + env.size].to_vec();
+                let (trail_len, height) = (height(&slots[2]), height(&slots[3]));
+                undo_trail(&mut self.trail, trail_len);
+                self.cut(height);
+                if self.unify(&slots[0], &ball) {
+                    let module = match &slots[4] {
+                        Term::Atom(module) => module.clone(),
+                        _ => unreachable!("the module of a catch/3"),
+                    };
+                    self.set_env(ce);
+                    self.p = cp;
+                    self.push_goal_in(slots[1].clone(), height, module);
+                    return Ok(());
+                }
+                undo_trail(&mut self.trail, trail_len);
+            }
+            e = ce;
+        }
+        Err(ball)
+    }
+
+    // Runs `goal` next, in a frame whose continuation is the one `p` had. A
+    // choice point that is never resumed keeps the trail the catcher undoes
+    // to safe from cuts inside the goal.
+    fn catch(&mut self, goal: Term, catcher: Term, recovery: Term) {
+        let height = self.choicepoints.len();
+        let trail_len = self.trail.mark(&self.id_factory);
+        self.push_choicepoint(Alternative::Disabled, trail_len, self.p.clone());
+        let slots = [catcher, recovery, Term::Int(trail_len as i64), Term::Int(height as i64), Term::Atom(self.module.clone())];
+        let cp = std::mem::replace(&mut self.p, Ptr { code: self.routines.catch.clone(), pc: 0 });
+        self.push_env(cp, slots, true);
+        self.push_goal(goal, height + 1);
+    }
+
+    // Sets the machine up to run `goal` to its end, then stop.
+    pub(crate) fn start(&mut self, goal: Term) {
+        self.set_env(NONE);
+        self.p = self.routines.stop();
+        self.cp = self.routines.stop();
+        self.push_goal(goal, 0);
+    }
+
+    // Puts the registers and stacks of the running query aside, for a
+    // directive to run on its own.
+    pub(crate) fn suspend(&mut self) -> Machine {
+        let machine = Machine {
+            p: self.p.clone(),
+            cp: self.cp.clone(),
+            e: self.e,
+            b0: self.b0,
+            envs: std::mem::take(&mut self.envs),
+            ys: std::mem::take(&mut self.ys),
+            choicepoints: std::mem::take(&mut self.choicepoints),
+            registers: std::mem::take(&mut self.registers),
+        };
+        self.set_env(NONE);
+        machine
+    }
+
+    pub(crate) fn resume(&mut self, machine: Machine) {
+        self.p = machine.p;
+        self.cp = machine.cp;
+        self.b0 = machine.b0;
+        self.envs = machine.envs;
+        self.ys = machine.ys;
+        self.choicepoints = machine.choicepoints;
+        self.registers = machine.registers;
+        self.set_env(machine.e);
+    }
+
+    // forgets the rest of a query an error ended
+    pub(crate) fn reset(&mut self) {
+        self.choicepoints.clear();
+        self.envs.clear();
+        self.ys.clear();
+        self.set_env(NONE);
+    }
+
+    pub(crate) fn unify(&mut self, lhs: &Term, rhs: &Term) -> bool {
+        unify(lhs, rhs, &mut self.trail)
+    }
+
+    pub(crate) fn undo_trail(&mut self, len: usize) {
+        undo_trail(&mut self.trail, len);
+    }
+
+    // nondeterministically unifies `term` with each of `candidates` in turn
+    pub(crate) fn unify_any(&mut self, term: &Term, mut candidates: Vec<Term>) -> bool {
+        candidates.reverse();
+        self.unify_next(term.clone(), candidates)
+    }
+
+    fn unify_next(&mut self, term: Term, mut candidates: Vec<Term>) -> bool {
+        let horizon = self.trail.horizon();
+        while let Some(candidate) = candidates.pop() {
+            let trail_len = self.trail.mark(&self.id_factory);
+            if self.unify(&term, &candidate) {
+                if !candidates.is_empty() {
+                    self.push_choicepoint(Alternative::Unify { term, candidates }, trail_len, self.p.clone());
+                }
+                return true;
+            }
+            undo_trail(&mut self.trail, trail_len);
+            self.trail.prune(trail_len, horizon);
+        }
+        false
+    }
+
+    // Runs `goal` to exhaustion, then unifies `list` with a copy of `template`
+    // for every solution followed by `tail`. The copies gather in a choice
+    // point below the goal, which a cut inside the goal cannot remove.
+    pub(crate) fn find_all(&mut self, template: Term, goal: Term, list: Term, tail: Term) {
+        let height = self.choicepoints.len();
+        let trail_len = self.trail.mark(&self.id_factory);
+        self.push_choicepoint(Alternative::Solutions { found: Vec::new(), list, tail }, trail_len, self.p.clone());
+        let cp = std::mem::replace(&mut self.p, Ptr { code: self.routines.collect.clone(), pc: 0 });
+        self.push_env(cp, [template, Term::Int(height as i64)], false);
+        self.push_goal(goal, height + 1);
+    }
+
+    // the number of environments the current goal returns through
+    pub(crate) fn continuation_depth(&self) -> usize {
+        let mut depth = 0;
+        let mut e = self.e;
+        while let Some(env) = self.envs.get(e) {
+            depth += 1;
+            e = env.ce;
+        }
+        depth
+    }
+
+    // Removes the choice points from `height` on. Bindings need recording
+    // from then on only for the choice point left on top.
+    fn cut(&mut self, height: usize) {
+        self.choicepoints.truncate(height);
+        let (trail_len, horizon) = match self.choicepoints.last() {
+            Some(choicepoint) => (choicepoint.trail_len, choicepoint.horizon),
+            None => self.floor,
+        };
+        self.trail.prune(trail_len, horizon);
+    }
+
+    // a choice point that runs `goal` when resumed
+    pub(crate) fn push_alternative(&mut self, goal: Term, cut_barrier: usize) {
+        let alternative = Alternative::Goal { goal, cut_barrier, module: self.module.clone() };
+        let trail_len = self.trail.mark(&self.id_factory);
+        self.push_choicepoint(alternative, trail_len, self.p.clone());
+    }
+
+    // runs `goal` before going on where `p` is
+    pub(crate) fn push_goal(&mut self, goal: Term, cut_barrier: usize) {
+        self.push_goal_in(goal, cut_barrier, self.module.clone());
+    }
+
+    fn push_goal_in(&mut self, goal: Term, cut_barrier: usize, module: Rc<str>) {
+        let cp = std::mem::replace(&mut self.p, Ptr { code: self.routines.meta.clone(), pc: 0 });
+        self.push_env(cp, [goal, Term::Int(cut_barrier as i64), Term::Atom(module)], false);
+    }
+}
@@ -1,15 +1,48 @@
 #[cfg(test)]
 mod tests;
-
-use super::parser::{Constant, Fact, Rule, Variable};
-use std::{collections::HashMap, fmt::Debug};
-
-struct IdFactory {
-    id_counter: u32,
+mod builtins;
+mod code;
+mod database;
+mod dict;
+pub mod error;
+mod flags;
+mod index;
+mod json;
+mod library;
+mod loader;
+mod machine;
+mod module;
+mod random;
+mod rational;
+mod stream;
+pub mod term;
+mod write;
+
+use super::parser::{Constant, Dict, Fact, Number, Operators, Parser, Rule, Str, Variable};
+use super::tokenizer::Tokenizer;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    io::{Read, Write},
+    rc::Rc,
+};
+use database::{ClauseRecord, Database, Key, Source};
+use error::PrologError;
+use flags::Flags;
+use library::Library;
+use machine::{ChoicePoint, Env, Ptr, Routines};
+use module::{Module, USER};
+use random::Rng;
+use rational::{Exact, Rational};
+use stream::{Port, Stream, Streams};
+use term::{undo_trail, Term, Trail};
+
+pub struct IdFactory {
+    id_counter: u64,
 }
 
 impl IdFactory {
-    pub fn id(&mut self) -> u32 {
+    pub fn id(&mut self) -> u64 {
         let id = self.id_counter;
         self.id_counter += 1;
         id
@@ -20,6 +53,10 @@ pub trait Clause: Debug + Unify {
     fn copy(&self) -> Box<dyn Clause>;
 
     fn get_variable_names(&self, _: &mut Vec<String>) {}
+
+    // converts the parsed clause into a runtime term; `variables` maps every
+    // named variable to the term that stands for it
+    fn to_term(&self, variables: &mut HashMap<String, Term>, id_factory: &mut IdFactory) -> Term;
 }
 
 pub trait Unify {
@@ -57,6 +94,16 @@ impl Clause for Variable {
             names.push(self.0.clone());
         }
     }
+
+    fn to_term(&self, variables: &mut HashMap<String, Term>, id_factory: &mut IdFactory) -> Term {
+        if self.0 == "_" {
+            return Term::var(id_factory);
+        }
+        variables
+            .entry(self.0.clone())
+            .or_insert_with(|| Term::var(id_factory))
+            .clone()
+    }
 }
 
 impl Unify for Variable {
@@ -67,7 +114,7 @@ impl Unify for Variable {
     ) -> Option<Box<dyn Clause>> {
         let name = rhs.get_name();
         // rhs is Variable
-        if let None = name {
+        if name.is_none() {
             let mut varname_singleton: Vec<String> = Vec::new();
             rhs.get_variable_names(&mut varname_singleton);
             if variable_mapping.contains_key(&self.0) {
@@ -82,14 +129,14 @@ impl Unify for Variable {
         }
 
         // to do: fix rhs.get_args(), will always return None
-        if let Some(_) = rhs.get_args() {
+        if rhs.get_args().is_some() {
             variable_mapping.insert(self.0.clone(), rhs.copy());
             return Some(rhs.copy());
         }
 
         // rhs is constant
         variable_mapping.insert(self.0.clone(), rhs.copy());
-        return Some(rhs.copy());
+        Some(rhs.copy())
     }
 
     fn apply_mapping(
@@ -107,6 +154,10 @@ impl Clause for Constant {
     fn copy(&self) -> Box<dyn Clause> {
         Box::new(Constant(self.0.clone()))
     }
+
+    fn to_term(&self, _variables: &mut HashMap<String, Term>, _id_factory: &mut IdFactory) -> Term {
+        Term::atom(&self.0)
+    }
 }
 
 impl Unify for Constant {
@@ -120,9 +171,9 @@ impl Unify for Constant {
         _variable_mapping: &mut HashMap<String, Box<dyn Clause>>,
     ) -> Option<Box<dyn Clause>> {
         let args = rhs.get_args();
-        if let None = args {
+        if args.is_none() {
             let rname = rhs.get_name();
-            if self.get_name() != rname && rname != None {
+            if self.get_name() != rname && rname.is_some() {
                 return None;
             }
             return Some(Box::new(Constant(self.0.clone())));
@@ -138,6 +189,154 @@ impl Unify for Constant {
     }
 }
 
+impl Clause for Str {
+    fn copy(&self) -> Box<dyn Clause> {
+        Box::new(Str(self.0.clone()))
+    }
+
+    fn to_term(&self, _variables: &mut HashMap<String, Term>, _id_factory: &mut IdFactory) -> Term {
+        Term::string(&self.0)
+    }
+}
+
+// Strings and dicts have no names to match on, so they unify with the
+// right-hand side when the terms they stand for do.
+fn unify_terms(lhs: &dyn Clause, rhs: &dyn Clause) -> bool {
+    let mut id_factory = IdFactory { id_counter: 0 };
+    let lhs = lhs.to_term(&mut HashMap::new(), &mut id_factory);
+    let rhs = rhs.to_term(&mut HashMap::new(), &mut id_factory);
+    term::unify(&lhs, &rhs, &mut Trail::default())
+}
+
+impl Unify for Str {
+    fn unify(
+        &self,
+        rhs: &dyn Clause,
+        _variable_mapping: &mut HashMap<String, Box<dyn Clause>>,
+    ) -> Option<Box<dyn Clause>> {
+        if !unify_terms(self, rhs) {
+            return None;
+        }
+        Some(self.copy())
+    }
+
+    fn apply_mapping(
+        &self,
+        _variable_mapping: &HashMap<String, Box<dyn Clause>>,
+    ) -> Box<dyn Clause> {
+        self.copy()
+    }
+}
+
+impl Clause for Dict {
+    fn copy(&self) -> Box<dyn Clause> {
+        Box::new(Dict {
+            tag: self.tag.copy(),
+            pairs: self.pairs.iter().map(|(key, value)| (key.copy(), value.copy())).collect(),
+        })
+    }
+
+    fn get_variable_names(&self, names: &mut Vec<String>) {
+        self.tag.get_variable_names(names);
+        for (_, value) in &self.pairs {
+            value.get_variable_names(names);
+        }
+    }
+
+    // the parser has already rejected duplicate keys
+    fn to_term(&self, variables: &mut HashMap<String, Term>, id_factory: &mut IdFactory) -> Term {
+        let tag = self.tag.to_term(variables, id_factory);
+        let mut dict = dict::Dict::new(tag);
+        for (key, value) in &self.pairs {
+            if let Some(key) = dict::Key::from_term(&key.to_term(variables, id_factory)) {
+                dict = dict.put(key, value.to_term(variables, id_factory));
+            }
+        }
+        Term::Dict(Rc::new(dict))
+    }
+}
+
+impl Unify for Dict {
+    fn unify(
+        &self,
+        rhs: &dyn Clause,
+        _variable_mapping: &mut HashMap<String, Box<dyn Clause>>,
+    ) -> Option<Box<dyn Clause>> {
+        if !unify_terms(self, rhs) {
+            return None;
+        }
+        Some(self.copy())
+    }
+
+    fn apply_mapping(
+        &self,
+        variable_mapping: &HashMap<String, Box<dyn Clause>>,
+    ) -> Box<dyn Clause> {
+        Box::new(Dict {
+            tag: self.tag.apply_mapping(variable_mapping),
+            pairs: self
+                .pairs
+                .iter()
+                .map(|(key, value)| (key.copy(), value.apply_mapping(variable_mapping)))
+                .collect(),
+        })
+    }
+}
+
+impl Clause for Number {
+    fn copy(&self) -> Box<dyn Clause> {
+        Box::new(match self {
+            Number::Integer(n) => Number::Integer(*n),
+            Number::Rational(n, d) => Number::Rational(*n, *d),
+            Number::Float(n) => Number::Float(*n),
+        })
+    }
+
+    fn to_term(&self, _variables: &mut HashMap<String, Term>, _id_factory: &mut IdFactory) -> Term {
+        match self {
+            Number::Integer(n) => Term::Int(*n),
+            Number::Rational(n, d) => match Rational::fraction(*n as i128, *d as i128) {
+                Some(Exact::Int(n)) => Term::Int(n),
+                Some(Exact::Rational(r)) => Term::Rational(r),
+                None => Term::Float(*n as f64 / *d as f64),
+            },
+            Number::Float(n) => Term::Float(*n),
+        }
+    }
+}
+
+impl Unify for Number {
+    fn get_name(&self) -> Option<String> {
+        match self {
+            Number::Integer(n) => Some(n.to_string()),
+            Number::Rational(n, d) => Some(format!("{}r{}", n, d)),
+            Number::Float(n) => Some(term::format_float(*n)),
+        }
+    }
+
+    fn unify(
+        &self,
+        rhs: &dyn Clause,
+        _variable_mapping: &mut HashMap<String, Box<dyn Clause>>,
+    ) -> Option<Box<dyn Clause>> {
+        if rhs.get_args().is_some() {
+            return None;
+        }
+        let rname = rhs.get_name();
+        if rname.is_some() && self.get_name() != rname {
+            return None;
+        }
+        Some(self.copy())
+    }
+
+    fn apply_mapping(
+        &self,
+        _variable_mapping: &HashMap<String, Box<dyn Clause>>,
+    ) -> Box<dyn Clause> {
+        self.copy()
+    }
+}
+
 impl Clause for Fact {
     fn copy(&self) -> Box<dyn Clause> {
         let mut args_copy = Vec::new();
@@ -155,6 +354,15 @@ impl Clause for Fact {
             arg.get_variable_names(names);
         }
     }
+
+    fn to_term(&self, variables: &mut HashMap<String, Term>, id_factory: &mut IdFactory) -> Term {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| arg.to_term(variables, id_factory))
+            .collect();
+        Term::compound(&self.name, args)
+    }
 }
 
 impl Unify for Fact {
@@ -170,15 +378,13 @@ impl Unify for Fact {
                     if self.args.len() != rargs.len() {
                         return None;
                     }
-                    let mut i = 0;
                     let mut new_args: Vec<Box<dyn Clause>> = Vec::new();
-                    for arg in self.args.iter() {
+                    for (i, arg) in self.args.iter().enumerate() {
                         let unified_arg = arg.unify(rargs[i].as_ref(), variable_mapping);
                         match unified_arg {
                             None => return None,
                             Some(u) => new_args.push(u),
                         };
-                        i += 1;
                     }
                     return Some(Box::new(Fact {
                         name: self.name.clone(),
@@ -209,7 +415,7 @@ impl Unify for Fact {
     ) -> Box<dyn Clause> {
         let mut new_args = Vec::new();
         for arg in self.args.iter() {
-            new_args.push(arg.apply_mapping(&variable_mapping));
+            new_args.push(arg.apply_mapping(variable_mapping));
         }
         Box::new(Fact {
             name: self.name.clone(),
@@ -237,6 +443,20 @@ impl Clause for Rule {
             clauses.get_variable_names(names);
         }
     }
+
+    fn to_term(&self, variables: &mut HashMap<String, Term>, id_factory: &mut IdFactory) -> Term {
+        let head = self.head.to_term(variables, id_factory);
+        let mut body = Term::atom("true");
+        for (i, goal) in self.body.iter().rev().enumerate() {
+            let goal = goal.to_term(variables, id_factory);
+            body = if i == 0 {
+                goal
+            } else {
+                Term::compound(",", vec![goal, body])
+            };
+        }
+        Term::compound(":-", vec![head, body])
+    }
 }
 
 impl Unify for Rule {
@@ -270,10 +490,10 @@ impl Unify for Rule {
         &self,
         variable_mapping: &HashMap<String, Box<dyn Clause>>,
     ) -> Box<dyn Clause> {
-        let mapped_head = self.head.apply_mapping(&variable_mapping);
+        let mapped_head = self.head.apply_mapping(variable_mapping);
         let mut mapped_body = Vec::new();
         for c in self.body.iter() {
-            mapped_body.push(c.apply_mapping(&variable_mapping));
+            mapped_body.push(c.apply_mapping(variable_mapping));
         }
         Box::new(Rule {
             head: mapped_head,
@@ -290,105 +510,395 @@ impl Unify for Rule {
     }
 }
 
-struct Ctx {
-    at_goal: usize,
-    goal: Box<dyn Clause>,
-    subgoals: Vec<u32>,
-    id: u32,
-    parent_id: u32,
+pub struct Runner {
+    goal: Term,
+    variables: Vec<(String, Term)>,
+    database: Database,
+    // the machine registers: the instruction to run next and where the
+    // running clause returns to, its environment and the base of its Y
+    // registers, and the choice point height of the call it answers
+    p: Ptr,
+    cp: Ptr,
+    e: usize,
+    y: usize,
+    b0: usize,
+    envs: Vec<Env>,
+    ys: Vec<Term>,
+    choicepoints: Vec<ChoicePoint>,
+    trail: Trail,
+    // the trail height and horizon a cut that leaves no choice point goes
+    // back to: those of the start of the query or of the directive being run
+    floor: (usize, u64),
+    // the X registers, which hold the arguments of a call
+    registers: Vec<Term>,
+    // room for the arguments of the next structure the machine builds
+    spare: Vec<Term>,
+    routines: Routines,
+    id_factory: IdFactory,
+    flags: Flags,
+    ops: Operators,
+    libraries: HashSet<&'static str>,
+    modules: HashMap<Rc<str>, Module>,
+    // the module of the goal being run, and the module that clauses being
+    // loaded go to
+    module: Rc<str>,
+    source_module: Rc<str>,
+    rng: Rng,
+    streams: Streams,
+    // the files consulted so far, and the files being loaded with the
+    // consulted file each belongs to
+    files: Vec<Rc<str>>,
+    loading: Vec<(Rc<str>, Rc<str>)>,
+    started: bool,
 }
 
-impl Ctx {
-    pub fn from_goal(goal: Box<dyn Clause>, id: u32, parent_id: u32) -> Ctx {
-        Ctx {
-            at_goal: 0,
+impl Runner {
+    pub fn from_input(goal: Box<dyn Clause>, clauses: Vec<Box<dyn Clause>>) -> Runner {
+        let mut id_factory = IdFactory { id_counter: 1 };
+        let mut variable_mapping = HashMap::new();
+        let goal = goal.to_term(&mut variable_mapping, &mut id_factory);
+        let mut variables: Vec<(String, Term)> = variable_mapping.into_iter().collect();
+        variables.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let routines = Routines::new();
+        let mut runner = Runner {
             goal,
-            subgoals: Vec::new(),
-            id,
-            parent_id,
+            variables,
+            database: Database::default(),
+            p: routines.stop(),
+            cp: routines.stop(),
+            e: machine::NONE,
+            y: 0,
+            b0: 0,
+            envs: Vec::new(),
+            ys: Vec::new(),
+            choicepoints: Vec::new(),
+            trail: Trail::default(),
+            floor: (0, 0),
+            registers: Vec::new(),
+            spare: Vec::new(),
+            routines,
+            id_factory,
+            flags: Flags::default(),
+            ops: Operators::default(),
+            libraries: HashSet::new(),
+            modules: HashMap::new(),
+            module: Rc::from(USER),
+            source_module: Rc::from(USER),
+            rng: Rng::default(),
+            streams: Streams::default(),
+            files: Vec::new(),
+            loading: Vec::new(),
+            started: false,
+        };
+        runner.modules.insert(Rc::from(USER), Module::default());
+        runner.modules.insert(Rc::from(module::SYSTEM), Module::default());
+        for clause in clauses {
+            let term = clause.to_term(&mut HashMap::new(), &mut runner.id_factory);
+            runner.load(term, None);
         }
+        // a program that starts with module/2 is used by the query
+        let module = std::mem::replace(&mut runner.source_module, Rc::from(USER));
+        runner.import(&module, None);
+        runner
     }
 
-    pub fn advance(
-        &mut self,
-        clauses: &Vec<Box<dyn Clause>>,
-        variable_mapping: &mut HashMap<String, Box<dyn Clause>>,
-        ctx_mapping: &mut HashMap<u32, Ctx>,
-        id_factory: &mut IdFactory
-    ) {
-        if self.at_goal == self.subgoals.len() {
-            if ctx_mapping.contains_key(&self.parent_id) {
-                ctx_mapping.get_mut(&self.parent_id).unwrap().advance(clauses, variable_mapping, ctx_mapping, id_factory);
+    // A runner for `query` over the clauses of `program`, both given as
+    // source text. A syntax error in either is returned rather than loaded
+    // around.
+    pub fn from_source(program: &str, query: &str) -> Result<Runner, PrologError> {
+        let syntax_error = |formal: Term| {
+            let ball = Term::compound("error", vec![formal, Term::var(&mut IdFactory { id_counter: 0 })]);
+            PrologError::from_ball(ball)
+        };
+        let parse = |text: &str, name: &str| match loader::parse(text, name) {
+            (_, errors) if !errors.is_empty() => Err(syntax_error(errors[0].1.clone())),
+            (clauses, _) => Ok(clauses),
+        };
+        let clauses = parse(program, "program")?;
+        let mut goal = parse(query, "query")?;
+        if goal.len() != 1 {
+            return Err(syntax_error(error::syntax_error("query: expected one goal")));
+        }
+        let clauses = clauses.into_iter().map(|(_, clause)| clause).collect();
+        Ok(Runner::from_input(goal.remove(0).1, clauses))
+    }
+
+    // Adds a program clause to the database, or runs it when it is a
+    // directive. A directive that fails or raises an error only produces a
+    // warning, as loading goes on with the next clause.
+    fn load(&mut self, term: Term, source: Option<Source>) {
+        if let Term::Compound(c) = &term {
+            if &*c.name == ":-" && c.args.len() == 1 {
+                let directive = c.args[0].clone();
+                let result = self.run_directive(directive.clone());
+                let place = source.map(|s| format!("{}:{}: ", s.file, s.line)).unwrap_or_default();
+                match result {
+                    Ok(true) => {}
+                    Ok(false) => self.warn(&format!("{}goal (directive) failed: {}", place, directive)),
+                    Err(ball) => self.warn(&format!("{}{} raised {}", place, directive, ball.resolve())),
+                }
+                return;
+            }
+        }
+        let module = self.source_module.clone();
+        let qualified = module::strip(&term, module).and_then(|(module, term)| {
+            let (head, body) = ClauseRecord::parts(&term);
+            let (module, head) = module::strip(&head, module)?;
+            // a clause for a builtin would never be called
+            if let Some((name, arity)) = head.name_arity().filter(|(name, arity)| builtins::is_builtin(name, *arity)) {
+                return Err(error::permission_error("modify", "static_procedure", error::indicator(&name, arity)));
             }
+            Ok((module, head, body))
+        });
+        match qualified {
+            Ok((module, head, body)) => {
+                if let Some(key) = head.name_arity() {
+                    self.database.add(&module, key, head, body, false, source);
+                }
+            }
+            Err(formal) => {
+                let place = source.map(|s| format!("{}:{}: ", s.file, s.line)).unwrap_or_default();
+                let ball = self.context_free(formal);
+                self.warn(&format!("{}{} raised {}", place, term, ball.resolve()));
+            }
+        }
+    }
+
+    // Runs a directive to its first solution, in the module being loaded. A
+    // query that is consulting a file gets its machine state back
+    // afterwards.
+    fn run_directive(&mut self, directive: Term) -> Result<bool, Term> {
+        let machine = self.suspend();
+        let module = std::mem::replace(&mut self.module, self.source_module.clone());
+        let trail_len = self.trail.mark(&self.id_factory);
+        let floor = std::mem::replace(&mut self.floor, (trail_len, self.trail.horizon()));
+        self.start(directive);
+        let result = self.run();
+        undo_trail(&mut self.trail, trail_len);
+        self.floor = floor;
+        self.resume(machine);
+        self.module = module;
+        result
+    }
+
+    // Loads a program file, first taking out whatever an earlier consult of
+    // the same file loaded. Its directives run as they are read. The clauses
+    // go to the module that loads the file, unless it declares a module of
+    // its own.
+    pub(crate) fn consult(&mut self, path: &str) -> Result<(), Term> {
+        let (clauses, errors) = loader::read(path)?;
+        let path: Rc<str> = Rc::from(path);
+        self.database.unload(&path);
+        if !self.files.contains(&path) {
+            self.files.push(path.clone());
         }
-        else {
+        let source_module = std::mem::replace(&mut self.source_module, self.module.clone());
+        self.loading.push((path.clone(), path));
+        self.load_clauses(clauses, errors);
+        self.loading.pop();
+        self.source_module = source_module;
+        Ok(())
+    }
+
+    // the module a consulted file declared
+    pub(crate) fn file_module(&self, path: &str) -> Option<Rc<str>> {
+        self.modules
+            .iter()
+            .find(|(_, module)| module.file.as_deref() == Some(path))
+            .map(|(name, _)| name.clone())
+    }
 
+    // Makes predicates of module `from` visible in the current module: the
+    // ones given, or everything it exports.
+    pub(crate) fn import(&mut self, from: &Rc<str>, keys: Option<Vec<Key>>) {
+        if *from == self.module {
+            return;
+        }
+        let keys = keys.unwrap_or_else(|| self.modules.get(from).map(|m| m.exports.clone()).unwrap_or_default());
+        let module = self.modules.entry(self.module.clone()).or_default();
+        for key in keys {
+            module.imports.insert(key, from.clone());
         }
+        self.database.touch();
     }
 
-    pub fn evaluate(
-        &mut self,
-        clauses: &Vec<Box<dyn Clause>>,
-        variable_mapping: &mut HashMap<String, Box<dyn Clause>>,
-        ctx_mapping: &mut HashMap<u32, Ctx>,
-        id_factory: &mut IdFactory,
-    ) {
-        for clause in clauses.iter() {
-            if let Some(matched_clause) = clause.unify(&*self.goal, variable_mapping) {
-                Runner::replace_variables(&matched_clause, variable_mapping);
-                let new_goal = matched_clause.apply_mapping(variable_mapping);
-                let subgoals = new_goal.subgoals();
-                if let Some(goals) = subgoals {
-                    for goal in goals.iter() {
-                        let id = id_factory.id();
-                        ctx_mapping.insert(id, Ctx {
-                            at_goal: 0,
-                            goal: goal.copy(),
-                            subgoals: Vec::new(),
-                            id: id_factory.id(),
-                            parent_id: self.id,
-                        });
-                        self.subgoals.push(id);
-                    }
-                }
-                self.advance(clauses, variable_mapping, ctx_mapping, id_factory);
+    // The module whose definition a goal in `module` calls: its own, an
+    // imported one, or else the one `user` sees.
+    pub(crate) fn visible(&self, module: &Rc<str>, key: &Key) -> Option<Rc<str>> {
+        if self.database.contains(module, key) {
+            return Some(module.clone());
+        }
+        let imported = self.modules.get(module).and_then(|m| m.imports.get(key));
+        if let Some(from) = imported.filter(|from| self.database.contains(from, key)) {
+            return Some(from.clone());
+        }
+        match &**module {
+            USER => None,
+            _ => self.visible(&Rc::from(USER), key),
+        }
+    }
+
+    // loads the clauses of another file as part of the file being loaded
+    pub(crate) fn include(&mut self, path: &str) -> Result<(), Term> {
+        let (clauses, errors) = loader::read(path)?;
+        let path: Rc<str> = Rc::from(path);
+        let owner = self.loading.last().map_or_else(|| path.clone(), |(owner, _)| owner.clone());
+        self.loading.push((owner, path));
+        self.load_clauses(clauses, errors);
+        self.loading.pop();
+        Ok(())
+    }
+
+    // loads the clauses of a file, warning about the clauses left out for a
+    // syntax error where they were
+    fn load_clauses(&mut self, clauses: loader::Lines, errors: Vec<(usize, Term)>) {
+        let mut errors = errors.into_iter().peekable();
+        for (line, clause) in clauses {
+            while let Some((_, error)) = errors.next_if(|(at, _)| *at < line) {
+                self.warn_syntax(error);
             }
+            let term = clause.to_term(&mut HashMap::new(), &mut self.id_factory);
+            let source = self.loading.last().map(|(owner, file)| Source {
+                owner: owner.clone(),
+                file: file.clone(),
+                line,
+            });
+            self.load(term, source);
+        }
+        for (_, error) in errors {
+            self.warn_syntax(error);
         }
     }
-}
 
-pub struct Runner {
-    goal: Box<dyn Clause>,
-    clauses: Vec<Box<dyn Clause>>,
-    variable_mapping: HashMap<String, Box<dyn Clause>>,
-    ctx_mapping: HashMap<u32, Ctx>,
-    id_factory: IdFactory,
-}
+    fn warn_syntax(&mut self, formal: Term) {
+        let ball = self.context_free(formal);
+        self.warn(&ball.resolve().to_string());
+    }
 
-impl Runner {
-    pub fn from_input(goal: Box<dyn Clause>, clauses: Vec<Box<dyn Clause>>) -> Runner {
-        Runner {
-            goal,
-            clauses,
-            variable_mapping: HashMap::new(),
-            ctx_mapping: HashMap::new(),
-            id_factory: IdFactory { id_counter: 1 },
+    // the file a file specification names, relative to the file being loaded
+    pub(crate) fn resolve_file(&self, spec: &Term) -> Result<String, Term> {
+        let spec = match spec.deref() {
+            Term::Var(_) => return Err(error::instantiation_error()),
+            Term::Atom(name) | Term::Str(name) => name,
+            culprit => return Err(error::domain_error("source_sink", culprit)),
+        };
+        Ok(loader::resolve(&spec, self.loading.last().map(|(_, file)| &**file)))
+    }
+
+    // Loads a bundled library into a module of its own name, which exports
+    // what the library lists. Returns that module.
+    pub(crate) fn load_library(&mut self, library: &Library) -> Rc<str> {
+        let name: Rc<str> = Rc::from(library.name);
+        if !self.libraries.insert(library.name) {
+            return name;
         }
+        let exports = library.exports.iter().map(|(export, arity)| (Rc::from(*export), *arity)).collect();
+        self.modules.insert(name.clone(), Module { exports, ..Module::default() });
+        self.database.touch();
+        let tokens = Tokenizer::from_str(library.source).parse().expect("bundled library tokenizes");
+        let clauses = Parser::from_tokens(tokens).parse().expect("bundled library parses");
+        let source_module = std::mem::replace(&mut self.source_module, name.clone());
+        for clause in clauses {
+            let term = clause.to_term(&mut HashMap::new(), &mut self.id_factory);
+            self.load(term, None);
+        }
+        self.source_module = source_module;
+        name
+    }
+
+    // Puts a reader or writer behind `user_input`, `user_output` or
+    // `user_error`, so an embedder can feed the program its input and collect
+    // its output. Directives in the program ran before any of these calls,
+    // on the process's own streams.
+    pub fn set_user_input(&mut self, reader: Box<dyn Read>) {
+        self.streams.replace(stream::USER_INPUT, Port::Input(stream::Input::new(reader)));
+    }
+
+    pub fn set_user_output(&mut self, writer: Box<dyn Write>) {
+        self.streams.replace(stream::USER_OUTPUT, Port::Output(writer));
+    }
+
+    pub fn set_user_error(&mut self, writer: Box<dyn Write>) {
+        self.streams.replace(stream::USER_ERROR, Port::Output(writer));
     }
 
-    fn replace_variables(clause: &Box<dyn Clause>, mapping: &mut HashMap<String, Box<dyn Clause>>) {
-        let mut variables = Vec::new();
-        clause.get_variable_names(&mut variables);
+    // Opens a text stream over `reader` or `writer` that the program reaches
+    // through `alias`.
+    pub fn add_input_stream(&mut self, alias: &str, reader: Box<dyn Read>) {
+        self.streams.add(Stream::input(Some(alias), reader));
+    }
+
+    pub fn add_output_stream(&mut self, alias: &str, writer: Box<dyn Write>) {
+        self.streams.add(Stream::output(Some(alias), writer));
+    }
+
+    fn warn(&mut self, message: &str) {
+        if let Some(writer) = self.streams.get_mut(stream::USER_ERROR).and_then(Stream::writer) {
+            let _ = writeln!(writer, "Warning: {}", message);
+        }
+    }
 
-        for variable in variables.iter() {
-            let mut delegated_var = variable.clone();
-            delegated_var.push_str("_");
-            mapping.insert(variable.clone(), Box::new(Variable(delegated_var)));
+    // Looks for the next answer to the goal, resuming from the last choice
+    // point on every call after the first. The answer maps the variables of
+    // the goal to their values; an error term that reached the top aborts the
+    // whole query.
+    pub fn next_solution(&mut self) -> Result<Option<HashMap<String, Term>>, PrologError> {
+        if !self.started {
+            self.started = true;
+            self.module = Rc::from(USER);
+            // an error undoes the bindings of the goal's variables
+            self.floor = (self.trail.mark(&self.id_factory), self.trail.horizon());
+            self.start(self.goal.clone());
+        } else if !self.backtrack() {
+            return Ok(None);
+        }
+        let result = self.run();
+        self.streams.flush_all();
+        match result {
+            Ok(true) => {}
+            Ok(false) => return Ok(None),
+            Err(ball) => {
+                self.reset();
+                undo_trail(&mut self.trail, 0);
+                return Err(PrologError::from_ball(ball));
+            }
         }
+        Ok(Some(
+            self.variables
+                .iter()
+                .map(|(name, value)| (name.clone(), value.resolve()))
+                .collect(),
+        ))
+    }
+
+    // wraps the formal part of an ISO error raised by a builtin into
+    // `error(Formal, context(Name/Arity, _))`
+    fn error(&mut self, formal: Term, name: &str, arity: usize) -> Term {
+        let context = Term::compound("context", vec![error::indicator(name, arity), Term::var(&mut self.id_factory)]);
+        Term::compound("error", vec![formal, context])
     }
 
-    fn run(&mut self) {
-        let mut ctx = Ctx::from_goal(self.goal.copy(), self.id_factory.id(), 0);
-        ctx.evaluate(&self.clauses, &mut self.variable_mapping, &mut self.ctx_mapping, &mut self.id_factory);
+    // `error(Formal, _)`, for errors that do not come from a builtin
+    fn context_free(&mut self, formal: Term) -> Term {
+        Term::compound("error", vec![formal, Term::var(&mut self.id_factory)])
+    }
+
+    // A called goal is a body as a whole: a part of its control constructs
+    // that could never run makes the goal itself not callable, before any
+    // of it runs.
+    fn check_body(&mut self, goal: &Term) -> Result<(), Term> {
+        let mut pending = vec![goal.deref()];
+        while let Some(part) = pending.pop() {
+            match &part {
+                Term::Var(_) | Term::Atom(_) => {}
+                Term::Compound(c) if matches!((&*c.name, c.args.len()), ("," | ";" | "->" | "*->", 2)) => {
+                    pending.extend(c.args.iter().map(Term::deref));
+                }
+                Term::Compound(_) => {}
+                _ => return Err(self.context_free(error::type_error("callable", goal.deref()))),
+            }
+        }
+        Ok(())
     }
 }
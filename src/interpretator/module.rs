@@ -0,0 +1,92 @@
+use std::{collections::HashMap, rc::Rc};
+
+use super::{
+    database::Key,
+    error::{instantiation_error, type_error},
+    term::Term,
+};
+
+// Goals run in a context module. A predicate a module does not define or
+// import comes from `user`, and the builtins, the `system` module, are seen
+// from every module.
+pub const USER: &str = "user";
+pub const SYSTEM: &str = "system";
+
+#[derive(Default)]
+pub struct Module {
+    // the file that declared the module with module/2
+    pub file: Option<Rc<str>>,
+    pub exports: Vec<Key>,
+    // the predicates taken from other modules, with the module each is from
+    pub imports: HashMap<Key, Rc<str>>,
+    // the meta_predicate/1 argument specifiers of the module's predicates
+    pub meta: HashMap<Key, Vec<Term>>,
+}
+
+// Takes the `Module:` qualifications off a term, returning the innermost
+// module, or `module` when there is none.
+pub fn strip(term: &Term, mut module: Rc<str>) -> Result<(Rc<str>, Term), Term> {
+    let mut term = term.deref();
+    loop {
+        let inner = match &term {
+            Term::Compound(c) if &*c.name == ":" && c.args.len() == 2 => {
+                module = match c.args[0].deref() {
+                    Term::Var(_) => return Err(instantiation_error()),
+                    Term::Atom(name) => name,
+                    culprit => return Err(type_error("module", culprit)),
+                };
+                c.args[1].deref()
+            }
+            _ => return Ok((module, term)),
+        };
+        term = inner;
+    }
+}
+
+pub fn qualify(term: &Term, module: &Rc<str>) -> Term {
+    match term.deref() {
+        qualified @ Term::Compound(_) if qualified.name_arity().is_some_and(|(name, arity)| &*name == ":" && arity == 2) => {
+            qualified
+        }
+        term => Term::compound(":", vec![Term::Atom(module.clone()), term]),
+    }
+}
+
+// Whether a meta_predicate/1 argument specifier is valid, and whether it marks
+// a goal or closure: an integer from 0 to 9 for the number of arguments
+// added, `^` for a bagof/3 goal, `//` for a grammar body or `:` for any term
+// that depends on the module.
+pub fn meta_argument(spec: &Term) -> Option<bool> {
+    match spec.deref() {
+        Term::Int(0..=9) => Some(true),
+        Term::Atom(name) => match &*name {
+            ":" | "^" | "//" => Some(true),
+            "?" | "+" | "-" | "*" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Qualifies the arguments a meta-predicate declaration marks with the module
+// of the caller, so the predicate calls them where they came from. The
+// existential variables of a `^` argument stay outside.
+pub fn qualify_meta_args(args: &[Term], specs: &[Term], caller: &Rc<str>) -> Vec<Term> {
+    args.iter()
+        .zip(specs)
+        .map(|(arg, spec)| match spec.deref() {
+            Term::Atom(name) if &*name == "^" => qualify_goal(arg, caller),
+            spec if meta_argument(&spec) == Some(true) => qualify(arg, caller),
+            _ => arg.clone(),
+        })
+        .collect()
+}
+
+fn qualify_goal(goal: &Term, caller: &Rc<str>) -> Term {
+    match goal.deref() {
+        Term::Compound(c) if &*c.name == "^" && c.args.len() == 2 => {
+            Term::compound("^", vec![c.args[0].clone(), qualify_goal(&c.args[1], caller)])
+        }
+        goal => qualify(&goal, caller),
+    }
+}
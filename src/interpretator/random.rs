@@ -0,0 +1,50 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// SplitMix64: small, fast and good enough for programs that want random
+// numbers, and it restarts the same sequence from the same seed.
+pub struct Rng {
+    state: u64,
+}
+
+impl Default for Rng {
+    // seeded from the clock; set_random(seed(N)) makes a run reproducible
+    fn default() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or(0);
+        Rng::seeded(nanos)
+    }
+}
+
+impl Rng {
+    pub fn seeded(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // uniform in [0, 1)
+    pub fn next_float(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // uniform in [low, high], which must not be empty
+    pub fn between(&mut self, low: i64, high: i64) -> i64 {
+        let span = (high as i128 - low as i128 + 1) as u128;
+        // rejects the top of the range that would favour small values
+        let zone = u64::MAX as u128 + 1 - (u64::MAX as u128 + 1) % span;
+        loop {
+            let value = self.next_u64() as u128;
+            if value < zone {
+                return (low as i128 + (value % span) as i128) as i64;
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::cmp::Ordering;
+
+// Exact fraction kept in lowest terms with a positive denominator. A
+// denominator of one never leaves this module: such values become integers.
+// Both parts are 64 bit, as integers are, so a result whose reduced parts do
+// not fit is an int_overflow rather than a bigger fraction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rational {
+    num: i64,
+    den: i64,
+}
+
+pub enum Exact {
+    Int(i64),
+    Rational(Rational),
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    a = a.abs();
+    b = b.abs();
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+impl Rational {
+    pub fn numerator(&self) -> i64 {
+        self.num
+    }
+
+    pub fn denominator(&self) -> i64 {
+        self.den
+    }
+
+    // normalises `num / den`, or `None` when `den` is zero or the reduced
+    // fraction does not fit in 64 bits
+    pub fn fraction(num: i128, den: i128) -> Option<Exact> {
+        if den == 0 {
+            return None;
+        }
+        let divisor = gcd(num, den);
+        let (mut num, mut den) = (num / divisor, den / divisor);
+        if den < 0 {
+            num = -num;
+            den = -den;
+        }
+        let num = i64::try_from(num).ok()?;
+        let den = i64::try_from(den).ok()?;
+        if den == 1 {
+            return Some(Exact::Int(num));
+        }
+        Some(Exact::Rational(Rational { num, den }))
+    }
+
+    pub fn from_int(n: i64) -> Rational {
+        Rational { num: n, den: 1 }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    pub fn add(self, rhs: Rational) -> Option<Exact> {
+        Rational::fraction(
+            self.num as i128 * rhs.den as i128 + rhs.num as i128 * self.den as i128,
+            self.den as i128 * rhs.den as i128,
+        )
+    }
+
+    pub fn sub(self, rhs: Rational) -> Option<Exact> {
+        self.add(Rational { num: rhs.num.checked_neg()?, den: rhs.den })
+    }
+
+    pub fn mul(self, rhs: Rational) -> Option<Exact> {
+        Rational::fraction(
+            self.num as i128 * rhs.num as i128,
+            self.den as i128 * rhs.den as i128,
+        )
+    }
+
+    pub fn div(self, rhs: Rational) -> Option<Exact> {
+        Rational::fraction(
+            self.num as i128 * rhs.den as i128,
+            self.den as i128 * rhs.num as i128,
+        )
+    }
+
+    pub fn pow(self, exp: u32) -> Option<Exact> {
+        Rational::fraction(
+            (self.num as i128).checked_pow(exp)?,
+            (self.den as i128).checked_pow(exp)?,
+        )
+    }
+
+    pub fn cmp(self, rhs: Rational) -> Ordering {
+        (self.num as i128 * rhs.den as i128).cmp(&(rhs.num as i128 * self.den as i128))
+    }
+
+    // the exact value of a finite float
+    pub fn from_f64(n: f64) -> Option<Exact> {
+        if !n.is_finite() {
+            return None;
+        }
+        if n == 0.0 {
+            return Some(Exact::Int(0));
+        }
+        let bits = n.to_bits();
+        let sign: i128 = if bits >> 63 == 0 { 1 } else { -1 };
+        let exponent = ((bits >> 52) & 0x7ff) as i32;
+        let mut mantissa = (bits & 0xf_ffff_ffff_ffff) as i128;
+        let mut exponent = if exponent == 0 {
+            -1074
+        } else {
+            mantissa |= 1 << 52;
+            exponent - 1075
+        };
+        while exponent < 0 && mantissa % 2 == 0 {
+            mantissa /= 2;
+            exponent += 1;
+        }
+        if exponent >= 0 {
+            let value = mantissa.checked_mul(1i128.checked_shl(exponent as u32)?)?;
+            return Rational::fraction(sign * value, 1);
+        }
+        if exponent < -126 {
+            return None;
+        }
+        Rational::fraction(sign * mantissa, 1i128 << -exponent)
+    }
+
+    // the simplest fraction that converts back to exactly `n`, found by
+    // walking the continued fraction expansion of `n`
+    pub fn rationalize(n: f64) -> Option<Exact> {
+        if !n.is_finite() {
+            return None;
+        }
+        let (mut p0, mut q0, mut p1, mut q1): (i128, i128, i128, i128) = (0, 1, 1, 0);
+        let mut x = n;
+        loop {
+            let a = x.floor();
+            if a.abs() >= i64::MAX as f64 {
+                return None;
+            }
+            let a = a as i128;
+            let (p2, q2) = (a * p1 + p0, a * q1 + q0);
+            if p2.abs() > i64::MAX as i128 || q2 > i64::MAX as i128 {
+                return None;
+            }
+            if p2 as f64 / q2 as f64 == n {
+                return Rational::fraction(p2, q2);
+            }
+            (p0, q0, p1, q1) = (p1, q1, p2, q2);
+            let fraction = x - a as f64;
+            if fraction == 0.0 {
+                return Rational::fraction(p2, q2);
+            }
+            x = 1.0 / fraction;
+        }
+    }
+}
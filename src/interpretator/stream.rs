@@ -0,0 +1,423 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+    rc::Rc,
+};
+
+use super::term::Term;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Read,
+    Write,
+    Append,
+}
+
+impl Mode {
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "read" => Some(Mode::Read),
+            "write" => Some(Mode::Write),
+            "append" => Some(Mode::Append),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Read => "read",
+            Mode::Write => "write",
+            Mode::Append => "append",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StreamType {
+    Text,
+    Binary,
+}
+
+impl StreamType {
+    pub fn name(self) -> &'static str {
+        match self {
+            StreamType::Text => "text",
+            StreamType::Binary => "binary",
+        }
+    }
+}
+
+// what reading past the end of an input stream does
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EofAction {
+    Error,
+    EofCode,
+    Reset,
+}
+
+impl EofAction {
+    pub fn from_name(name: &str) -> Option<EofAction> {
+        match name {
+            "error" => Some(EofAction::Error),
+            "eof_code" => Some(EofAction::EofCode),
+            "reset" => Some(EofAction::Reset),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EofAction::Error => "error",
+            EofAction::EofCode => "eof_code",
+            EofAction::Reset => "reset",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EndOfStream {
+    Not,
+    At,
+    Past,
+}
+
+impl EndOfStream {
+    pub fn name(self) -> &'static str {
+        match self {
+            EndOfStream::Not => "not",
+            EndOfStream::At => "at",
+            EndOfStream::Past => "past",
+        }
+    }
+}
+
+// The reading end of a stream. Bytes that were looked at but not consumed
+// wait in `pushback`, last byte first.
+pub struct Input {
+    reader: Box<dyn Read>,
+    pushback: Vec<u8>,
+    pub end: EndOfStream,
+}
+
+impl Input {
+    pub fn new(reader: Box<dyn Read>) -> Input {
+        Input { reader, pushback: Vec::new(), end: EndOfStream::Not }
+    }
+
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        if let Some(byte) = self.pushback.pop() {
+            return Ok(Some(byte));
+        }
+        let mut buf = [0u8];
+        loop {
+            match self.reader.read(&mut buf) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(buf[0])),
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    // records whether a read found the end, so `end_of_stream` can tell
+    fn seen<T>(&mut self, item: Option<T>, consumed: bool) -> Option<T> {
+        self.end = match (&item, consumed) {
+            (Some(_), _) => EndOfStream::Not,
+            (None, true) => EndOfStream::Past,
+            (None, false) if self.end == EndOfStream::Past => EndOfStream::Past,
+            (None, false) => EndOfStream::At,
+        };
+        item
+    }
+
+    pub fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let byte = self.next_byte()?;
+        Ok(self.seen(byte, true))
+    }
+
+    pub fn peek_byte(&mut self) -> io::Result<Option<u8>> {
+        let byte = self.next_byte()?;
+        self.pushback.extend(byte);
+        Ok(self.seen(byte, false))
+    }
+
+    // decodes one UTF-8 character
+    fn next_char(&mut self) -> io::Result<Option<(char, Vec<u8>)>> {
+        let first = match self.next_byte()? {
+            Some(byte) => byte,
+            None => return Ok(None),
+        };
+        let width = match first {
+            0x00..=0x7F => 1,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 0,
+        };
+        let mut bytes = vec![first];
+        while bytes.len() < width {
+            match self.next_byte()? {
+                Some(byte) => bytes.push(byte),
+                None => break,
+            }
+        }
+        match std::str::from_utf8(&bytes).ok().and_then(|text| text.chars().next()) {
+            Some(ch) => Ok(Some((ch, bytes))),
+            None => Err(io::Error::new(ErrorKind::InvalidData, "stream is not valid UTF-8")),
+        }
+    }
+
+    pub fn read_char(&mut self) -> io::Result<Option<char>> {
+        let ch = self.next_char()?.map(|(ch, _)| ch);
+        Ok(self.seen(ch, true))
+    }
+
+    pub fn peek_char(&mut self) -> io::Result<Option<char>> {
+        let ch = self.next_char()?.map(|(ch, bytes)| {
+            self.pushback.extend(bytes.iter().rev());
+            ch
+        });
+        Ok(self.seen(ch, false))
+    }
+}
+
+pub enum Port {
+    Input(Input),
+    Output(Box<dyn Write>),
+    // output kept in memory, as with_output_to/2 captures it
+    Buffer(Vec<u8>),
+}
+
+pub struct Stream {
+    pub alias: Option<Rc<str>>,
+    pub file_name: Option<Rc<str>>,
+    pub mode: Mode,
+    pub stream_type: StreamType,
+    pub eof_action: EofAction,
+    pub port: Port,
+    // the column the next character written goes to
+    pub column: usize,
+}
+
+impl Stream {
+    pub fn input(alias: Option<&str>, reader: Box<dyn Read>) -> Stream {
+        Stream {
+            alias: alias.map(Rc::from),
+            file_name: None,
+            mode: Mode::Read,
+            stream_type: StreamType::Text,
+            eof_action: EofAction::EofCode,
+            port: Port::Input(Input::new(reader)),
+            column: 0,
+        }
+    }
+
+    pub fn output(alias: Option<&str>, writer: Box<dyn Write>) -> Stream {
+        Stream {
+            alias: alias.map(Rc::from),
+            file_name: None,
+            mode: Mode::Append,
+            stream_type: StreamType::Text,
+            eof_action: EofAction::EofCode,
+            port: Port::Output(writer),
+            column: 0,
+        }
+    }
+
+    // opens a file; reads and writes go through a buffer
+    pub fn open(file_name: &str, mode: Mode, stream_type: StreamType, eof_action: EofAction) -> io::Result<Stream> {
+        let port = match mode {
+            Mode::Read => Port::Input(Input::new(Box::new(BufReader::new(std::fs::File::open(file_name)?)))),
+            Mode::Write => Port::Output(Box::new(BufWriter::new(std::fs::File::create(file_name)?))),
+            Mode::Append => {
+                let file = std::fs::OpenOptions::new().append(true).create(true).open(file_name)?;
+                Port::Output(Box::new(BufWriter::new(file)))
+            }
+        };
+        Ok(Stream {
+            alias: None,
+            file_name: Some(Rc::from(file_name)),
+            mode,
+            stream_type,
+            eof_action,
+            port,
+            column: 0,
+        })
+    }
+
+    pub fn is_input(&self) -> bool {
+        matches!(self.port, Port::Input(_))
+    }
+
+    pub fn reader(&mut self) -> Option<&mut Input> {
+        match &mut self.port {
+            Port::Input(input) => Some(input),
+            _ => None,
+        }
+    }
+
+    pub fn writer(&mut self) -> Option<&mut dyn Write> {
+        match &mut self.port {
+            Port::Output(writer) => Some(writer.as_mut()),
+            Port::Buffer(bytes) => Some(bytes),
+            Port::Input(_) => None,
+        }
+    }
+
+    // writes text, keeping track of the column
+    pub fn write_text(&mut self, text: &str) -> io::Result<()> {
+        if let Some(writer) = self.writer() {
+            writer.write_all(text.as_bytes())?;
+        }
+        self.column = match text.rfind('\n') {
+            Some(newline) => text[newline + 1..].chars().count(),
+            None => self.column + text.chars().count(),
+        };
+        Ok(())
+    }
+
+    pub fn end_of_stream(&self) -> Option<EndOfStream> {
+        match &self.port {
+            Port::Input(input) => Some(input.end),
+            _ => None,
+        }
+    }
+}
+
+// The open streams, by number. Streams 0, 1 and 2 are `user_input`,
+// `user_output` and `user_error`; they are never closed, and closing the
+// current input or output switches back to them. Numbers are never handed
+// out twice, so a handle to a closed stream stays closed.
+pub struct Streams {
+    table: BTreeMap<usize, Stream>,
+    next_id: usize,
+    pub input: usize,
+    pub output: usize,
+}
+
+pub const USER_INPUT: usize = 0;
+pub const USER_OUTPUT: usize = 1;
+pub const USER_ERROR: usize = 2;
+
+impl Default for Streams {
+    fn default() -> Self {
+        let table = BTreeMap::from([
+            (USER_INPUT, Stream::input(Some("user_input"), Box::new(io::stdin()))),
+            (USER_OUTPUT, Stream::output(Some("user_output"), Box::new(io::stdout()))),
+            (USER_ERROR, Stream::output(Some("user_error"), Box::new(io::stderr()))),
+        ]);
+        Streams { table, next_id: USER_ERROR + 1, input: USER_INPUT, output: USER_OUTPUT }
+    }
+}
+
+impl Streams {
+    // the term that stands for stream `id`
+    pub fn term(id: usize) -> Term {
+        Term::compound("$stream", vec![Term::Int(id as i64)])
+    }
+
+    pub fn id(term: &Term) -> Option<usize> {
+        match term.deref() {
+            Term::Compound(c) if &*c.name == "$stream" && c.args.len() == 1 => match c.args[0].deref() {
+                Term::Int(n) if n >= 0 => Some(n as usize),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Stream> {
+        self.table.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Stream> {
+        self.table.get_mut(&id)
+    }
+
+    pub fn by_alias(&self, alias: &str) -> Option<usize> {
+        self.table.iter().find(|(_, stream)| stream.alias.as_deref() == Some(alias)).map(|(id, _)| *id)
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.table.keys().copied()
+    }
+
+    // adds a stream, taking its alias away from any stream that had it
+    pub fn add(&mut self, stream: Stream) -> usize {
+        if let Some(owner) = stream.alias.as_deref().and_then(|alias| self.by_alias(alias)) {
+            if let Some(owner) = self.get_mut(owner) {
+                owner.alias = None;
+            }
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.table.insert(id, stream);
+        id
+    }
+
+    // puts a new reader or writer behind one of the standard streams
+    pub fn replace(&mut self, id: usize, port: Port) {
+        if let Some(stream) = self.get_mut(id) {
+            stream.port = port;
+        }
+    }
+
+    // flushes and drops a stream; the standard streams stay open
+    pub fn close(&mut self, id: usize) -> io::Result<()> {
+        if id <= USER_ERROR {
+            return self.flush(id);
+        }
+        let result = self.flush(id);
+        self.table.remove(&id);
+        if self.input == id {
+            self.input = USER_INPUT;
+        }
+        if self.output == id {
+            self.output = USER_OUTPUT;
+        }
+        result
+    }
+
+    pub fn flush(&mut self, id: usize) -> io::Result<()> {
+        match self.get_mut(id).and_then(Stream::writer) {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    // opens an output stream that keeps what is written to it in memory
+    pub fn add_buffer(&mut self) -> usize {
+        self.add(Stream {
+            alias: None,
+            file_name: None,
+            mode: Mode::Write,
+            stream_type: StreamType::Text,
+            eof_action: EofAction::EofCode,
+            port: Port::Buffer(Vec::new()),
+            column: 0,
+        })
+    }
+
+    // closes a buffer stream, handing back what was written to it
+    pub fn take_buffer(&mut self, id: usize) -> Option<Vec<u8>> {
+        if !matches!(self.get(id)?.port, Port::Buffer(_)) {
+            return None;
+        }
+        let stream = self.table.remove(&id)?;
+        if self.output == id {
+            self.output = USER_OUTPUT;
+        }
+        match stream.port {
+            Port::Buffer(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn flush_all(&mut self) {
+        for stream in self.table.values_mut() {
+            if let Some(writer) = stream.writer() {
+                let _ = writer.flush();
+            }
+        }
+    }
+}
@@ -0,0 +1,706 @@
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    rc::Rc,
+};
+
+use super::{dict::Dict, rational::Rational, IdFactory};
+
+pub struct Var {
+    pub id: u64,
+    value: RefCell<Option<Term>>,
+}
+
+pub struct Compound {
+    pub name: Rc<str>,
+    pub args: Args,
+}
+
+// The arguments of a compound term. Those of most terms, lists among them,
+// are few enough to live in the term rather than in an allocation of their
+// own.
+pub enum Args {
+    One([Term; 1]),
+    Two([Term; 2]),
+    Many(Vec<Term>),
+}
+
+impl Args {
+    // takes the arguments out of `args`, which keeps its capacity
+    pub fn take(args: &mut Vec<Term>) -> Args {
+        match args.len() {
+            1 => Args::One([args.pop().expect("one argument")]),
+            2 => {
+                let second = args.pop().expect("two arguments");
+                Args::Two([args.pop().expect("two arguments"), second])
+            }
+            _ => Args::Many(std::mem::take(args)),
+        }
+    }
+}
+
+impl Default for Args {
+    fn default() -> Args {
+        Args::Many(Vec::new())
+    }
+}
+
+impl From<Vec<Term>> for Args {
+    fn from(mut args: Vec<Term>) -> Args {
+        Args::take(&mut args)
+    }
+}
+
+impl std::ops::Deref for Args {
+    type Target = [Term];
+
+    fn deref(&self) -> &[Term] {
+        match self {
+            Args::One(args) => args,
+            Args::Two(args) => args,
+            Args::Many(args) => args,
+        }
+    }
+}
+
+// Runtime representation of terms. Unlike the parser's `Clause` trees these
+// are cheap to clone and variables are shared cells, so a binding made during
+// unification is seen by every term that refers to the variable.
+#[derive(Clone)]
+pub enum Term {
+    Var(Rc<Var>),
+    Atom(Rc<str>),
+    Int(i64),
+    Rational(Rational),
+    Float(f64),
+    Str(Rc<str>),
+    Compound(Rc<Compound>),
+    Dict(Rc<Dict>),
+}
+
+// The bindings to undo on backtracking. A variable created since the last
+// mark cannot be reached from any state the engine returns to, so binding it
+// is not recorded; a deterministic loop then leaves the trail as it was.
+#[derive(Default)]
+pub struct Trail {
+    vars: Vec<Rc<Var>>,
+    // the id of the first variable created after the last mark
+    horizon: u64,
+}
+
+impl Trail {
+    pub fn len(&self) -> usize {
+        self.vars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+
+    // the height to undo to later, from where on every binding of an
+    // existing variable is recorded
+    pub fn mark(&mut self, id_factory: &IdFactory) -> usize {
+        self.horizon = id_factory.id_counter;
+        self.vars.len()
+    }
+
+    pub fn horizon(&self) -> u64 {
+        self.horizon
+    }
+
+    // Goes back to a mark made earlier at height `len` once the marks after
+    // it are gone, as after a cut. Bindings recorded since then of variables
+    // that mark had not seen yet have nothing left to be undone for.
+    pub fn prune(&mut self, len: usize, horizon: u64) {
+        // at the same horizon every binding recorded since is of an older
+        // variable already
+        if horizon == self.horizon {
+            return;
+        }
+        self.horizon = horizon;
+        let mut kept = len;
+        for i in len..self.vars.len() {
+            if self.vars[i].id < horizon {
+                self.vars.swap(kept, i);
+                kept += 1;
+            }
+        }
+        self.vars.truncate(kept);
+    }
+}
+
+// Dropping a long list or a long chain of bound variables one level per
+// call would overflow the stack, so the terms that go away with them are
+// taken apart here instead.
+impl Drop for Compound {
+    fn drop(&mut self) {
+        if self.args.iter().any(goes_away) {
+            let (args, last) = split_last(std::mem::take(&mut self.args));
+            args.chain(last).for_each(drop_all);
+        }
+    }
+}
+
+impl Drop for Var {
+    fn drop(&mut self) {
+        if let Some(value) = self.value.get_mut().take() {
+            drop_all(value);
+        }
+    }
+}
+
+// the arguments but the last, and the last
+fn split_last(args: Args) -> (impl Iterator<Item = Term>, Option<Term>) {
+    let (first, rest, last) = match args {
+        Args::One([last]) => (None, Vec::new(), Some(last)),
+        Args::Two([first, last]) => (Some(first), Vec::new(), Some(last)),
+        Args::Many(mut args) => {
+            let last = args.pop();
+            (None, args, last)
+        }
+    };
+    (first.into_iter().chain(rest), last)
+}
+
+// a term that holds others and that nothing else refers to
+fn goes_away(term: &Term) -> bool {
+    match term {
+        Term::Compound(c) => Rc::strong_count(c) == 1,
+        Term::Var(v) => Rc::strong_count(v) == 1,
+        _ => false,
+    }
+}
+
+// Takes the terms that go away with `term` apart. The last argument of a
+// compound term is followed rather than set aside, so a list needs no room
+// for its cells.
+fn drop_all(term: Term) {
+    let mut terms = vec![];
+    let mut next = Some(term).filter(goes_away);
+    while let Some(term) = next.take().or_else(|| terms.pop()) {
+        next = match term {
+            Term::Compound(c) => Rc::try_unwrap(c).ok().and_then(|mut c| {
+                let (args, last) = split_last(std::mem::take(&mut c.args));
+                terms.extend(args.filter(goes_away));
+                last.filter(goes_away)
+            }),
+            Term::Var(v) => Rc::try_unwrap(v).ok().and_then(|v| v.value.take()).filter(goes_away),
+            _ => None,
+        };
+    }
+}
+
+impl Var {
+    pub fn value(&self) -> Option<Term> {
+        self.value.borrow().clone()
+    }
+
+    pub fn bind(&self, value: Term) {
+        *self.value.borrow_mut() = Some(value);
+    }
+
+    pub fn unbind(&self) {
+        *self.value.borrow_mut() = None;
+    }
+}
+
+impl Term {
+    pub fn var(id_factory: &mut IdFactory) -> Term {
+        Term::Var(Rc::new(Var {
+            id: id_factory.id(),
+            value: RefCell::new(None),
+        }))
+    }
+
+    pub fn atom(name: &str) -> Term {
+        Term::Atom(Rc::from(name))
+    }
+
+    pub fn string(text: &str) -> Term {
+        Term::Str(Rc::from(text))
+    }
+
+    pub fn compound(name: &str, args: Vec<Term>) -> Term {
+        Term::new_compound(Rc::from(name), args)
+    }
+
+    pub fn list(items: Vec<Term>, tail: Term) -> Term {
+        items
+            .into_iter()
+            .rev()
+            .fold(tail, |list, item| Term::compound(".", vec![item, list]))
+    }
+
+    pub fn from_list(items: Vec<Term>) -> Term {
+        Term::list(items, Term::atom("[]"))
+    }
+
+    // the elements of a proper list together with whatever ends it: `[]`
+    // for a proper list, a variable for a partial one
+    pub fn list_items(&self) -> (Vec<Term>, Term) {
+        let mut items = Vec::new();
+        let mut list = self.deref();
+        loop {
+            let next = match &list {
+                Term::Compound(c) if &*c.name == "." && c.args.len() == 2 => {
+                    items.push(c.args[0].clone());
+                    c.args[1].deref()
+                }
+                _ => return (items, list),
+            };
+            list = next;
+        }
+    }
+
+    pub fn is_list(&self) -> bool {
+        matches!(self.list_items().1, Term::Atom(name) if &*name == "[]")
+    }
+
+    pub fn new_compound(name: Rc<str>, args: impl Into<Args>) -> Term {
+        let args = args.into();
+        if args.is_empty() {
+            return Term::Atom(name);
+        }
+        Term::Compound(Rc::new(Compound { name, args }))
+    }
+
+    // follows variable bindings until an unbound variable or a non-variable
+    pub fn deref(&self) -> Term {
+        let mut term = match self {
+            Term::Var(v) => match &*v.value.borrow() {
+                Some(value) => value.clone(),
+                None => return self.clone(),
+            },
+            _ => return self.clone(),
+        };
+        while let Term::Var(v) = &term {
+            let next = match &*v.value.borrow() {
+                Some(value) => value.clone(),
+                None => break,
+            };
+            term = next;
+        }
+        term
+    }
+
+    pub fn name_arity(&self) -> Option<(Rc<str>, usize)> {
+        match self.deref() {
+            Term::Atom(name) => Some((name, 0)),
+            Term::Compound(c) => Some((c.name.clone(), c.args.len())),
+            _ => None,
+        }
+    }
+
+    pub fn is_callable(&self) -> bool {
+        self.name_arity().is_some()
+    }
+
+    // the distinct unbound variables of the term, depth-first and left to
+    // right
+    pub fn variables(&self) -> Vec<Term> {
+        let mut variables: Vec<Term> = Vec::new();
+        let mut pending = vec![self.clone()];
+        while let Some(term) = pending.pop() {
+            match term.deref() {
+                Term::Var(v) => {
+                    let seen = variables.iter().any(|known| match known {
+                        Term::Var(k) => Rc::ptr_eq(k, &v),
+                        _ => false,
+                    });
+                    if !seen {
+                        variables.push(Term::Var(v));
+                    }
+                }
+                Term::Compound(c) => pending.extend(c.args.iter().rev().cloned()),
+                Term::Dict(d) => {
+                    pending.extend(d.pairs().into_iter().rev().map(|(_, value)| value));
+                    pending.push(d.tag.clone());
+                }
+                _ => {}
+            }
+        }
+        variables
+    }
+
+    // copy of the term with every bound variable replaced by its value
+    pub fn resolve(&self) -> Term {
+        copy(self, &mut |v| Term::Var(v.clone()))
+    }
+
+    // copy of the term where every unbound variable is replaced by a fresh
+    // one; `mapping` keeps the correspondence so several terms can share it
+    pub fn rename(&self, mapping: &mut HashMap<u64, Term>, id_factory: &mut IdFactory) -> Term {
+        copy(self, &mut |v| mapping.entry(v.id).or_insert_with(|| Term::var(id_factory)).clone())
+    }
+}
+
+// what is left to do to copy a term: copy a subterm, or put together a
+// compound or dict from the copies of its arguments on top of the results
+enum Copy {
+    Term(Term),
+    Compound(Rc<str>, usize),
+    Dict(Rc<Dict>),
+}
+
+// Copies a term through its bindings, with `var` giving what an unbound
+// variable becomes. Like the Drop impls this keeps its own stack, so deep
+// terms such as long lists do not overflow the native one.
+fn copy(term: &Term, var: &mut dyn FnMut(&Rc<Var>) -> Term) -> Term {
+    let mut pending = vec![Copy::Term(term.clone())];
+    let mut results: Vec<Term> = Vec::new();
+    while let Some(task) = pending.pop() {
+        match task {
+            Copy::Term(term) => match term.deref() {
+                Term::Var(v) => results.push(var(&v)),
+                Term::Compound(c) => {
+                    pending.push(Copy::Compound(c.name.clone(), c.args.len()));
+                    pending.extend(c.args.iter().rev().cloned().map(Copy::Term));
+                }
+                Term::Dict(d) => {
+                    let values: Vec<Term> = d.pairs().into_iter().map(|(_, value)| value).collect();
+                    pending.push(Copy::Dict(d.clone()));
+                    pending.extend(values.into_iter().rev().map(Copy::Term));
+                    pending.push(Copy::Term(d.tag.clone()));
+                }
+                term => results.push(term),
+            },
+            Copy::Compound(name, arity) => {
+                let args = results.split_off(results.len() - arity);
+                results.push(Term::new_compound(name, args));
+            }
+            Copy::Dict(d) => {
+                let mut values = results.split_off(results.len() - d.len()).into_iter();
+                let tag = results.pop().expect("the tag is copied before the values");
+                let dict = d.map(tag, &mut |_| values.next().expect("a copy for every value"));
+                results.push(Term::Dict(Rc::new(dict)));
+            }
+        }
+    }
+    results.pop().expect("a copy of the term")
+}
+
+pub fn unify(lhs: &Term, rhs: &Term, trail: &mut Trail) -> bool {
+    unify_terms(lhs, rhs, trail, false)
+}
+
+pub fn unify_with_occurs_check(lhs: &Term, rhs: &Term, trail: &mut Trail) -> bool {
+    unify_terms(lhs, rhs, trail, true)
+}
+
+fn unify_terms(lhs: &Term, rhs: &Term, trail: &mut Trail, occurs_check: bool) -> bool {
+    // the pairs left to unify only take space once there are arguments
+    let mut pending = Vec::new();
+    let (mut lhs, mut rhs) = (lhs.clone(), rhs.clone());
+    loop {
+        match (lhs.deref(), rhs.deref()) {
+            (Term::Var(l), Term::Var(r)) if Rc::ptr_eq(&l, &r) => {}
+            (Term::Var(l), Term::Var(r)) => {
+                // bind the younger variable so older ones stay the roots
+                if l.id < r.id {
+                    bind(&r, Term::Var(l), trail);
+                } else {
+                    bind(&l, Term::Var(r), trail);
+                }
+            }
+            (Term::Var(v), t) | (t, Term::Var(v)) => {
+                if occurs_check && occurs(&v, &t) {
+                    return false;
+                }
+                bind(&v, t, trail)
+            }
+            (Term::Atom(l), Term::Atom(r)) => {
+                if l != r {
+                    return false;
+                }
+            }
+            (Term::Int(l), Term::Int(r)) => {
+                if l != r {
+                    return false;
+                }
+            }
+            (Term::Rational(l), Term::Rational(r)) => {
+                if l != r {
+                    return false;
+                }
+            }
+            (Term::Float(l), Term::Float(r)) => {
+                if l.to_bits() != r.to_bits() {
+                    return false;
+                }
+            }
+            (Term::Str(l), Term::Str(r)) => {
+                if l != r {
+                    return false;
+                }
+            }
+            (Term::Compound(l), Term::Compound(r)) if Rc::ptr_eq(&l, &r) => {}
+            (Term::Compound(l), Term::Compound(r)) => {
+                if l.name != r.name || l.args.len() != r.args.len() {
+                    return false;
+                }
+                for (a, b) in l.args.iter().zip(r.args.iter()).rev() {
+                    pending.push((a.clone(), b.clone()));
+                }
+            }
+            // dicts unify when they have the same keys, pairing up the tags
+            // and the values under each key
+            (Term::Dict(l), Term::Dict(r)) => {
+                let (lp, rp) = (l.pairs(), r.pairs());
+                if lp.len() != rp.len() || lp.iter().zip(&rp).any(|(a, b)| a.0 != b.0) {
+                    return false;
+                }
+                for ((_, a), (_, b)) in lp.into_iter().zip(rp).rev() {
+                    pending.push((a, b));
+                }
+                pending.push((l.tag.clone(), r.tag.clone()));
+            }
+            _ => return false,
+        }
+        match pending.pop() {
+            Some(next) => (lhs, rhs) = next,
+            None => return true,
+        }
+    }
+}
+
+// true when the terms are equal up to a consistent renaming of variables
+pub fn variant(lhs: &Term, rhs: &Term) -> bool {
+    let mut forward: HashMap<u64, u64> = HashMap::new();
+    let mut backward: HashMap<u64, u64> = HashMap::new();
+    let mut pending = vec![(lhs.clone(), rhs.clone())];
+    while let Some((lhs, rhs)) = pending.pop() {
+        match (lhs.deref(), rhs.deref()) {
+            (Term::Var(l), Term::Var(r)) => {
+                if *forward.entry(l.id).or_insert(r.id) != r.id || *backward.entry(r.id).or_insert(l.id) != l.id {
+                    return false;
+                }
+            }
+            (Term::Compound(l), Term::Compound(r)) => {
+                if l.name != r.name || l.args.len() != r.args.len() {
+                    return false;
+                }
+                pending.extend(l.args.iter().cloned().zip(r.args.iter().cloned()));
+            }
+            (Term::Dict(l), Term::Dict(r)) => {
+                let (lp, rp) = (l.pairs(), r.pairs());
+                if lp.len() != rp.len() || lp.iter().zip(&rp).any(|(a, b)| a.0 != b.0) {
+                    return false;
+                }
+                pending.push((l.tag.clone(), r.tag.clone()));
+                pending.extend(lp.into_iter().zip(rp).map(|((_, a), (_, b))| (a, b)));
+            }
+            (Term::Var(_), _) | (_, Term::Var(_)) => return false,
+            (l, r) => {
+                if compare(&l, &r) != Ordering::Equal {
+                    return false;
+                }
+            }
+        }
+    }
+    true
+}
+
+fn occurs(var: &Rc<Var>, term: &Term) -> bool {
+    let mut pending = vec![term.clone()];
+    while let Some(term) = pending.pop() {
+        match term.deref() {
+            Term::Var(v) if Rc::ptr_eq(var, &v) => return true,
+            Term::Compound(c) => pending.extend(c.args.iter().cloned()),
+            Term::Dict(d) => {
+                pending.push(d.tag.clone());
+                pending.extend(d.pairs().into_iter().map(|(_, value)| value));
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+// Standard order of terms: variables by age, then numbers by value (a float
+// before an equal integer), atoms and then strings alphabetically, and
+// compound terms by arity, name and arguments from left to right. Dicts come
+// last, by size, tag, keys and then values.
+pub fn compare(lhs: &Term, rhs: &Term) -> Ordering {
+    // pairs still to compare, and orders already known that decide unless
+    // the pairs before them differ
+    let mut pending: Vec<Result<(Term, Term), Ordering>> = vec![Ok((lhs.clone(), rhs.clone()))];
+    while let Some(next) = pending.pop() {
+        let order = match next {
+            Ok((lhs, rhs)) => compare_step(&lhs.deref(), &rhs.deref(), &mut pending),
+            Err(order) => order,
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    Ordering::Equal
+}
+
+// compares the outside of two terms, leaving their arguments to `pending`
+fn compare_step(lhs: &Term, rhs: &Term, pending: &mut Vec<Result<(Term, Term), Ordering>>) -> Ordering {
+    let rank = |t: &Term| match t {
+        Term::Var(_) => 0,
+        Term::Int(_) | Term::Rational(_) | Term::Float(_) => 1,
+        Term::Atom(_) => 2,
+        Term::Str(_) => 3,
+        Term::Compound(_) => 4,
+        Term::Dict(_) => 5,
+    };
+    match (lhs, rhs) {
+        (Term::Var(l), Term::Var(r)) => l.id.cmp(&r.id),
+        (Term::Atom(l), Term::Atom(r)) | (Term::Str(l), Term::Str(r)) => l.cmp(r),
+        (Term::Compound(l), Term::Compound(r)) => {
+            let order = l.args.len().cmp(&r.args.len()).then_with(|| l.name.cmp(&r.name));
+            if order == Ordering::Equal {
+                pending.extend(l.args.iter().cloned().zip(r.args.iter().cloned()).rev().map(Ok));
+            }
+            order
+        }
+        (Term::Dict(l), Term::Dict(r)) => {
+            let (lp, rp) = (l.pairs(), r.pairs());
+            let order = lp.len().cmp(&rp.len());
+            if order == Ordering::Equal {
+                let keys = lp.iter().map(|p| &p.0).cmp(rp.iter().map(|p| &p.0));
+                pending.extend(lp.into_iter().zip(rp).rev().map(|((_, a), (_, b))| Ok((a, b))));
+                pending.push(Err(keys));
+                pending.push(Ok((l.tag.clone(), r.tag.clone())));
+            }
+            order
+        }
+        _ if rank(lhs) == 1 && rank(rhs) == 1 => compare_numbers(lhs, rhs),
+        _ => rank(lhs).cmp(&rank(rhs)),
+    }
+}
+
+fn compare_numbers(lhs: &Term, rhs: &Term) -> Ordering {
+    let by_value = match (lhs, rhs) {
+        (Term::Int(l), Term::Int(r)) => l.cmp(r),
+        (Term::Rational(l), Term::Rational(r)) => l.cmp(*r),
+        (Term::Int(l), Term::Rational(r)) => Rational::from_int(*l).cmp(*r),
+        (Term::Rational(l), Term::Int(r)) => l.cmp(Rational::from_int(*r)),
+        _ => number_value(lhs).total_cmp(&number_value(rhs)),
+    };
+    let is_float = |t: &Term| matches!(t, Term::Float(_));
+    by_value.then_with(|| is_float(rhs).cmp(&is_float(lhs)))
+}
+
+fn number_value(term: &Term) -> f64 {
+    match term {
+        Term::Int(n) => *n as f64,
+        Term::Rational(r) => r.to_f64(),
+        Term::Float(n) => *n,
+        _ => 0.0,
+    }
+}
+
+pub fn bind(var: &Rc<Var>, value: Term, trail: &mut Trail) {
+    var.bind(value);
+    if var.id < trail.horizon {
+        trail.vars.push(var.clone());
+    }
+}
+
+pub fn undo_trail(trail: &mut Trail, len: usize) {
+    while trail.vars.len() > len {
+        if let Some(var) = trail.vars.pop() {
+            var.unbind();
+        }
+    }
+}
+
+// what is left to write of a term: a subterm or punctuation between them
+enum Piece {
+    Term(Term),
+    Text(&'static str),
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut pending = vec![Piece::Term(self.clone())];
+        while let Some(piece) = pending.pop() {
+            let term = match piece {
+                Piece::Text(text) => {
+                    write!(f, "{}", text)?;
+                    continue;
+                }
+                Piece::Term(term) => term.deref(),
+            };
+            // the pieces of a compound, in the order they are written
+            let mut pieces = Vec::new();
+            match &term {
+                Term::Var(v) => write!(f, "_G{}", v.id)?,
+                Term::Atom(name) => write!(f, "{}", name)?,
+                Term::Int(n) => write!(f, "{}", n)?,
+                Term::Rational(r) => write!(f, "{}r{}", r.numerator(), r.denominator())?,
+                Term::Float(n) => write!(f, "{}", format_float(*n))?,
+                Term::Str(text) => write!(f, "{}", text)?,
+                Term::Compound(c) if &*c.name == "." && c.args.len() == 2 => {
+                    let (items, tail) = term.list_items();
+                    write!(f, "[")?;
+                    for (i, item) in items.into_iter().enumerate() {
+                        if i > 0 {
+                            pieces.push(Piece::Text(","));
+                        }
+                        pieces.push(Piece::Term(item));
+                    }
+                    match tail {
+                        Term::Atom(name) if &*name == "[]" => {}
+                        tail => {
+                            pieces.push(Piece::Text("|"));
+                            pieces.push(Piece::Term(tail));
+                        }
+                    }
+                    pieces.push(Piece::Text("]"));
+                }
+                Term::Compound(c) => {
+                    write!(f, "{}(", c.name)?;
+                    for (i, arg) in c.args.iter().enumerate() {
+                        if i > 0 {
+                            pieces.push(Piece::Text(","));
+                        }
+                        pieces.push(Piece::Term(arg.clone()));
+                    }
+                    pieces.push(Piece::Text(")"));
+                }
+                Term::Dict(d) => {
+                    pieces.push(Piece::Term(d.tag.clone()));
+                    pieces.push(Piece::Text("{"));
+                    for (i, (key, value)) in d.pairs().into_iter().enumerate() {
+                        if i > 0 {
+                            pieces.push(Piece::Text(","));
+                        }
+                        pieces.push(Piece::Term(key.to_term()));
+                        pieces.push(Piece::Text(":"));
+                        pieces.push(Piece::Term(value));
+                    }
+                    pieces.push(Piece::Text("}"));
+                }
+            }
+            pending.extend(pieces.into_iter().rev());
+        }
+        Ok(())
+    }
+}
+
+// floats always show a fraction or an exponent so they read back as floats
+pub fn format_float(n: f64) -> String {
+    if n.is_nan() {
+        return String::from("nan");
+    }
+    if n.is_infinite() {
+        return String::from(if n > 0.0 { "inf" } else { "-inf" });
+    }
+    let text = format!("{:?}", n);
+    match text.find('e') {
+        Some(i) if !text[..i].contains('.') => format!("{}.0{}", &text[..i], &text[i..]),
+        _ => text,
+    }
+}
+
+impl fmt::Debug for Term {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
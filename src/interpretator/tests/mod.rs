@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use crate::{tokenizer::Tokenizer, parser::Parser};
 
-// use super::ContextEvaluator;
+use super::{error::ErrorKind, Runner};
 
 
 #[test]
@@ -36,6 +36,18 @@ fn it_unifies_rules() {
   println!("{:?}", u);
 }
 
+#[test]
+fn it_unifies_strings_and_dicts_by_value() {
+  let parse = |text: &str| Parser::from_tokens(Tokenizer::from_str(text).parse().unwrap()).parse().unwrap();
+  let (abc, abd, any) = (parse("s(\"abc\")."), parse("s(\"abd\")."), parse("s(X)."));
+  assert!(abc[0].unify(abc[0].as_ref(), &mut HashMap::new()).is_some());
+  assert!(abc[0].unify(abd[0].as_ref(), &mut HashMap::new()).is_none());
+  assert!(abc[0].unify(any[0].as_ref(), &mut HashMap::new()).is_some());
+  let (one, two) = (parse("d(_{a: 1})."), parse("d(_{a: 2})."));
+  assert!(one[0].unify(one[0].as_ref(), &mut HashMap::new()).is_some());
+  assert!(one[0].unify(two[0].as_ref(), &mut HashMap::new()).is_none());
+}
+
 #[test]
 fn it_evaluates_facts() {
   let t = "cat(list(), list(one,list(two,list())), X).";
@@ -44,11 +56,12 @@ fn it_evaluates_facts() {
   let tokens_t = tokenizer_t.parse().unwrap();
   let mut tokenizer_c = Tokenizer::from_str(c);
   let tokens_c = tokenizer_c.parse().unwrap();
-  // let clause_t = Parser::from_tokens(tokens_t).parse().unwrap();
-  // let clause_c = Parser::from_tokens(tokens_c).parse().unwrap();
-  //let mut variable_mapping = HashMap::new();
-  // let mut ctx = ContextEvaluator::new_goal(clause_t[0].copy(), &None, &variable_mapping);
-  // ctx.evaluate(&clause_c);
+  let clause_t = Parser::from_tokens(tokens_t).parse().unwrap();
+  let clause_c = Parser::from_tokens(tokens_c).parse().unwrap();
+  let mut runner = Runner::from_input(clause_t[0].copy(), clause_c);
+  let solution = runner.next_solution().unwrap().unwrap();
+  assert_eq!(solution["X"].to_string(), "list(one,list(two,list))");
+  assert!(runner.next_solution().unwrap().is_none());
 }
 
 #[test]
@@ -61,7 +74,326 @@ fn it_evaluates_rules() {
   let tokens_c = tokenizer_c.parse().unwrap();
   let clause_t = Parser::from_tokens(tokens_t).parse().unwrap();
   let clause_c = Parser::from_tokens(tokens_c).parse().unwrap();
-  //let mut variable_mapping = HashMap::new();
-  //let mut ctx = ContextEvaluator::new_goal(clause_c[0].copy(), &None, &variable_mapping);
-  //ctx.evaluate(&clause_t);
+  let mut runner = Runner::from_input(clause_c[0].copy(), clause_t);
+  let solution = runner.next_solution().unwrap().unwrap();
+  assert_eq!(solution["Z"].to_string(), "list(a,list(b,list))");
+  assert!(runner.next_solution().unwrap().is_none());
+}
+
+pub fn runner(program: &str, query: &str) -> Runner {
+  let clauses = Parser::from_tokens(Tokenizer::from_str(program).parse().unwrap()).parse().unwrap();
+  let goal = Parser::from_tokens(Tokenizer::from_str(query).parse().unwrap()).parse().unwrap();
+  Runner::from_input(goal[0].copy(), clauses)
+}
+
+// the values `variable` takes in every answer to `query`
+pub fn solve(program: &str, query: &str, variable: &str) -> Vec<String> {
+  let mut runner = runner(program, query);
+  let mut answers = Vec::new();
+  while let Some(solution) = runner.next_solution().unwrap() {
+    answers.push(solution.get(variable).map(|t| t.to_string()).unwrap_or_default());
+  }
+  answers
+}
+
+// the error term `query` ends with
+pub fn solve_error(program: &str, query: &str) -> String {
+  let mut runner = runner(program, query);
+  loop {
+    match runner.next_solution() {
+      Ok(Some(_)) => {}
+      Ok(None) => panic!("{} did not raise an error", query),
+      Err(ball) => return ball.to_string(),
+    }
+  }
+}
+
+#[test]
+fn it_backtracks_into_disjunction() {
+  let program = "
+    colour(X) :- X = red ; X = green ; X = blue.
+  ";
+  assert_eq!(solve(program, "colour(C).", "C"), vec!["red", "green", "blue"]);
+}
+
+#[test]
+fn it_commits_to_if_then_else_branch() {
+  let program = "
+    :- dynamic q/1.
+    p(a). p(b).
+    first(X) :- (p(X) -> true ; X = none).
+    absent(X) :- (q(X) -> true ; X = none).
+  ";
+  assert_eq!(solve(program, "first(X).", "X"), vec!["a"]);
+  assert_eq!(solve(program, "absent(X).", "X"), vec!["none"]);
+  assert_eq!(solve(program, "(p(X) -> fail ; X = none).", "X"), Vec::<String>::new());
+}
+
+#[test]
+fn it_keeps_condition_solutions_with_soft_cut() {
+  let program = "
+    :- dynamic q/1.
+    p(a). p(b).
+    all(X) :- (p(X) *-> true ; X = none).
+  ";
+  assert_eq!(solve(program, "all(X).", "X"), vec!["a", "b"]);
+  assert_eq!(solve(program, "(q(X) *-> true ; X = none).", "X"), vec!["none"]);
+}
+
+#[test]
+fn it_negates_goals() {
+  let program = "
+    p(a).
+  ";
+  assert_eq!(solve(program, "\\+ p(b).", "X"), vec![""]);
+  assert!(solve(program, "\\+ p(a).", "X").is_empty());
+  assert_eq!(solve(program, "\\+ \\+ p(X), X = b.", "X"), vec!["b"]);
+}
+
+#[test]
+fn it_cuts_to_parent_predicate() {
+  let program = "
+    p(a). p(b). p(c).
+    first(X) :- p(X), !.
+    max(X, Y, X) :- X = big, !.
+    max(_, Y, Y).
+    either(X) :- (p(X), ! ; X = none).
+  ";
+  assert_eq!(solve(program, "first(X).", "X"), vec!["a"]);
+  assert_eq!(solve(program, "max(big, small, M).", "M"), vec!["big"]);
+  assert_eq!(solve(program, "max(small, big, M).", "M"), vec!["big"]);
+  assert_eq!(solve(program, "either(X).", "X"), vec!["a"]);
+  assert_eq!(solve(program, "p(X), !.", "X"), vec!["a"]);
+}
+
+#[test]
+fn it_keeps_cut_local_to_call_negation_and_condition() {
+  let program = "
+    p(a). p(b).
+    via_call(X) :- call((p(X), !)).
+    via_not(X) :- p(X), \\+ (p(_), !, fail).
+    via_cond(X) :- (p(X), ! -> true ; true).
+  ";
+  assert_eq!(solve(program, "via_call(X).", "X"), vec!["a"]);
+  assert_eq!(solve(program, "via_call(X) ; X = c.", "X"), vec!["a", "c"]);
+  assert_eq!(solve(program, "via_not(X).", "X"), vec!["a", "b"]);
+  assert_eq!(solve(program, "via_cond(X) ; X = c.", "X"), vec!["a", "c"]);
+}
+
+#[test]
+fn it_catches_thrown_balls() {
+  let program = "
+    p(1). p(2). p(3).
+    check(X) :- X > 2, throw(too_big(X)).
+    check(_).
+    safe(X, R) :- catch(check(X), too_big(N), R = caught(N)).
+  ";
+  assert_eq!(solve(program, "catch(throw(oops), E, true).", "E"), vec!["oops"]);
+  assert_eq!(solve(program, "safe(1, R).", "R"), vec!["_G1"]);
+  assert_eq!(solve(program, "safe(5, R).", "R"), vec!["caught(5)"]);
+  assert!(solve(program, "catch((p(X), check(X)), too_big(N), true), X == 3.", "N").is_empty());
+  assert_eq!(solve(program, "catch((p(X), check(X)), too_big(N), X = none).", "X"), vec!["1", "2", "none"]);
+  assert_eq!(solve(program, "catch(catch(throw(inner), outer, X = wrong), inner, X = right).", "X"), vec!["right"]);
+  assert_eq!(solve(program, "catch(X is foo + 1, error(type_error(T, C), _), true).", "C"), vec!["/(foo,0)"]);
+  assert_eq!(solve(program, "catch(call(1), error(E, _), true).", "E"), vec!["type_error(callable,1)"]);
+  assert_eq!(solve(program, "catch(call((fail, 1)), error(E, _), true).", "E"), vec!["type_error(callable,,(fail,1))"]);
+  assert_eq!(solve(program, "G = (write(x), (true ; 2)), catch(G, error(E, _), true).", "E"), vec!["type_error(callable,,(write(x),;(true,2)))"]);
+  assert_eq!(solve(program, "call((fail, X)).", "X"), Vec::<String>::new());
+  assert_eq!(solve(program, "catch(G, error(E, _), true).", "E"), vec!["instantiation_error"]);
+  assert_eq!(solve_error(program, "catch(true, _, true), throw(late)."), "late");
+}
+
+#[test]
+fn it_restores_state_when_catching() {
+  let program = "
+    p(1). p(2).
+    q(X) :- X = bound, throw(stop).
+  ";
+  assert_eq!(solve(program, "catch(q(X), stop, true).", "X"), vec!["_G1"]);
+  assert_eq!(solve(program, "catch((p(X), throw(stop)), stop, true) ; X = other.", "X"), vec!["_G1", "other"]);
+  assert_eq!(solve_error(program, "catch(p(X), stop, true), X > 1, throw(stop)."), "stop");
+  // a cut inside the goal does not keep the catcher from undoing bindings
+  let query = "p(Z), length(L, 1), catch((L = [1], !, throw(stop)), stop, true), L = [V], var(V).";
+  assert_eq!(solve(program, query, "Z"), vec!["1", "2"]);
+  assert_eq!(solve(program, "catch((p(Y), !, throw(stop)), stop, true), var(Y).", "Y").len(), 1);
+  assert_eq!(solve(program, "p(X), length(L, 1), (true -> L = [X] ; true), X > 1.", "L"), vec!["[2]"]);
+}
+
+#[test]
+fn it_reports_typed_errors() {
+  let error = runner("", "atom_length(X, L).").next_solution().unwrap_err();
+  assert!(matches!(error.kind, ErrorKind::Instantiation));
+  let error = runner("", "X is 1 / 0.").next_solution().unwrap_err();
+  assert!(matches!(error.kind, ErrorKind::Evaluation { error } if error == "zero_divisor"));
+  match runner("", "atom_length(abc, foo).").next_solution().unwrap_err().kind {
+    ErrorKind::Type { valid_type, culprit } => {
+      assert_eq!(valid_type, "integer");
+      assert_eq!(culprit.to_string(), "foo");
+    }
+    kind => panic!("unexpected {:?}", kind),
+  }
+  let mut query = runner("", "throw(custom(1)).");
+  let error = query.next_solution().unwrap_err();
+  assert!(matches!(&error.kind, ErrorKind::Thrown(ball) if ball.to_string() == "custom(1)"));
+  assert_eq!(error.to_string(), "custom(1)");
+  assert!(query.next_solution().unwrap().is_none());
+}
+
+#[test]
+fn it_handles_unknown_procedures() {
+  let program = "
+    :- dynamic known/1.
+    typo(X) :- knwon(X).
+  ";
+  assert!(solve_error(program, "typo(X).").starts_with("error(existence_error(procedure,/(knwon,1))"));
+  assert_eq!(solve(program, "known(X).", "X"), Vec::<String>::new());
+  assert_eq!(solve(program, "catch(typo(X), error(existence_error(procedure, PI), _), true).", "PI"), vec!["/(knwon,1)"]);
+  assert_eq!(solve(program, "set_prolog_flag(unknown, fail), (typo(a) -> X = yes ; X = no).", "X"), vec!["no"]);
+  assert_eq!(solve(program, "current_prolog_flag(unknown, F).", "F"), vec!["error"]);
+  assert!(solve_error(program, "set_prolog_flag(unknown, ignore).").starts_with("error(domain_error(flag_value,+(unknown,ignore))"));
+}
+
+#[test]
+fn it_selects_clauses_by_first_argument() {
+  let program = "
+    p(a, 1). p(b, 2). p(c, 3).
+    q(a, 1). q(_, 2). q(b, 3). q(a, 4).
+    r(1.5, x). r(f(1), y). r(f(2), z). r(g(1), w). r(\"s\", v). r(_, u).
+    :- dynamic t/1.
+  ";
+  let mut query = runner(program, "p(b, X).");
+  assert_eq!(query.next_solution().unwrap().unwrap()["X"].to_string(), "2");
+  assert!(query.choicepoints.is_empty());
+  assert_eq!(solve(program, "q(a, X).", "X"), vec!["1", "2", "4"]);
+  assert_eq!(solve(program, "q(c, X).", "X"), vec!["2"]);
+  assert_eq!(solve(program, "q(Y, X).", "X"), vec!["1", "2", "3", "4"]);
+  assert_eq!(solve(program, "r(1.5, X).", "X"), vec!["x", "u"]);
+  assert_eq!(solve(program, "r(f(_), X).", "X"), vec!["y", "z", "u"]);
+  assert_eq!(solve(program, "r(\"s\", X).", "X"), vec!["v", "u"]);
+  let changed = "assertz(t(b)), assertz(t(_)), asserta(t(a)), once(retract(t(b))), findall(x, t(b), X), findall(Y, t(a), Ys), length(Ys, N).";
+  assert_eq!(solve(program, changed, "X"), vec!["[x]"]);
+  assert_eq!(solve(program, changed, "N"), vec!["2"]);
+  assert!(solve(program, "retract(t(a)), t(a).", "X").is_empty());
+}
+
+#[test]
+fn it_indexes_the_arguments_calls_bind() {
+  let mut program = String::from(":- dynamic col/2.\n");
+  for i in 1..=12 {
+    program += &format!("row({0}, k{0}). item(p({0}, c{0}), v{0}). col({0}, k{0}). nest(p({0}, c{0}), v{0}).\n", i);
+    if i == 6 {
+      program += "col(0, _). nest(p(0, _), any).\n";
+    }
+  }
+  let mut query = runner(&program, "row(X, k9).");
+  assert_eq!(query.next_solution().unwrap().unwrap()["X"].to_string(), "9");
+  assert!(query.choicepoints.is_empty());
+  let mut query = runner(&program, "item(p(_, c11), V).");
+  assert_eq!(query.next_solution().unwrap().unwrap()["V"].to_string(), "v11");
+  assert!(query.choicepoints.is_empty());
+  assert_eq!(solve(&program, "findall(X, col(X, k3), L), once(col(Y, k4)), findall(X, col(X, k3), L).", "L"), vec!["[3,0]"]);
+  assert_eq!(solve(&program, "findall(V, nest(p(_, c2), V), L).", "L"), vec!["[v2,any]"]);
+  let changed = "col(_, k1), assertz(col(13, k1)), asserta(col(14, k2)), retract(col(1, _)), findall(X, col(X, k1), L).";
+  assert_eq!(solve(&program, changed, "L"), vec!["[0,13]"]);
+  assert_eq!(solve(&program, "findall(X, col(X, _), L), length(L, N).", "N"), vec!["13"]);
+}
+
+#[test]
+fn it_runs_compiled_clauses() {
+  let program = "
+    swap(p(X, q(Y, [Z|T])), r(T, Z, Y, X)).
+    same(X, X, f(X)).
+    constant(f(a, [1, 2])).
+    shared(f(A, A)).
+    wrap(X, D) :- D = _{value: X}.
+    unwrap(_{value: X}, X).
+    run(G) :- G.
+    local(X) :- member(X, [1, 2, 3]), run(!).
+    p(1). p(2).
+    r(X) :- f(Y, g(Y)) = f(1, X).
+    w(X) :- findall(Z, p(Z), X).
+    t(X) :- copy_term(f(Y, p(Y)), X).
+    u(X) :- X = f(g(Y), Y, h(Y)), Y = 3.
+  ";
+  assert_eq!(solve(program, "swap(p(1, q(2, [3, 4])), R).", "R"), vec!["r([4],3,2,1)"]);
+  assert_eq!(solve(program, "swap(A, r([], c, b, a)).", "A"), vec!["p(a,q(b,[c]))"]);
+  assert_eq!(solve(program, "same(1, Y, Z).", "Z"), vec!["f(1)"]);
+  assert!(solve(program, "same(1, 2, _).", "X").is_empty());
+  assert_eq!(solve(program, "constant(f(a, L)).", "L"), vec!["[1,2]"]);
+  assert_eq!(solve(program, "shared(X), shared(Y), X = f(1, _), Y = f(2, B).", "B"), vec!["2"]);
+  assert_eq!(solve(program, "wrap(7, D), unwrap(D, V).", "V"), vec!["7"]);
+  assert_eq!(solve(program, "findall(X, local(X), L).", "L"), vec!["[1,2,3]"]);
+  // a variable of a body goal shared with a term nested inside it
+  assert_eq!(solve(program, "r(X).", "X"), vec!["g(1)"]);
+  assert_eq!(solve(program, "w(X).", "X"), vec!["[1,2]"]);
+  assert_eq!(solve(program, "t(f(A, p(B))), A == B, var(A).", "A").len(), 1);
+  assert_eq!(solve(program, "u(X).", "X"), vec!["f(g(3),3,h(3))"]);
+}
+
+#[test]
+fn it_numbers_variables_past_u32() {
+  // bindings are trailed by comparing variable ids with the last mark, so
+  // ids have to keep increasing where a 32 bit counter would wrap around
+  let mut query = runner("", "length(L, 4), member(X, [a, b]), L = [X|_], X == b.");
+  query.id_factory.id_counter = u32::MAX as u64 - 2;
+  let answer = query.next_solution().unwrap().unwrap();
+  assert_eq!(answer["L"].to_string().get(..3), Some("[b,"));
+  assert!(query.id_factory.id_counter > u32::MAX as u64);
+}
+
+#[test]
+fn it_returns_deep_terms() {
+  let program = "
+    mk(0, z) :- !.
+    mk(N, s(T)) :- M is N - 1, mk(M, T).
+  ";
+  let list = solve(program, "numlist(1, 1000000, L).", "L");
+  assert!(list[0].starts_with("[1,2,3,") && list[0].ends_with(",999999,1000000]"));
+  let query = "mk(300000, T), copy_term(T, C), C == T, msort([T, z, C], [z|_]), format(atom(A), '~q', [C]), atom_length(A, N).";
+  let answer = runner(program, query).next_solution().unwrap().unwrap();
+  let nested = answer["T"].to_string();
+  assert_eq!((nested.len(), &nested[599996..600003]), (900001, "s(s(z))"));
+  assert_eq!(answer["N"].to_string(), "900001");
+}
+
+// Runs a loop of 10^6 iterations that records the size of the trail and of
+// the continuation every 100000 of them. Nothing may grow with the number of
+// iterations: the trail stays empty and the continuation as deep as at the
+// start.
+fn assert_bounded(program: &str, goal: &str) {
+  let program = format!("{}
+    sample(I) :- (I mod 100000 =:= 0 -> statistics(trailused, T), statistics(localused, L), assertz(seen(T, L)) ; true).
+  ", program);
+  let query = format!("{}, findall(T, seen(T, _), Ts), findall(L, seen(_, L), Ls), sort(Ls, [_]).", goal);
+  let mut query = runner(&program, &query);
+  let answer = query.next_solution().unwrap().unwrap_or_else(|| panic!("{}: the continuation grew", goal));
+  assert_eq!(answer["Ts"].to_string(), "[0,0,0,0,0,0,0,0,0,0]", "{}", goal);
+  assert!(query.choicepoints.is_empty());
+}
+
+#[test]
+fn it_runs_deep_recursion_in_bounded_stack() {
+  let program = "
+    count(N, N) :- !.
+    count(I, N) :- sample(I), J is I + 1, count(J, N).
+  ";
+  assert_bounded(program, "count(0, 1000000)");
+  let program = "
+    len([], 0).
+    len([_|T], N) :- len(T, M), N is M + 1.
+    long(N) :- numlist(1, N, L), len(L, N).
+    down(0) :- throw(bottom).
+    down(N) :- M is N - 1, down(M), true.
+  ";
+  assert_eq!(solve(program, "long(200000).", "X").len(), 1);
+  assert_eq!(solve(program, "catch(down(200000), B, true).", "B"), vec!["bottom"]);
+}
+
+#[test]
+fn it_runs_loops_that_cut_in_bounded_stack() {
+  let program = "
+    loop(N) :- N > 0, !, sample(N), M is N - 1, loop(M).
+    loop(0).
+  ";
+  assert_bounded(program, "loop(1000000)");
 }
@@ -0,0 +1,237 @@
+use std::rc::Rc;
+
+use super::{
+    super::parser::{OpType, Operators},
+    term::{format_float, Compound, Term},
+};
+
+// Turns terms back into Prolog text, using operator notation wherever the
+// operator table allows it. Quoted output reads back as the same term.
+struct Writer<'a> {
+    ops: &'a Operators,
+    quoted: bool,
+    out: String,
+    // what is left to write, last first; subterms go here rather than
+    // through recursive calls so deeply nested terms can be written
+    pending: Vec<Piece>,
+}
+
+enum Piece {
+    Term(Term, u32),
+    Atom(Rc<str>),
+    Char(char),
+}
+
+pub fn format_term(term: &Term, ops: &Operators, quoted: bool) -> String {
+    let mut writer = Writer { ops, quoted, out: String::new(), pending: Vec::new() };
+    writer.pending.push(Piece::Term(term.clone(), 1200));
+    while let Some(piece) = writer.pending.pop() {
+        match piece {
+            Piece::Term(term, max_priority) => writer.write(&term, max_priority),
+            Piece::Atom(name) => writer.atom(&name),
+            Piece::Char(ch) => writer.out.push(ch),
+        }
+    }
+    writer.out
+}
+
+impl Writer<'_> {
+    fn write(&mut self, term: &Term, max_priority: u32) {
+        let term = term.deref();
+        match &term {
+            Term::Var(v) => self.token(&format!("_G{}", v.id)),
+            Term::Int(n) => self.token(&n.to_string()),
+            Term::Rational(r) => self.token(&format!("{}r{}", r.numerator(), r.denominator())),
+            Term::Float(n) => self.token(&format_float(*n)),
+            Term::Str(text) if self.quoted => self.token(&quote(text, '"')),
+            Term::Str(text) => self.out.push_str(text),
+            Term::Atom(name) => {
+                let bracket = self.operator_priority(name) > max_priority;
+                if bracket {
+                    self.out.push('(');
+                }
+                self.atom(name);
+                if bracket {
+                    self.out.push(')');
+                }
+            }
+            Term::Compound(c) if &*c.name == "." && c.args.len() == 2 => self.list(&term),
+            Term::Compound(c) => self.compound(c, max_priority),
+            Term::Dict(d) => {
+                let mut pieces = vec![Piece::Term(d.tag.clone(), 0), Piece::Char('{')];
+                for (i, (key, value)) in d.pairs().into_iter().enumerate() {
+                    if i > 0 {
+                        pieces.push(Piece::Char(','));
+                    }
+                    pieces.extend([Piece::Term(key.to_term(), 0), Piece::Char(':'), Piece::Term(value, 999)]);
+                }
+                pieces.push(Piece::Char('}'));
+                self.defer(pieces);
+            }
+        }
+    }
+
+    // queues pieces to be written in the order given
+    fn defer(&mut self, pieces: Vec<Piece>) {
+        self.pending.extend(pieces.into_iter().rev());
+    }
+
+    fn compound(&mut self, c: &Compound, max_priority: u32) {
+        if &*c.name == "{}" && c.args.len() == 1 {
+            self.defer(vec![Piece::Char('{'), Piece::Term(c.args[0].clone(), 1200), Piece::Char('}')]);
+            return;
+        }
+        let mut pieces = Vec::new();
+        if c.args.len() == 2 {
+            if let Some((priority, op_type)) = self.ops.infix(&c.name) {
+                let (left_max, right_max) = match op_type {
+                    OpType::Xfy => (priority - 1, priority),
+                    OpType::Yfx => (priority, priority - 1),
+                    _ => (priority - 1, priority - 1),
+                };
+                let bracket = priority > max_priority;
+                if bracket {
+                    pieces.push(Piece::Char('('));
+                }
+                pieces.push(Piece::Term(c.args[0].clone(), left_max));
+                if &*c.name == "," {
+                    pieces.push(Piece::Char(','));
+                } else {
+                    pieces.push(Piece::Atom(c.name.clone()));
+                }
+                pieces.push(Piece::Term(c.args[1].clone(), right_max));
+                if bracket {
+                    pieces.push(Piece::Char(')'));
+                }
+                self.defer(pieces);
+                return;
+            }
+        }
+        if c.args.len() == 1 {
+            if let Some((priority, op_type)) = self.ops.prefix(&c.name) {
+                let arg_max = match op_type {
+                    OpType::Fy => priority,
+                    _ => priority - 1,
+                };
+                let bracket = priority > max_priority;
+                if bracket {
+                    pieces.push(Piece::Char('('));
+                }
+                pieces.push(Piece::Atom(c.name.clone()));
+                // `- 1` is the compound, `-1` would read back as a number, and
+                // `-(a,b)` as a compound with two arguments
+                let arg = c.args[0].deref();
+                let is_number = matches!(arg, Term::Int(_) | Term::Rational(_) | Term::Float(_));
+                if is_number || self.term_priority(&arg) > arg_max {
+                    pieces.push(Piece::Char(' '));
+                }
+                pieces.push(Piece::Term(arg, arg_max));
+                if bracket {
+                    pieces.push(Piece::Char(')'));
+                }
+                self.defer(pieces);
+                return;
+            }
+        }
+        pieces.extend([Piece::Atom(c.name.clone()), Piece::Char('(')]);
+        for (i, arg) in c.args.iter().enumerate() {
+            if i > 0 {
+                pieces.push(Piece::Char(','));
+            }
+            pieces.push(Piece::Term(arg.clone(), 999));
+        }
+        pieces.push(Piece::Char(')'));
+        self.defer(pieces);
+    }
+
+    fn list(&mut self, list: &Term) {
+        let (items, tail) = list.list_items();
+        let mut pieces = vec![Piece::Char('[')];
+        for (i, item) in items.into_iter().enumerate() {
+            if i > 0 {
+                pieces.push(Piece::Char(','));
+            }
+            pieces.push(Piece::Term(item, 999));
+        }
+        match tail {
+            Term::Atom(name) if &*name == "[]" => {}
+            tail => pieces.extend([Piece::Char('|'), Piece::Term(tail, 999)]),
+        }
+        pieces.push(Piece::Char(']'));
+        self.defer(pieces);
+    }
+
+    fn atom(&mut self, name: &str) {
+        if self.quoted && needs_quotes(name) {
+            self.token(&quote(name, '\''));
+        } else {
+            self.token(name);
+        }
+    }
+
+    // the priority a term is written at; operands above their argument
+    // priority get brackets
+    fn term_priority(&self, term: &Term) -> u32 {
+        match term.deref() {
+            Term::Atom(name) => self.operator_priority(&name),
+            Term::Compound(c) if c.args.len() == 2 && &*c.name != "." => {
+                self.ops.infix(&c.name).map_or(0, |op| op.0)
+            }
+            Term::Compound(c) if c.args.len() == 1 && &*c.name != "{}" => {
+                self.ops.prefix(&c.name).map_or(0, |op| op.0)
+            }
+            _ => 0,
+        }
+    }
+
+    fn operator_priority(&self, name: &str) -> u32 {
+        let prefix = self.ops.prefix(name).map_or(0, |op| op.0);
+        let infix = self.ops.infix(name).map_or(0, |op| op.0);
+        prefix.max(infix)
+    }
+
+    // appends a token, separated from the previous one when the two would
+    // otherwise read back as a single token
+    fn token(&mut self, text: &str) {
+        if let (Some(last), Some(first)) = (self.out.chars().last(), text.chars().next()) {
+            let alphanumeric = |ch: char| ch.is_alphanumeric() || ch == '_';
+            let symbolic = |ch: char| "+-*/\\^<>=~:.?@#&".contains(ch);
+            if (alphanumeric(last) && alphanumeric(first)) || (symbolic(last) && symbolic(first)) {
+                self.out.push(' ');
+            }
+        }
+        self.out.push_str(text);
+    }
+}
+
+fn needs_quotes(name: &str) -> bool {
+    if matches!(name, "[]" | "!" | ";" | "{}") {
+        return false;
+    }
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_lowercase() => !chars.all(|ch| ch.is_alphanumeric() || ch == '_'),
+        Some(_) if name != "." => !name.chars().all(|ch| "+-*/\\^<>=~:.?@#&".contains(ch)),
+        _ => true,
+    }
+}
+
+fn quote(text: &str, quote: char) -> String {
+    let mut quoted = String::new();
+    quoted.push(quote);
+    for ch in text.chars() {
+        match ch {
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            ch if ch == quote => {
+                quoted.push('\\');
+                quoted.push(ch);
+            }
+            ch if ch.is_control() => quoted.push_str(&format!("\\x{:x}\\", ch as u32)),
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push(quote);
+    quoted
+}
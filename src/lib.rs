@@ -3,4 +3,10 @@ mod tokenizer;
 #[allow(dead_code, unused_must_use)]
 mod parser;
 #[allow(dead_code, unused_must_use)]
-mod interpretator;
\ No newline at end of file
+mod interpretator;
+
+pub use interpretator::{
+    error::{ErrorKind, PrologError},
+    term::Term,
+    Runner,
+};
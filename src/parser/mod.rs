@@ -1,7 +1,7 @@
 #[cfg(test)]
 mod tests;
 
-use std::{fmt::{Formatter, Debug, Error}};
+use std::{collections::HashMap, fmt::{Formatter, Debug, Error}};
 
 use super::tokenizer::*;
 use super::interpretator::*;
@@ -10,6 +10,14 @@ pub struct Variable(pub String);
 
 pub struct Constant(pub String);
 
+pub struct Str(pub String);
+
+pub enum Number {
+    Integer(i64),
+    Rational(i64, i64),
+    Float(f64),
+}
+
 pub struct Fact {
     pub name: String,
     pub args: Vec<Box<dyn Clause>>,
@@ -20,9 +28,82 @@ pub struct Rule {
     pub body: Vec<Box<dyn Clause>>,
 }
 
+/// A dict literal such as `point{x: 1, y: 2}`; the tag is an atom or a
+/// variable and every key is an atom or an integer.
+pub struct Dict {
+    pub tag: Box<dyn Clause>,
+    pub pairs: Vec<(Box<dyn Clause>, Box<dyn Clause>)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpType {
+    Xfx,
+    Xfy,
+    Yfx,
+    Fy,
+    Fx,
+}
+
+pub struct Operators {
+    prefix: HashMap<String, (u32, OpType)>,
+    infix: HashMap<String, (u32, OpType)>,
+}
+
+impl Operators {
+    pub fn add(&mut self, priority: u32, op_type: OpType, name: &str) {
+        let table = match op_type {
+            OpType::Fy | OpType::Fx => &mut self.prefix,
+            _ => &mut self.infix,
+        };
+        table.insert(String::from(name), (priority, op_type));
+    }
+
+    pub fn prefix(&self, name: &str) -> Option<(u32, OpType)> {
+        self.prefix.get(name).copied()
+    }
+
+    pub fn infix(&self, name: &str) -> Option<(u32, OpType)> {
+        self.infix.get(name).copied()
+    }
+}
+
+impl Default for Operators {
+    fn default() -> Self {
+        let mut ops = Operators { prefix: HashMap::new(), infix: HashMap::new() };
+        ops.add(1200, OpType::Xfx, ":-");
+        ops.add(1200, OpType::Fx, ":-");
+        ops.add(1200, OpType::Fx, "?-");
+        ops.add(1150, OpType::Fx, "dynamic");
+        ops.add(1150, OpType::Fx, "discontiguous");
+        ops.add(1150, OpType::Fx, "meta_predicate");
+        ops.add(1100, OpType::Xfy, ";");
+        ops.add(1050, OpType::Xfy, "->");
+        ops.add(1050, OpType::Xfy, "*->");
+        ops.add(1000, OpType::Xfy, ",");
+        ops.add(900, OpType::Fy, "\\+");
+        for name in ["=", "\\=", "==", "\\==", "@<", "@>", "@=<", "@>=", "=..", "is", "=:=", "=\\=", "<", ">", "=<", ">="] {
+            ops.add(700, OpType::Xfx, name);
+        }
+        ops.add(600, OpType::Xfy, ":");
+        for name in ["+", "-", "/\\", "\\/", "xor"] {
+            ops.add(500, OpType::Yfx, name);
+        }
+        for name in ["*", "/", "//", "rem", "mod", "div", "rdiv", "<<", ">>"] {
+            ops.add(400, OpType::Yfx, name);
+        }
+        ops.add(200, OpType::Xfx, "**");
+        ops.add(200, OpType::Xfy, "^");
+        for name in ["-", "+", "\\"] {
+            ops.add(200, OpType::Fy, name);
+        }
+        ops
+    }
+}
+
 pub struct Parser {
     pos: usize,
     tokens: Vec<Token>,
+    ops: Operators,
 }
 
 #[derive(Debug)]
@@ -43,7 +124,7 @@ impl ParserError {
 
 impl Parser {
     pub fn from_tokens(tokens: Vec<Token>) -> Self {
-        Parser { tokens, pos: 0 }
+        Parser { tokens, pos: 0, ops: Operators::default() }
     }
 
     pub fn parse(&mut self) -> Result<Vec<Box<dyn Clause>>, ParserError> {
@@ -57,115 +138,298 @@ impl Parser {
         Ok(clauses)
     }
 
-    fn parse_clause(&mut self) -> Result<Box<dyn Clause>, ParserError> {
-        let variable_clause = self.parse_variable_clause();
-        if let Ok(v) = variable_clause {
-            return Ok(v);
+    // the index of the token the next clause starts at, or `None` once only
+    // layout is left
+    pub fn next_clause_start(&mut self) -> Option<usize> {
+        if self.has_tokens() {
+            Some(self.pos)
+        } else {
+            None
         }
-        if let Ok(r) = self.parse_rule_clause() {
-            return Ok(r);
+    }
+
+    // goes past the end of a clause that did not parse, so reading can go on
+    // with the next one
+    pub fn skip_clause(&mut self) {
+        while let Some(token) = self.tokens.get(self.pos) {
+            self.pos += 1;
+            if matches!(token, Token::SpecialSymbol(SpecialSymbol::Dot)) {
+                break;
+            }
+        }
+    }
+
+    // a single term, as read by `term_to_atom/2` and friends: the closing
+    // dot is optional but nothing may follow it
+    pub fn parse_read_term(&mut self) -> Result<Box<dyn Clause>, ParserError> {
+        let term = self.parse_term(1200)?.0;
+        if self.has_tokens() {
+            self.is_symbol(SpecialSymbol::Dot)?;
+        }
+        if self.has_tokens() {
+            return Err(ParserError::BaseError { msg: String::from("Operator expected") });
+        }
+        Ok(term)
+    }
+
+    pub fn parse_clause(&mut self) -> Result<Box<dyn Clause>, ParserError> {
+        let term = self.parse_term(1200)?.0;
+        self.is_symbol(SpecialSymbol::Dot)?;
+        if term.get_name().as_deref() == Some(":-") {
+            if let Some(mut args) = term.get_args() {
+                if args.len() == 2 {
+                    let body = args.pop().unwrap();
+                    let head = args.pop().unwrap();
+                    let mut goals = Vec::new();
+                    Self::flatten_conjunction(body, &mut goals);
+                    return Ok(Box::new(Rule { head, body: goals }));
+                }
+            }
+        }
+        Ok(term)
+    }
+
+    fn flatten_conjunction(goal: Box<dyn Clause>, goals: &mut Vec<Box<dyn Clause>>) {
+        if goal.get_name().as_deref() == Some(",") {
+            if let Some(args) = goal.get_args() {
+                if args.len() == 2 {
+                    for arg in args {
+                        Self::flatten_conjunction(arg, goals);
+                    }
+                    return;
+                }
+            }
         }
-        let fact = self.parse_fact_clause()?;
-        Ok(fact)
+        goals.push(goal);
     }
 
-    fn parse_variable_clause(&mut self) -> Result<Box<dyn Clause>, ParserError> {
-        let var = self.parse_variable()?;
-        if let Some(token) = self.next_token() {
-            return match token {
-                Token::SpecialSymbol(SpecialSymbol::Dot) => Ok(var),
-                t => {
-                    self.advance_pos(-1);
-                    return Err(ParserError::expected_error(
-                        self.pos,
-                        &Token::SpecialSymbol(SpecialSymbol::Dot),
-                        &t,
-                    ));
-                },
+    // Operator precedence parsing: reads a primary term and then folds in as
+    // many infix operators as `max_priority` allows.
+    fn parse_term(&mut self, max_priority: u32) -> Result<(Box<dyn Clause>, u32), ParserError> {
+        let (mut left, mut left_priority) = self.parse_primary(max_priority)?;
+        loop {
+            let start = self.pos;
+            let name = match self.next_token() {
+                Some(Token::Constant(name)) => name,
+                Some(Token::SpecialSymbol(SpecialSymbol::Comma)) => String::from(","),
+                _ => {
+                    self.pos = start;
+                    break;
+                }
             };
+            let (priority, op_type) = match self.ops.infix(&name) {
+                Some(op) => op,
+                None => {
+                    self.pos = start;
+                    break;
+                }
+            };
+            let (left_max, right_max) = match op_type {
+                OpType::Xfy => (priority - 1, priority),
+                OpType::Yfx => (priority, priority - 1),
+                _ => (priority - 1, priority - 1),
+            };
+            if priority > max_priority || left_priority > left_max {
+                self.pos = start;
+                break;
+            }
+            let right = self.parse_term(right_max)?.0;
+            left = Box::new(Fact { name, args: vec![left, right] });
+            left_priority = priority;
         }
-        return Err(ParserError::BaseError {
-            msg: String::from("Expected Token, found None"),
-        });
+        Ok((left, left_priority))
     }
 
-    fn parse_rule_clause(&mut self) -> Result<Box<dyn Clause>, ParserError> {
-        let current_pos = self.pos;
+    fn parse_primary(&mut self, max_priority: u32) -> Result<(Box<dyn Clause>, u32), ParserError> {
+        if let Ok(var) = self.parse_variable() {
+            if let Some(Token::SpecialSymbol(SpecialSymbol::LCurly)) = self.tokens.get(self.pos) {
+                return Ok((self.parse_dict(var)?, 0));
+            }
+            return Ok((var, 0));
+        }
+        if let Some(number) = self.parse_number(false) {
+            return Ok((number, 0));
+        }
+        let start = self.pos;
+        if let Some(Token::Str(text)) = self.next_token() {
+            return Ok((Box::new(Str(text)), 0));
+        }
+        self.pos = start;
+        if self.is_symbol(SpecialSymbol::LBrace).is_ok() {
+            let term = self.parse_term(1200)?.0;
+            self.is_symbol(SpecialSymbol::RBrace)?;
+            return Ok((term, 0));
+        }
+        if self.is_symbol(SpecialSymbol::LBracket).is_ok() {
+            return Ok((self.parse_list()?, 0));
+        }
+        if self.is_symbol(SpecialSymbol::LCurly).is_ok() {
+            if self.is_symbol(SpecialSymbol::RCurly).is_ok() {
+                return Ok((Box::new(Constant(String::from("{}"))), 0));
+            }
+            let term = self.parse_term(1200)?.0;
+            self.is_symbol(SpecialSymbol::RCurly)?;
+            return Ok((Box::new(Fact { name: String::from("{}"), args: vec![term] }), 0));
+        }
         let name = self.get_constant()?;
-        let head = self.parse_functor(name)?;
-        if let Err(e) = self.is_symbol(SpecialSymbol::Dots) {
-            self.pos = current_pos;
-            return Err(e);
+        if name == "-" {
+            // a minus sign directly in front of a number is part of the literal
+            if let Some(Token::Number(_)) | Some(Token::Rational(..)) | Some(Token::Float(_)) = self.tokens.get(self.pos) {
+                if let Some(number) = self.parse_number(true) {
+                    return Ok((number, 0));
+                }
+            }
+        }
+        if let Some(Token::SpecialSymbol(SpecialSymbol::LBrace)) = self.tokens.get(self.pos) {
+            return Ok((self.parse_functor(name)?, 0));
+        }
+        if let Some(Token::SpecialSymbol(SpecialSymbol::LCurly)) = self.tokens.get(self.pos) {
+            return Ok((self.parse_dict(Box::new(Constant(name)))?, 0));
+        }
+        if let Some((priority, op_type)) = self.ops.prefix(&name) {
+            if !self.at_term_end() {
+                let start = self.pos;
+                let priority = priority.min(max_priority);
+                let arg_max = match op_type {
+                    OpType::Fy => priority,
+                    _ => priority - 1,
+                };
+                if let Ok((arg, _)) = self.parse_term(arg_max) {
+                    return Ok((Box::new(Fact { name, args: vec![arg] }), priority));
+                }
+                self.pos = start;
+            }
         }
+        Ok((Box::new(Constant(name)), 0))
+    }
 
-        self.is_symbol(SpecialSymbol::Minus)?;
-        
-        let mut body = Vec::new();
-        while let Err(_) = self.is_symbol(SpecialSymbol::Dot) {
-            let name = self.get_constant()?;
-            let body_functor = self.parse_functor(name)?;
-            body.push(body_functor);
-            self.is_symbol(SpecialSymbol::Comma);
+    // true when the next token cannot start an operand, so a prefix operator
+    // in front of it has to be read as a plain atom
+    fn at_term_end(&mut self) -> bool {
+        let start = self.pos;
+        let token = self.next_token();
+        self.pos = start;
+        match token {
+            None => true,
+            Some(Token::SpecialSymbol(SpecialSymbol::LBrace)) => false,
+            Some(Token::SpecialSymbol(SpecialSymbol::LBracket)) => false,
+            Some(Token::SpecialSymbol(SpecialSymbol::LCurly)) => false,
+            Some(Token::SpecialSymbol(_)) => true,
+            Some(Token::Constant(name)) => {
+                self.ops.infix(&name).is_some() && self.ops.prefix(&name).is_none()
+            }
+            Some(_) => false,
         }
-        Ok(Box::new(Rule{head, body}))
     }
 
-    fn parse_fact_clause(&mut self) -> Result<Box<dyn Clause>, ParserError> {
-        let constant = self.get_constant()?;
-        let functor = self.parse_functor(constant)?;
-        self.is_symbol(SpecialSymbol::Dot)?;
-        Ok(functor)
+    // the elements after `[`, with lists built from '.'/2 cells ending in []
+    fn parse_list(&mut self) -> Result<Box<dyn Clause>, ParserError> {
+        if self.is_symbol(SpecialSymbol::RBracket).is_ok() {
+            return Ok(Box::new(Constant(String::from("[]"))));
+        }
+        let mut items = vec![self.parse_term(999)?.0];
+        while self.is_symbol(SpecialSymbol::Comma).is_ok() {
+            items.push(self.parse_term(999)?.0);
+        }
+        let mut list: Box<dyn Clause> = if self.is_symbol(SpecialSymbol::Bar).is_ok() {
+            self.parse_term(999)?.0
+        } else {
+            Box::new(Constant(String::from("[]")))
+        };
+        self.is_symbol(SpecialSymbol::RBracket)?;
+        while let Some(item) = items.pop() {
+            list = Box::new(Fact { name: String::from("."), args: vec![item, list] });
+        }
+        Ok(list)
+    }
+
+    fn parse_number(&mut self, negative: bool) -> Option<Box<dyn Clause>> {
+        let start = self.pos;
+        let number = match self.next_token() {
+            Some(Token::Number(n)) if negative => Number::Integer(-n),
+            Some(Token::Number(n)) => Number::Integer(n),
+            Some(Token::Rational(n, d)) if negative => Number::Rational(-n, d),
+            Some(Token::Rational(n, d)) => Number::Rational(n, d),
+            Some(Token::Float(f)) if negative => Number::Float(-f),
+            Some(Token::Float(f)) => Number::Float(f),
+            _ => {
+                self.pos = start;
+                return None;
+            }
+        };
+        Some(Box::new(number))
     }
 
     fn parse_functor(&mut self, name: String) -> Result<Box<dyn Clause>, ParserError> {
         self.is_symbol(SpecialSymbol::LBrace)?;
         let mut args = Vec::<Box<dyn Clause>>::new();
-        while let Err(_) = self.is_symbol(SpecialSymbol::RBrace) {
-            let arg = self.parse_arg()?;
+        while self.is_symbol(SpecialSymbol::RBrace).is_err() {
+            let arg = self.parse_term(999)?.0;
             args.push(arg);
-            self.is_symbol(SpecialSymbol::Comma);
+            if self.is_symbol(SpecialSymbol::Comma).is_err() {
+                self.is_symbol(SpecialSymbol::RBrace)?;
+                break;
+            }
         }
         Ok(Box::new(Fact{ name, args }))
     }
 
-    fn parse_arg(&mut self) -> Result<Box<dyn Clause>, ParserError> {
-        let var = self.parse_variable();
-        if let Ok(boxed_var) = var {
-            return Ok(boxed_var);
+    // the `Key: Value` pairs of a dict after its tag, with no key twice
+    fn parse_dict(&mut self, tag: Box<dyn Clause>) -> Result<Box<dyn Clause>, ParserError> {
+        self.is_symbol(SpecialSymbol::LCurly)?;
+        let mut pairs: Vec<(Box<dyn Clause>, Box<dyn Clause>)> = Vec::new();
+        if self.is_symbol(SpecialSymbol::RCurly).is_ok() {
+            return Ok(Box::new(Dict { tag, pairs }));
         }
-        let constant = self.get_constant()?;
-        if let Ok(functor) = self.parse_functor(constant.clone()) {
-            return Ok(functor);
+        loop {
+            let key: Box<dyn Clause> = match self.next_token() {
+                Some(Token::Constant(name)) => Box::new(Constant(name)),
+                Some(Token::Number(n)) => Box::new(Number::Integer(n)),
+                _ => return Err(ParserError::BaseError { msg: String::from("Dict key expected") }),
+            };
+            let duplicate = pairs.iter().any(|(known, _)| format!("{:?}", known) == format!("{:?}", key));
+            if duplicate {
+                return Err(ParserError::BaseError { msg: format!("Duplicate dict key {:?}", key) });
+            }
+            match self.next_token() {
+                Some(Token::Constant(colon)) if colon == ":" => {}
+                _ => return Err(ParserError::BaseError { msg: String::from("Expected : after dict key") }),
+            }
+            let value = self.parse_term(999)?.0;
+            pairs.push((key, value));
+            if self.is_symbol(SpecialSymbol::Comma).is_err() {
+                self.is_symbol(SpecialSymbol::RCurly)?;
+                return Ok(Box::new(Dict { tag, pairs }));
+            }
         }
-        Ok(Box::new(Constant(constant)))
     }
 
     fn parse_variable(&mut self) -> Result<Box<dyn Clause>, ParserError> {
+        let start = self.pos;
         let token = self.next_token();
         let r = self.get_variable(&token);
         if let Ok(var) = r {
             return Ok(Box::new(Variable(var)));
         }
-        self.advance_pos(-1);
+        self.pos = start;
         Err(r.unwrap_err())
     }
 
     fn get_constant(&mut self) -> Result<String, ParserError> {
+        let start = self.pos;
         let token = self.next_token();
         match token {
             None => Err(ParserError::BaseError {
                 msg: String::from("No token"),
             }),
-            Some(token) => match token.clone() {
-                Token::Constant(s) => Ok(s),
-                t => {
-                    self.advance_pos(-1);
-                    return Err(ParserError::expected_error(
-                        self.pos,
-                        &Token::Constant(String::from("Some")),
-                        &t,
-                    ));
-                },
+            Some(Token::Constant(s)) => Ok(s),
+            Some(t) => {
+                self.pos = start;
+                Err(ParserError::expected_error(
+                    self.pos,
+                    &Token::Constant(String::from("Some")),
+                    &t,
+                ))
             },
         }
     }
@@ -174,6 +438,7 @@ impl Parser {
         &mut self,
         special_symbol: SpecialSymbol,
     ) -> Result<(), ParserError> {
+        let start = self.pos;
         let token = self.next_token();
         match token {
             None => Err(ParserError::BaseError {
@@ -185,12 +450,12 @@ impl Parser {
                         return Ok(());
                     }
                 }
-                self.advance_pos(-1);
-                return Err(ParserError::expected_error(
+                self.pos = start;
+                Err(ParserError::expected_error(
                     self.pos,
                     &Token::SpecialSymbol(special_symbol),
                     &token,
-                ));
+                ))
             }
         }
     }
@@ -211,18 +476,23 @@ impl Parser {
         }
     }
 
+    // returns the next token that is not layout
     fn next_token(&mut self) -> Option<Token> {
-        let t = self.tokens.get(self.pos).map(|token_ref| token_ref.clone());
-        self.advance_pos(1);
+        self.skip_whitespace();
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
         t
     }
 
-    fn has_tokens(&self) -> bool {
-        self.pos < self.tokens.len()
+    fn skip_whitespace(&mut self) {
+        while let Some(Token::Whitespace(_)) = self.tokens.get(self.pos) {
+            self.pos += 1;
+        }
     }
 
-    fn advance_pos(&mut self, p: i32) {
-        self.pos = (self.pos as i32 + p) as usize;
+    fn has_tokens(&mut self) -> bool {
+        self.skip_whitespace();
+        self.pos < self.tokens.len()
     }
 }
 
@@ -240,6 +510,24 @@ impl Debug for Constant {
     }
 }
 
+impl Debug for Str {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.write_fmt(format_args!("Str({:?})", &self.0));
+        Ok(())
+    }
+}
+
+impl Debug for Number {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            Number::Integer(n) => f.write_fmt(format_args!("Number({})", n)),
+            Number::Rational(n, d) => f.write_fmt(format_args!("Number({}r{})", n, d)),
+            Number::Float(n) => f.write_fmt(format_args!("Number({:?})", n)),
+        };
+        Ok(())
+    }
+}
+
 impl Debug for Fact {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         f.write_fmt(format_args!("Fact({}, {:?})", &self.name, &self.args));
@@ -247,6 +535,13 @@ impl Debug for Fact {
     }
 }
 
+impl Debug for Dict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.write_fmt(format_args!("Dict({:?}, {:?})", &self.tag, &self.pairs));
+        Ok(())
+    }
+}
+
 impl Debug for Rule {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         f.write_fmt(format_args!("Rule({:?}, {:?})", &self.head, &self.body));
@@ -52,4 +52,64 @@ fn it_parses_list_of_clauses() {
   assert_eq!(clauses.len(), 2);
   println!("{:?}", &clauses);
 
-}
\ No newline at end of file
+}
+#[test]
+fn it_parses_control_constructs() {
+  let input = "p(X) :- (q(X) -> r ; s), \\+ t(X).";
+  let tokens = Tokenizer::from_str(input).parse().unwrap();
+  let mut parser = Parser::from_tokens(tokens);
+  let clauses = parser.parse().unwrap();
+  assert_eq!(clauses.len(), 1);
+  let body = clauses[0].subgoals().unwrap();
+  assert_eq!(body.len(), 2);
+  assert_eq!(body[0].get_name().unwrap(), ";");
+  assert_eq!(body[1].get_name().unwrap(), "\\+");
+  println!("{:?}", &clauses);
+}
+
+#[test]
+fn it_parses_arithmetic_expressions() {
+  let input = "X is 1 - -2 * 3.5 + - (4).";
+  let tokens = Tokenizer::from_str(input).parse().unwrap();
+  let clauses = Parser::from_tokens(tokens).parse().unwrap();
+  assert_eq!(
+    format!("{:?}", clauses[0]),
+    "Fact(is, [Variable(X), Fact(+, [Fact(-, [Number(1), Fact(*, [Number(-2), Number(3.5)])]), Fact(-, [Number(4)])])])"
+  );
+}
+
+#[test]
+fn it_parses_lists() {
+  let input = "f([a, b | T], [], [c]).";
+  let tokens = Tokenizer::from_str(input).parse().unwrap();
+  let clauses = Parser::from_tokens(tokens).parse().unwrap();
+  assert_eq!(
+    format!("{:?}", clauses[0]),
+    "Fact(f, [Fact(., [Constant(a), Fact(., [Constant(b), Variable(T)])]), Constant([]), Fact(., [Constant(c), Constant([])])])"
+  );
+}
+
+#[test]
+fn it_parses_quoted_atoms_and_strings() {
+  let input = "X = f('A b', \"text\").";
+  let tokens = Tokenizer::from_str(input).parse().unwrap();
+  let clauses = Parser::from_tokens(tokens).parse().unwrap();
+  assert_eq!(
+    format!("{:?}", clauses[0]),
+    "Fact(=, [Variable(X), Fact(f, [Constant(A b), Str(\"text\")])])"
+  );
+}
+
+#[test]
+fn it_parses_dicts() {
+  let input = "X = point{x: 1, 2: \"b\"}, Y = _{}, Z = {a}.";
+  let tokens = Tokenizer::from_str(input).parse().unwrap();
+  let clauses = Parser::from_tokens(tokens).parse().unwrap();
+  assert_eq!(
+    format!("{:?}", clauses[0]),
+    "Fact(,, [Fact(=, [Variable(X), Dict(Constant(point), [(Constant(x), Number(1)), (Number(2), Str(\"b\"))])]), \
+     Fact(,, [Fact(=, [Variable(Y), Dict(Variable(_), [])]), Fact(=, [Variable(Z), Fact({}, [Constant(a)])])])])"
+  );
+  let tokens = Tokenizer::from_str("X = _{a: 1, a: 2}.").parse().unwrap();
+  assert!(Parser::from_tokens(tokens).parse().is_err());
+}
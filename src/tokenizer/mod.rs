@@ -6,25 +6,32 @@ pub enum SpecialSymbol {
     LBrace,                   // (
     RBrace,                   // )
     Comma,                    // ,
-    Dot,                      // .
-    Dots,                     // :
-    Eq,                       // =
-    LThan,                    // <
-    GThan,                    // >
-    Minus                     // -
+    Dot,                      // . followed by layout or end of input
+    LBracket,                 // [
+    RBracket,                 // ]
+    Bar,                      // |
+    LCurly,                   // {
+    RCurly,                   // }
 }
 
+/// Marker for the characters that glue together into symbolic atoms such as
+/// `:-`, `->` or `\+`.
+pub struct Symbol;
+
 #[derive(Clone, Debug)]
 pub enum Constant {
     Literal(String),
-    Number(u32)
+    Number(i64)
 }
 
 #[derive(Clone, Debug)]
 pub enum Token {
     Constant(String),
-    Number(u32),
+    Number(i64),
+    Rational(i64, i64),
+    Float(f64),
     Variable(String),
+    Str(String),
     SpecialSymbol(SpecialSymbol),
     Whitespace(String),
 }
@@ -40,39 +47,157 @@ pub struct Tokenizer {
     pos: usize
 }
 
-impl<'a> Tokenizer {
+impl Tokenizer {
     pub fn from_str(input_str: &str) -> Self {
         Tokenizer { input: input_str.chars().collect(), pos: 0 }
     }
 
     pub fn parse(&mut self) -> Result<Vec<Token>, TokenizerError> {
+        Ok(self.parse_with_lines()?.into_iter().map(|(token, _)| token).collect())
+    }
+
+    /// Tokenizes the input, pairing every token with the line it starts on,
+    /// counting from 1.
+    pub fn parse_with_lines(&mut self) -> Result<Vec<(Token, usize)>, TokenizerError> {
+        let (tokens, mut errors) = self.scan(false);
+        if errors.is_empty() {
+            Ok(tokens)
+        }
+        else {
+            Err(errors.remove(0))
+        }
+    }
+
+    /// Tokenizes the input like `parse_with_lines`, going on past characters
+    /// that do not scan: the clause they are in is left out, and scanning
+    /// picks up after its end.
+    pub fn parse_recovering(&mut self) -> (Vec<(Token, usize)>, Vec<TokenizerError>) {
+        self.scan(true)
+    }
+
+    fn scan(&mut self, recover: bool) -> (Vec<(Token, usize)>, Vec<TokenizerError>) {
         let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        let (mut line, mut counted) = (1, 0);
         while self.input.len() > self.pos {
-            if let Some(ch) = self.seek_char() {
-                let token: Token;
-                if ch.is_whitespace() {
-                    self.next_char();
-                    continue;
-                }
-                else if ch.is_uppercase() {
-                    token = self.parse_variable()?;
+            line += self.input[counted..self.pos].iter().filter(|ch| **ch == '\n').count();
+            counted = self.pos;
+            match self.next_token() {
+                Ok(Some(token)) => tokens.push((token, line)),
+                Ok(None) => {}
+                Err(error) => {
+                    let TokenizerError::BaseError { position, .. } = &error;
+                    let position = *position;
+                    errors.push(error);
+                    if !recover {
+                        break;
+                    }
+                    let clause_start = tokens
+                        .iter()
+                        .rposition(|(token, _)| matches!(token, Token::SpecialSymbol(SpecialSymbol::Dot)))
+                        .map_or(0, |dot| dot + 1);
+                    tokens.truncate(clause_start);
+                    self.skip_clause(position.max(counted));
                 }
-                else if ch.is_lowercase() || ch.is_digit(10) {
-                    token = self.parse_constant()?;
+            }
+        }
+        (tokens, errors)
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, TokenizerError> {
+        let ch = match self.seek_char() {
+            Some(ch) => ch,
+            None => return Ok(None),
+        };
+        let token = if ch.is_whitespace() {
+            self.parse_whitespace()
+        }
+        else if ch == '%' || (ch == '/' && self.peek_char(1) == Some('*')) {
+            self.parse_comment()?
+        }
+        else if ch.is_uppercase() || ch == '_' {
+            self.parse_variable()?
+        }
+        else if ch.is_lowercase() || ch.is_ascii_digit() {
+            self.parse_constant()?
+        }
+        else if ch == '\'' {
+            Token::Constant(self.parse_quoted(ch)?)
+        }
+        else if ch == '"' {
+            Token::Str(self.parse_quoted(ch)?)
+        }
+        else if ch == ';' || ch == '!' {
+            self.next_char();
+            Token::Constant(ch.to_string())
+        }
+        else if Symbol::starts_with(ch) {
+            self.parse_symbol()
+        }
+        else {
+            self.parse_special_symbol()?
+        };
+        Ok(Some(token))
+    }
+
+    // moves past the first end dot after `from`, a `.` followed by layout or
+    // the end of the input
+    fn skip_clause(&mut self, from: usize) {
+        self.pos = from + 1;
+        while self.pos < self.input.len() {
+            let ch = self.input[self.pos];
+            self.pos += 1;
+            if ch == '.' && self.seek_char().is_none_or(|next| next.is_whitespace() || next == '%') {
+                break;
+            }
+        }
+    }
+
+    fn parse_whitespace(&mut self) -> Token {
+        let mut layout = String::new();
+        while let Some(ch) = self.next_char() {
+            if ch.is_whitespace() {
+                layout.push(ch);
+            }
+            else {
+                self.previous_char();
+                break;
+            }
+        }
+        Token::Whitespace(layout)
+    }
+
+    // a `%` comment runs to the end of the line, a `/* */` one to its closing
+    // mark; either counts as layout
+    fn parse_comment(&mut self) -> Result<Token, TokenizerError> {
+        let start = self.pos;
+        let mut comment = String::new();
+        if self.next_char() == Some('%') {
+            while let Some(ch) = self.next_char() {
+                if ch == '\n' {
+                    self.previous_char();
+                    break;
                 }
-                else {
-                    token = self.parse_special_symbol()?; 
+                comment.push(ch);
+            }
+            return Ok(Token::Whitespace(comment));
+        }
+        self.next_char();
+        loop {
+            match self.next_char() {
+                Some('*') if self.seek_char() == Some('/') => {
+                    self.next_char();
+                    return Ok(Token::Whitespace(comment));
                 }
-                tokens.push(token);
-                
+                Some(ch) => comment.push(ch),
+                None => return Err(TokenizerError::new_base_error(start, "Unterminated comment")),
             }
         }
-        Ok(tokens)
     }
 
     fn parse_variable(&mut self) -> Result<Token, TokenizerError> {
         let uppercase = match self.seek_char() {
-            Some(ch) => ch.is_uppercase(),
+            Some(ch) => ch.is_uppercase() || ch == '_',
             None => return Err(TokenizerError::new_base_error(self.pos, "No character"))
         };
         if !uppercase {
@@ -102,10 +227,11 @@ impl<'a> Tokenizer {
             return Err(TokenizerError::new_base_error(self.pos, "Char is not a valid prefix"));
         }
 
-        match self.parse_integer() {
-            Ok(token) => return Ok(token),
-            Err(_) => ()
-        };
+        if let Some(ch) = self.seek_char() {
+            if ch.is_ascii_digit() {
+                return self.parse_number();
+            }
+        }
 
         let mut literal = String::new();
         
@@ -121,27 +247,191 @@ impl<'a> Tokenizer {
         Ok(Token::Constant(literal))
     }
 
-    fn parse_integer(&mut self) -> Result<Token, TokenizerError> {
+    fn parse_number(&mut self) -> Result<Token, TokenizerError> {
+        let start = self.pos;
+        if self.next_char() == Some('0') {
+            let radix = match self.next_char() {
+                Some('\'') => return Ok(Token::Number(self.parse_char_code()? as i64)),
+                Some('x') => 16,
+                Some('o') => 8,
+                Some('b') => 2,
+                _ => 10,
+            };
+            let has_digits = self.seek_char().is_some_and(|ch| ch.is_digit(radix));
+            if radix != 10 && has_digits {
+                return Ok(Token::Number(self.parse_integer(radix)?));
+            }
+        }
+        self.pos = start;
+        let integer = self.parse_integer(10)?;
+
+        if self.seek_char() == Some('r') && self.peek_char(1).is_some_and(|ch| ch.is_ascii_digit()) {
+            self.next_char();
+            let denominator = self.parse_integer(10)?;
+            if denominator == 0 {
+                return Err(TokenizerError::new_base_error(start, "Zero denominator"));
+            }
+            return Ok(Token::Rational(integer, denominator));
+        }
+
+        let mut is_float = false;
+        if self.seek_char() == Some('.') && self.peek_char(1).is_some_and(|ch| ch.is_ascii_digit()) {
+            is_float = true;
+            self.next_char();
+            self.parse_integer(10)?;
+        }
+        if let Some('e') | Some('E') = self.seek_char() {
+            let sign = matches!(self.peek_char(1), Some('+') | Some('-'));
+            let digit_at = if sign { 2 } else { 1 };
+            if self.peek_char(digit_at).is_some_and(|ch| ch.is_ascii_digit()) {
+                is_float = true;
+                self.pos += digit_at;
+                self.parse_integer(10)?;
+            }
+        }
+        if !is_float {
+            return Ok(Token::Number(integer));
+        }
+        let text: String = self.input[start..self.pos].iter().collect();
+        match text.parse::<f64>() {
+            Ok(float) if float.is_finite() => Ok(Token::Float(float)),
+            _ => Err(TokenizerError::new_base_error(start, "Float out of range")),
+        }
+    }
+
+    fn parse_integer(&mut self, radix: u32) -> Result<i64, TokenizerError> {
+        let start = self.pos;
         let is_digit = match self.seek_char() {
-            Some(ch) => ch.is_digit(10),
+            Some(ch) => ch.is_digit(radix),
             None => return Err(TokenizerError::new_base_error(self.pos, "No character"))
         };
         if !is_digit {
             return Err(TokenizerError::new_base_error(self.pos, "Char is not digit"));
         }
 
-        let mut number = 0; 
+        let mut number: i64 = 0;
 
         while let Some(ch) = self.next_char() {
-            if let Some(v) = ch.to_digit(10) {
-                number += number*10 + v;
+            if let Some(v) = ch.to_digit(radix) {
+                number = number
+                    .checked_mul(radix as i64)
+                    .and_then(|n| n.checked_add(v as i64))
+                    .ok_or_else(|| TokenizerError::new_base_error(start, "Integer out of range"))?;
             }
             else {
                 self.previous_char();
                 break;
             }
         }
-        Ok(Token::Number(number))
+        Ok(number)
+    }
+
+    // the text between a pair of `quote` characters; a doubled quote stands
+    // for itself and a backslash before a newline continues the text
+    fn parse_quoted(&mut self, quote: char) -> Result<String, TokenizerError> {
+        let start = self.pos;
+        self.next_char();
+        let mut text = String::new();
+        loop {
+            match self.next_char() {
+                Some(ch) if ch == quote => {
+                    if self.seek_char() != Some(quote) {
+                        return Ok(text);
+                    }
+                    self.next_char();
+                    text.push(quote);
+                }
+                Some('\\') => {
+                    if self.seek_char() == Some('\n') {
+                        self.next_char();
+                    } else {
+                        text.push(self.parse_escape()?);
+                    }
+                }
+                Some(ch) => text.push(ch),
+                None => return Err(TokenizerError::new_base_error(start, "Unterminated quoted")),
+            }
+        }
+    }
+
+    // the character after `0'`, which may be written as an escape sequence
+    fn parse_char_code(&mut self) -> Result<u32, TokenizerError> {
+        match self.next_char() {
+            Some('\\') => self.parse_escape().map(|ch| ch as u32),
+            Some('\'') if self.seek_char() == Some('\'') => {
+                self.next_char();
+                Ok('\'' as u32)
+            }
+            Some(ch) => Ok(ch as u32),
+            None => Err(TokenizerError::new_base_error(self.pos, "No character")),
+        }
+    }
+
+    // reads the escape sequence following a backslash
+    fn parse_escape(&mut self) -> Result<char, TokenizerError> {
+        let start = self.pos;
+        let ch = match self.next_char() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('a') => '\x07',
+            Some('b') => '\x08',
+            Some('f') => '\x0c',
+            Some('v') => '\x0b',
+            Some('e') => '\x1b',
+            Some('s') => ' ',
+            Some('x') => {
+                let code = self.parse_integer(16)?;
+                self.escape_end(start)?;
+                return Self::code_to_char(start, code);
+            }
+            Some(ch) if ch.is_digit(8) => {
+                self.previous_char();
+                let code = self.parse_integer(8)?;
+                self.escape_end(start)?;
+                return Self::code_to_char(start, code);
+            }
+            Some(ch) if "\\'\"`".contains(ch) => ch,
+            _ => return Err(TokenizerError::new_base_error(start, "Undefined escape sequence")),
+        };
+        Ok(ch)
+    }
+
+    fn escape_end(&mut self, start: usize) -> Result<(), TokenizerError> {
+        match self.next_char() {
+            Some('\\') => Ok(()),
+            _ => Err(TokenizerError::new_base_error(start, "Unterminated escape sequence")),
+        }
+    }
+
+    fn code_to_char(position: usize, code: i64) -> Result<char, TokenizerError> {
+        u32::try_from(code)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| TokenizerError::new_base_error(position, "Invalid character code"))
+    }
+
+    fn parse_symbol(&mut self) -> Token {
+        let mut symbol = String::new();
+        while let Some(ch) = self.next_char() {
+            if Symbol::starts_with(ch) {
+                symbol.push(ch);
+            }
+            else {
+                self.previous_char();
+                break;
+            }
+        }
+        // a lone `.` followed by layout, a comment or the end of input
+        // terminates a clause
+        let ends_clause = match self.seek_char() {
+            Some(ch) => ch.is_whitespace() || ch == '%',
+            None => true
+        };
+        if symbol == "." && ends_clause {
+            return Token::SpecialSymbol(SpecialSymbol::Dot);
+        }
+        Token::Constant(symbol)
     }
 
     fn parse_special_symbol(&mut self) -> Result<Token, TokenizerError> {
@@ -154,23 +444,21 @@ impl<'a> Tokenizer {
         if !is_special_symbol {
             return Err(TokenizerError::new_base_error(self.pos, "Unrecognised character"));
         }
-        let token: Token;
         if let Some(ch) = self.next_char() {
-            token = match ch {
+            let token = match ch {
                 '(' => Token::SpecialSymbol(SpecialSymbol::LBrace),
                 ')' => Token::SpecialSymbol(SpecialSymbol::RBrace),
                 ',' => Token::SpecialSymbol(SpecialSymbol::Comma),
-                '.' => Token::SpecialSymbol(SpecialSymbol::Dot),
-                ':' => Token::SpecialSymbol(SpecialSymbol::Dots),
-                '=' => Token::SpecialSymbol(SpecialSymbol::Eq),
-                '<' => Token::SpecialSymbol(SpecialSymbol::LThan),
-                '>' => Token::SpecialSymbol(SpecialSymbol::GThan),
-                '-' => Token::SpecialSymbol(SpecialSymbol::Minus),
+                '[' => Token::SpecialSymbol(SpecialSymbol::LBracket),
+                ']' => Token::SpecialSymbol(SpecialSymbol::RBracket),
+                '|' => Token::SpecialSymbol(SpecialSymbol::Bar),
+                '{' => Token::SpecialSymbol(SpecialSymbol::LCurly),
+                '}' => Token::SpecialSymbol(SpecialSymbol::RCurly),
                 _ => return Err(TokenizerError::new_base_error(self.pos, "Character is not a special symbol"))
             };
             return Ok(token);
         }
-        return Err(TokenizerError::new_base_error(self.pos, "Character is not a special symbol"));
+        Err(TokenizerError::new_base_error(self.pos, "Character is not a special symbol"))
     }
 
     fn seek_char(&self) -> Option<char> {
@@ -180,26 +468,25 @@ impl<'a> Tokenizer {
         Some(self.input[self.pos])
     }
 
+    fn peek_char(&self, offset: usize) -> Option<char> {
+        self.input.get(self.pos + offset).copied()
+    }
+
     fn next_char(&mut self) -> Option<char> {
-        let ch: char;
         if self.input.len() <= self.pos {
             return None;
-        } else {
-            ch = self.input[self.pos];
-            self.pos += 1;
-        };
+        }
+        let ch = self.input[self.pos];
+        self.pos += 1;
         Some(ch)
     }
 
     fn previous_char(&mut self) -> Option<char> {
-        let ch: char;
-        if self.pos <= 0 {
+        if self.pos == 0 {
             return None;
-        } else {
-            self.pos -= 1;
-            ch = self.input[self.pos];           
-        };
-        Some(ch)
+        }
+        self.pos -= 1;
+        Some(self.input[self.pos])
     }
 }
 
@@ -209,14 +496,21 @@ pub trait StartsWith {
 
 impl StartsWith for SpecialSymbol {
     fn starts_with(ch: char) -> bool {
-        let options = "(),.:=<>-";
+        let options = "(),[]|{}";
+        options.contains(ch)
+    }
+}
+
+impl StartsWith for Symbol {
+    fn starts_with(ch: char) -> bool {
+        let options = "+-*/\\^<>=~:.?@#&";
         options.contains(ch)
     }
 }
 
 impl StartsWith for Constant {
     fn starts_with(ch: char) -> bool {
-        ch.is_digit(10) || ch.is_alphabetic() || ch == '_'
+        ch.is_ascii_digit() || ch.is_alphabetic() || ch == '_'
     }
 }
 
@@ -224,4 +518,10 @@ impl TokenizerError {
     pub fn new_base_error(position: usize, msg: &str) -> Self {
         TokenizerError::BaseError{position, msg: String::from(msg)}
     }
+
+    /// The line of `input` the error was found on, counting from 1.
+    pub fn line(&self, input: &str) -> usize {
+        let TokenizerError::BaseError { position, .. } = self;
+        1 + input.chars().take(*position).filter(|ch| *ch == '\n').count()
+    }
 }
\ No newline at end of file
@@ -27,3 +27,104 @@ fn it_does_not_make_tokens() {
         }
     };
 }
+
+
+#[test]
+fn it_makes_symbol_constants() {
+    let clause = "p :- \\+ q ; r -> s.";
+    let mut tokenizer = Tokenizer::from_str(clause);
+    let r: Vec<Token> = tokenizer
+        .parse()
+        .unwrap()
+        .into_iter()
+        .filter(|t| !matches!(t, Token::Whitespace(_)))
+        .collect();
+    assert_eq!(9, r.len());
+    assert!(matches!(&r[1], Token::Constant(s) if s == ":-"));
+    assert!(matches!(&r[2], Token::Constant(s) if s == "\\+"));
+    assert!(matches!(&r[4], Token::Constant(s) if s == ";"));
+    assert!(matches!(&r[8], Token::SpecialSymbol(SpecialSymbol::Dot)));
+}
+
+#[test]
+fn it_makes_cut_token() {
+    let mut tokenizer = Tokenizer::from_str("p:-q,!,r.");
+    let r = tokenizer.parse().unwrap();
+    assert_eq!(8, r.len());
+    assert!(matches!(&r[4], Token::Constant(s) if s == "!"));
+}
+
+#[test]
+fn it_makes_number_tokens() {
+    let mut tokenizer = Tokenizer::from_str("f(123, 0x1F, 0'a, 2.5e3, 7).");
+    let r: Vec<Token> = tokenizer
+        .parse()
+        .unwrap()
+        .into_iter()
+        .filter(|t| !matches!(t, Token::Whitespace(_)))
+        .collect();
+    assert!(matches!(r[2], Token::Number(123)));
+    assert!(matches!(r[4], Token::Number(31)));
+    assert!(matches!(r[6], Token::Number(97)));
+    assert!(matches!(r[8], Token::Float(f) if f == 2500.0));
+    assert!(matches!(r[10], Token::Number(7)));
+    assert!(matches!(r[12], Token::SpecialSymbol(SpecialSymbol::Dot)));
+}
+
+#[test]
+fn it_makes_rational_tokens() {
+    let r = Tokenizer::from_str("1r3 10r0").parse();
+    assert_eq!(
+        r.unwrap_err(),
+        TokenizerError::BaseError { position: 4, msg: String::from("Zero denominator") }
+    );
+    let r = Tokenizer::from_str("22r7.").parse().unwrap();
+    assert!(matches!(r[0], Token::Rational(22, 7)));
+}
+
+#[test]
+fn it_makes_quoted_tokens() {
+    let mut tokenizer = Tokenizer::from_str("f('Hello world', 'it''s\\n', \"a \\\"b\\\"\").");
+    let r: Vec<Token> = tokenizer
+        .parse()
+        .unwrap()
+        .into_iter()
+        .filter(|t| !matches!(t, Token::Whitespace(_)))
+        .collect();
+    assert!(matches!(&r[2], Token::Constant(s) if s == "Hello world"));
+    assert!(matches!(&r[4], Token::Constant(s) if s == "it's\n"));
+    assert!(matches!(&r[6], Token::Str(s) if s == "a \"b\""));
+    let r = Tokenizer::from_str("'open").parse();
+    assert_eq!(
+        r.unwrap_err(),
+        TokenizerError::BaseError { position: 0, msg: String::from("Unterminated quoted") }
+    );
+}
+
+#[test]
+fn it_skips_comments() {
+    let mut tokenizer = Tokenizer::from_str("% heading\np(a).% one\n/* a\n * b */ q(b). % done");
+    let r: Vec<Token> = tokenizer
+        .parse()
+        .unwrap()
+        .into_iter()
+        .filter(|t| !matches!(t, Token::Whitespace(_)))
+        .collect();
+    assert_eq!(r.len(), 10);
+    assert!(matches!(&r[0], Token::Constant(s) if s == "p"));
+    assert!(matches!(&r[5], Token::Constant(s) if s == "q"));
+    let r = Tokenizer::from_str("p. /* open").parse();
+    assert_eq!(
+        r.unwrap_err(),
+        TokenizerError::BaseError { position: 3, msg: String::from("Unterminated comment") }
+    );
+}
+
+#[test]
+fn it_recovers_after_bad_clauses() {
+    let (tokens, errors) = Tokenizer::from_str("p(a).\nq('b).\nr(c).\n").parse_recovering();
+    let r: Vec<(Token, usize)> = tokens.into_iter().filter(|(t, _)| !matches!(t, Token::Whitespace(_))).collect();
+    assert_eq!(r.len(), 10);
+    assert!(matches!(&r[5], (Token::Constant(s), 3) if s == "r"));
+    assert_eq!(errors, vec![TokenizerError::BaseError { position: 8, msg: String::from("Unterminated quoted") }]);
+}
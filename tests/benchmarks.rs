@@ -0,0 +1,34 @@
+use std::time::Instant;
+
+use prolog_interpretator::Runner;
+
+// Run with `cargo test --release --test benchmarks -- --ignored --nocapture`.
+// nrev30 runs at about 3.3M LIPS there. Each list cell is a reference
+// counted term on the Rust heap, and allocating and freeing the cells is
+// most of what is left; going faster needs a term store of the machine's own.
+
+const NREV: &str = "
+app([], L, L).
+app([H|T], L, [H|R]) :- app(T, L, R).
+
+nrev([], []).
+nrev([H|T], R) :- nrev(T, RT), app(RT, [H], R).
+
+bench(0, _) :- !.
+bench(N, L) :- nrev(L, _), M is N - 1, bench(M, L).
+";
+
+#[test]
+#[ignore]
+fn nrev30() {
+  let iterations = 5000;
+  let query = format!("numlist(1, 30, L), bench({}, L), nrev(L, R).", iterations);
+  let mut runner = Runner::from_source(NREV, &query).unwrap_or_else(|e| panic!("{:?}", e));
+  let start = Instant::now();
+  let answer = runner.next_solution().unwrap().expect("nrev30 failed");
+  let elapsed = start.elapsed();
+  assert!(answer["R"].to_string().starts_with("[30,29,28,"));
+  // naive reverse of 30 elements takes 496 logical inferences
+  let lips = 496.0 * iterations as f64 / elapsed.as_secs_f64();
+  println!("nrev30 x {}: {:?}, {:.0} LIPS", iterations, elapsed, lips);
+}
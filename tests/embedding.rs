@@ -0,0 +1,41 @@
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+use prolog_interpretator::{ErrorKind, Runner};
+
+#[derive(Clone, Default)]
+struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Buffer {
+  fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+    self.0.borrow_mut().extend_from_slice(bytes);
+    Ok(bytes.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+#[test]
+fn it_redirects_user_output() {
+  let program = "greet(Name) :- format(\"hello ~w~n\", [Name]).";
+  let mut runner = Runner::from_source(program, "member(X, [world, there]), greet(X).").unwrap_or_else(|e| panic!("{:?}", e));
+  let output = Buffer::default();
+  runner.set_user_output(Box::new(output.clone()));
+  let mut answers = Vec::new();
+  while let Some(answer) = runner.next_solution().unwrap() {
+    answers.push(answer["X"].to_string());
+  }
+  assert_eq!(answers, vec!["world", "there"]);
+  assert_eq!(String::from_utf8(output.0.borrow().clone()).unwrap(), "hello world\nhello there\n");
+}
+
+#[test]
+fn it_reports_syntax_errors_in_the_source() {
+  let error = |program: &str, query: &str| match Runner::from_source(program, query) {
+    Ok(_) => panic!("{} {} parsed", program, query),
+    Err(error) => error.kind,
+  };
+  assert!(matches!(error("p(1).\np(.", "p(X)."), ErrorKind::Syntax { message } if message.starts_with("program:2:")));
+  assert!(matches!(error("p(1).", "p(X). p(Y)."), ErrorKind::Syntax { .. }));
+}